@@ -0,0 +1,11 @@
+-- CENTRABIO R&D NEXUS - Plot Replication & Factor Levels
+-- Lets multi-factor designs (split-plot, factorial) map each plot to its
+-- replication and factor levels so they can be analysed from stored data.
+
+ALTER TABLE experimental_blocks
+    ADD COLUMN replication INTEGER,                       -- Ulangan / RCBD block number (1-based)
+    ADD COLUMN factor_levels JSONB DEFAULT '{}'::jsonb;   -- Level of each factor on this plot
+    -- Structure: {"main_plot": "N1", "sub_plot": "V2"} for split-plot designs,
+    --            {"N": "N1", "K": "K0"} for factorial designs
+
+CREATE INDEX idx_blocks_replication ON experimental_blocks(project_id, replication);
@@ -0,0 +1,15 @@
+-- CENTRABIO R&D NEXUS - Field Layout Randomization
+-- Keeps the seed and inputs of each generated plot layout so the
+-- randomization can be reproduced and audited.
+
+CREATE TABLE field_layouts (
+    project_id UUID PRIMARY KEY REFERENCES projects(id) ON DELETE CASCADE,
+    experiment_design experiment_design NOT NULL,
+    seed BIGINT NOT NULL,
+    replications INTEGER NOT NULL,
+    units_per_plot INTEGER NOT NULL,
+    treatments JSONB NOT NULL,                            -- Treatments in input order
+    -- Structure: [{"formula_id": "...", "description": "F1", "is_control": false, "factor_levels": {}}]
+    generated_by UUID NOT NULL REFERENCES users(id),
+    generated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,8 @@
+-- CENTRABIO R&D NEXUS - Disease / Pest Scoring Scales
+-- Describes how a pest or disease score maps to severity so disease
+-- severity index, incidence and AUDPC can be derived from stored scores.
+
+ALTER TABLE monitoring_parameters
+    ADD COLUMN severity_scale JSONB;                      -- NULL: scale taken from min_value / max_value
+    -- Structure: {"max_class": 5, "healthy_class": 0}                    McKinney index, score / max class
+    --            {"max_class": 11, "class_midpoints": [0, 1.2, 4.7, ...]} % severity per class (Horsfall-Barratt)
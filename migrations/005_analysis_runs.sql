@@ -0,0 +1,90 @@
+-- CENTRABIO R&D NEXUS - Versioned Analysis Runs
+-- Every stored analysis keeps the engine version and a hash of the data it
+-- read, and is marked stale as soon as that data changes. Runs on values
+-- posted with the request read no project data and are never stale.
+
+ALTER TABLE analysis_results
+    ADD COLUMN version INTEGER NOT NULL DEFAULT 1,        -- Run number per project and analysis type
+    ADD COLUMN engine_version VARCHAR(50),
+    ADD COLUMN data_hash VARCHAR(64),                     -- SHA-256 of the monitoring data snapshot; NULL for request-body input
+    ADD COLUMN related_project_ids UUID[] NOT NULL DEFAULT '{}', -- Further projects read by cross-project runs
+    ADD COLUMN parameter_ids UUID[] NOT NULL DEFAULT '{}', -- Parameters read; empty means all
+    ADD COLUMN session_ids UUID[] NOT NULL DEFAULT '{}',   -- Sessions read; empty means all
+    ADD COLUMN is_stale BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN stale_since TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN invalidated_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN invalidated_by UUID REFERENCES users(id);
+
+-- Number existing runs before versions become unique
+UPDATE analysis_results ar
+SET version = numbered.version
+FROM (
+    SELECT id, ROW_NUMBER() OVER (PARTITION BY project_id, analysis_type ORDER BY generated_at, id) AS version
+    FROM analysis_results
+) numbered
+WHERE ar.id = numbered.id;
+
+ALTER TABLE analysis_results
+    ADD CONSTRAINT analysis_results_version_unique UNIQUE (project_id, analysis_type, version);
+
+CREATE INDEX idx_analysis_results_project ON analysis_results(project_id, analysis_type, generated_at DESC);
+
+-- Monitoring data written after a run makes the runs that read it stale.
+-- Writers share-lock the project row until they commit, and storing a run
+-- takes it exclusively: a write either lands before the run's hash is
+-- compared or finds the stored run here.
+CREATE OR REPLACE FUNCTION mark_analysis_results_stale()
+RETURNS TRIGGER AS $$
+DECLARE
+    changed monitoring_data%ROWTYPE;
+    changed_project UUID;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        changed := OLD;
+    ELSE
+        changed := NEW;
+    END IF;
+
+    SELECT ms.project_id INTO changed_project FROM monitoring_sessions ms WHERE ms.id = changed.session_id;
+    PERFORM 1 FROM projects WHERE id = changed_project FOR SHARE;
+
+    UPDATE analysis_results ar
+    SET is_stale = TRUE, stale_since = CURRENT_TIMESTAMP
+    WHERE (ar.project_id = changed_project OR changed_project = ANY(ar.related_project_ids))
+    AND NOT ar.is_stale
+    AND ar.data_hash IS NOT NULL
+    AND (cardinality(ar.parameter_ids) = 0 OR changed.parameter_id = ANY(ar.parameter_ids))
+    AND (cardinality(ar.session_ids) = 0 OR changed.session_id = ANY(ar.session_ids));
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER monitoring_data_analysis_stale
+    AFTER INSERT OR DELETE OR UPDATE OF numeric_value, is_outlier ON monitoring_data
+    FOR EACH ROW EXECUTE FUNCTION mark_analysis_results_stale();
+
+-- Excluding or re-activating a unit changes which rows every analysis uses
+CREATE OR REPLACE FUNCTION mark_unit_analysis_results_stale()
+RETURNS TRIGGER AS $$
+DECLARE
+    changed_project UUID;
+BEGIN
+    SELECT eb.project_id INTO changed_project FROM experimental_blocks eb WHERE eb.id = NEW.block_id;
+    PERFORM 1 FROM projects WHERE id = changed_project FOR SHARE;
+
+    UPDATE analysis_results ar
+    SET is_stale = TRUE, stale_since = CURRENT_TIMESTAMP
+    WHERE (ar.project_id = changed_project OR changed_project = ANY(ar.related_project_ids))
+    AND NOT ar.is_stale
+    AND ar.data_hash IS NOT NULL;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER experimental_units_analysis_stale
+    AFTER UPDATE OF is_active, excluded_reason ON experimental_units
+    FOR EACH ROW
+    WHEN (OLD.is_active IS DISTINCT FROM NEW.is_active OR OLD.excluded_reason IS DISTINCT FROM NEW.excluded_reason)
+    EXECUTE FUNCTION mark_unit_analysis_results_stale();
@@ -0,0 +1,9 @@
+-- CENTRABIO R&D NEXUS - Outlier Flag Source
+-- The automatic scan may clear flags it set itself once a value no longer
+-- stands out; flags set by a reviewer are never touched by the scan.
+
+ALTER TABLE monitoring_data
+    ADD COLUMN outlier_source VARCHAR(20);                -- scan, manual; NULL when not flagged
+
+-- Existing flags cannot be attributed, so keep them
+UPDATE monitoring_data SET outlier_source = 'manual' WHERE is_outlier;
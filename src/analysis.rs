@@ -1,4 +1,5 @@
 use crate::config::Settings;
+use crate::diagnostics::AnovaDiagnostics;
 use crate::errors::AppError;
 use crate::models::*;
 use crate::services::ProjectService;
@@ -10,10 +11,17 @@ use async_openai::{
     },
     Client,
 };
+use nalgebra::{DMatrix, DVector};
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
-use statrs::distribution::{ContinuousCDF, FisherSnedecor, StudentsT};
+use sqlx::{PgConnection, PgPool};
+use statrs::distribution::{ChiSquared, ContinuousCDF, FisherSnedecor, Normal, StudentsT};
+use statrs::function::beta::beta_reg;
+use statrs::function::gamma::ln_gamma;
 use statrs::statistics::{Data, Distribution, Max, Min, OrderStatistics};
 use std::collections::HashMap;
 use uuid::Uuid;
@@ -141,21 +149,26 @@ impl StatisticalAnalysis {
         // Coefficient of determination (R²)
         let r_squared = if sst > 0.0 { ssb / sst } else { 0.0 };
 
+        let source_between = AnovaSource {
+            ss: ssb,
+            df: df_between as i32,
+            ms: msb,
+            f: Some(f_statistic),
+            p: Some(p_value),
+        };
+        let source_within = AnovaSource {
+            ss: ssw,
+            df: df_within as i32,
+            ms: msw,
+            f: None,
+            p: None,
+        };
+
         AnovaResult {
-            source_between: AnovaSource {
-                ss: ssb,
-                df: df_between as i32,
-                ms: msb,
-                f: Some(f_statistic),
-                p: Some(p_value),
-            },
-            source_within: AnovaSource {
-                ss: ssw,
-                df: df_within as i32,
-                ms: msw,
-                f: None,
-                p: None,
-            },
+            effect_size: Self::effect_sizes(&source_between, &source_within, sst),
+            mean_intervals: Self::mean_intervals(&group_means, &group_sizes, msw, df_within),
+            source_between,
+            source_within,
             source_total: AnovaSource {
                 ss: sst,
                 df: df_total as i32,
@@ -169,41 +182,60 @@ impl StatisticalAnalysis {
             group_means,
             group_sizes,
             grand_mean,
+            diagnostics: None,
         }
     }
 
-    /// Two-way ANOVA for factorial experiments (simplified)
+    /// Two-way ANOVA for factorial experiments.
+    ///
+    /// Sums of squares come from the general linear model: Type III when every
+    /// cell has data, Type II when whole cells are empty, so factorial trials
+    /// with lost plots still give correct F-tests.
     pub fn two_way_anova(
         data: &HashMap<(usize, usize), Vec<f64>>,
         factor_a_levels: usize,
         factor_b_levels: usize,
     ) -> TwoWayAnovaResult {
-        let mut grand_sum = 0.0;
-        let mut n_total = 0;
         let mut factor_a_sums: Vec<f64> = vec![0.0; factor_a_levels];
         let mut factor_b_sums: Vec<f64> = vec![0.0; factor_b_levels];
         let mut factor_a_n: Vec<usize> = vec![0; factor_a_levels];
         let mut factor_b_n: Vec<usize> = vec![0; factor_b_levels];
         let mut cell_means: HashMap<(usize, usize), f64> = HashMap::new();
 
-        // Calculate sums
+        let mut model = GlmModel {
+            response: Vec::new(),
+            factors: vec![
+                GlmFactor { name: "A".to_string(), levels: Vec::new() },
+                GlmFactor { name: "B".to_string(), levels: Vec::new() },
+            ],
+            covariates: Vec::new(),
+            terms: vec![
+                vec!["A".to_string()],
+                vec!["B".to_string()],
+                vec!["A".to_string(), "B".to_string()],
+            ],
+        };
+
+        // Calculate sums and collect observations for the model
         for ((a, b), values) in data {
             let sum: f64 = values.iter().sum();
             let n = values.len();
-            let mean = if n > 0 { sum / n as f64 } else { 0.0 };
-            
-            cell_means.insert((*a, *b), mean);
-            grand_sum += sum;
-            n_total += n;
+            if n > 0 {
+                cell_means.insert((*a, *b), sum / n as f64);
+            }
             factor_a_sums[*a] += sum;
             factor_b_sums[*b] += sum;
             factor_a_n[*a] += n;
             factor_b_n[*b] += n;
-        }
 
-        let grand_mean = if n_total > 0 { grand_sum / n_total as f64 } else { 0.0 };
+            for value in values {
+                model.response.push(*value);
+                model.factors[0].levels.push(a.to_string());
+                model.factors[1].levels.push(b.to_string());
+            }
+        }
 
-        // Calculate factor means
+        // Observed marginal means
         let factor_a_means: Vec<f64> = factor_a_sums
             .iter()
             .zip(factor_a_n.iter())
@@ -216,94 +248,334 @@ impl StatisticalAnalysis {
             .map(|(sum, n)| if *n > 0 { sum / *n as f64 } else { 0.0 })
             .collect();
 
-        // Calculate SS for Factor A
-        let n_per_a: usize = factor_a_n.iter().sum::<usize>() / factor_a_levels;
-        let ss_a: f64 = factor_a_means
+        // Type III needs every cell; with empty cells fall back to Type II
+        let observed_a: Vec<usize> = (0..factor_a_levels).filter(|a| factor_a_n[*a] > 0).collect();
+        let observed_b: Vec<usize> = (0..factor_b_levels).filter(|b| factor_b_n[*b] > 0).collect();
+        let all_cells = observed_a
             .iter()
-            .map(|mean| n_per_a as f64 * (mean - grand_mean).powi(2))
-            .sum();
-
-        // Calculate SS for Factor B
-        let n_per_b: usize = factor_b_n.iter().sum::<usize>() / factor_b_levels;
-        let ss_b: f64 = factor_b_means
-            .iter()
-            .map(|mean| n_per_b as f64 * (mean - grand_mean).powi(2))
-            .sum();
+            .all(|a| observed_b.iter().all(|b| cell_means.contains_key(&(*a, *b))));
+
+        let glm = Self::glm(&model);
+        let source = |i: usize| {
+            glm.terms
+                .get(i)
+                .map(|t| if all_cells { t.type_iii.clone() } else { t.type_ii.clone() })
+                .unwrap_or(AnovaSource {
+                    ss: 0.0,
+                    df: 0,
+                    ms: 0.0,
+                    f: None,
+                    p: None,
+                })
+        };
 
-        // Calculate Total SS and Error SS
-        let mut ss_total = 0.0;
-        for ((_, _), values) in data {
-            for value in values {
-                ss_total += (value - grand_mean).powi(2);
-            }
+        let (factor_a, factor_b, interaction) = (source(0), source(1), source(2));
+        TwoWayAnovaResult {
+            factor_a_effect: Self::effect_sizes(&factor_a, &glm.error, glm.total.ss),
+            factor_b_effect: Self::effect_sizes(&factor_b, &glm.error, glm.total.ss),
+            interaction_effect: Self::effect_sizes(&interaction, &glm.error, glm.total.ss),
+            factor_a,
+            factor_b,
+            interaction,
+            error: glm.error,
+            total: glm.total,
+            factor_a_means,
+            factor_b_means,
+            cell_means: cell_means.into_iter().collect(),
         }
+    }
 
-        // Calculate interaction SS (simplified - cell means method)
-        let r = data.values().next().map(|v| v.len()).unwrap_or(1); // replications
-        let mut ss_ab = 0.0;
-        for a in 0..factor_a_levels {
-            for b in 0..factor_b_levels {
-                if let Some(&cell_mean) = cell_means.get(&(a, b)) {
-                    let expected = factor_a_means[a] + factor_b_means[b] - grand_mean;
-                    ss_ab += r as f64 * (cell_mean - expected).powi(2);
+    /// Randomized complete block (RAK) ANOVA.
+    ///
+    /// `data[t][b]` holds the plot value of treatment `t` in block `b`; `None`
+    /// marks a missing plot. The additive model is fitted by least squares on
+    /// the observed plots, so treatment SS is adjusted for blocks and the
+    /// error df loses one degree of freedom per missing plot.
+    ///
+    /// Missing plots are estimated as `mu + tau_i + beta_j` of that fit. For a
+    /// single missing plot this is Yates' (tT + bB - G) / ((t-1)(b-1)); for
+    /// several it is the converged iterative Yates solution, which is also
+    /// what Bartlett's covariance method gives. The sums of squares are the
+    /// exact least-squares ones, so they need no Yates bias correction.
+    pub fn rcbd_anova(data: &[Vec<Option<f64>>]) -> RcbdAnovaResult {
+        let t = data.len();
+        let b = data.iter().map(|row| row.len()).max().unwrap_or(0);
+        let cell = |i: usize, j: usize| data[i].get(j).copied().flatten();
+
+        let mut observed = Vec::new();
+        let mut missing_plots = Vec::new();
+        for i in 0..t {
+            for j in 0..b {
+                match cell(i, j) {
+                    Some(y) => observed.push((i, j, y)),
+                    None => missing_plots.push((i, j)),
                 }
             }
         }
 
-        let ss_error = ss_total - ss_a - ss_b - ss_ab;
+        let n = observed.len();
+        let grand_mean = if n > 0 {
+            observed.iter().map(|(_, _, y)| y).sum::<f64>() / n as f64
+        } else {
+            0.0
+        };
 
-        // Degrees of freedom
-        let df_a = (factor_a_levels - 1) as f64;
-        let df_b = (factor_b_levels - 1) as f64;
-        let df_ab = df_a * df_b;
-        let df_error = (n_total - factor_a_levels * factor_b_levels) as f64;
-        let df_total = (n_total - 1) as f64;
+        let (mu, tau, beta) = Self::fit_additive(&observed, t, b);
 
-        // Mean squares
-        let ms_a = if df_a > 0.0 { ss_a / df_a } else { 0.0 };
-        let ms_b = if df_b > 0.0 { ss_b / df_b } else { 0.0 };
-        let ms_ab = if df_ab > 0.0 { ss_ab / df_ab } else { 0.0 };
+        let ss_total: f64 = observed.iter().map(|(_, _, y)| (y - grand_mean).powi(2)).sum();
+        let ss_error: f64 = observed
+            .iter()
+            .map(|&(i, j, y)| (y - (mu + tau[i] + beta[j])).powi(2))
+            .sum();
+
+        // Error of the blocks-only model, used to adjust treatments for blocks
+        let mut block_sums = vec![0.0; b];
+        let mut block_n = vec![0usize; b];
+        for &(_, j, y) in &observed {
+            block_sums[j] += y;
+            block_n[j] += 1;
+        }
+        let ss_error_blocks_only: f64 = observed
+            .iter()
+            .map(|&(_, j, y)| (y - block_sums[j] / block_n[j] as f64).powi(2))
+            .sum();
+
+        let ss_block = (ss_total - ss_error_blocks_only).max(0.0);
+        let ss_treatment = (ss_error_blocks_only - ss_error).max(0.0);
+
+        let df_treatment = t.saturating_sub(1) as f64;
+        let df_block = b.saturating_sub(1) as f64;
+        let df_total = n.saturating_sub(1) as f64;
+        let df_error = (df_total - df_treatment - df_block).max(0.0);
+
+        let ms_treatment = if df_treatment > 0.0 { ss_treatment / df_treatment } else { 0.0 };
+        let ms_block = if df_block > 0.0 { ss_block / df_block } else { 0.0 };
         let ms_error = if df_error > 0.0 { ss_error / df_error } else { 0.0 };
 
-        // F-statistics
-        let f_a = if ms_error > 0.0 { ms_a / ms_error } else { 0.0 };
-        let f_b = if ms_error > 0.0 { ms_b / ms_error } else { 0.0 };
-        let f_ab = if ms_error > 0.0 { ms_ab / ms_error } else { 0.0 };
+        let f_treatment = if ms_error > 0.0 { ms_treatment / ms_error } else { 0.0 };
+        let f_block = if ms_error > 0.0 { ms_block / ms_error } else { 0.0 };
+        let p_treatment = Self::f_p_value(f_treatment, df_treatment, df_error);
+        let p_block = Self::f_p_value(f_block, df_block, df_error);
+
+        let cv_percent = if grand_mean != 0.0 {
+            ms_error.sqrt() / grand_mean * 100.0
+        } else {
+            0.0
+        };
+
+        // Efficiency relative to a CRD, with the error-df precision correction
+        let relative_efficiency = if ms_error > 0.0 {
+            let df_crd = df_block + df_error;
+            let re = (df_block * ms_block + (df_treatment + df_error) * ms_error)
+                / ((df_block + df_treatment + df_error) * ms_error);
+            let precision = ((df_error + 1.0) * (df_crd + 3.0)) / ((df_error + 3.0) * (df_crd + 1.0));
+            re * precision
+        } else {
+            0.0
+        };
 
-        // P-values
-        let p_a = Self::f_p_value(f_a, df_a, df_error);
-        let p_b = Self::f_p_value(f_b, df_b, df_error);
-        let p_ab = Self::f_p_value(f_ab, df_ab, df_error);
+        let mut treatment_sizes = vec![0usize; t];
+        for &(i, _, _) in &observed {
+            treatment_sizes[i] += 1;
+        }
 
-        TwoWayAnovaResult {
-            factor_a: AnovaSource {
-                ss: ss_a,
-                df: df_a as i32,
-                ms: ms_a,
-                f: Some(f_a),
-                p: Some(p_a),
-            },
-            factor_b: AnovaSource {
-                ss: ss_b,
-                df: df_b as i32,
-                ms: ms_b,
-                f: Some(f_b),
-                p: Some(p_b),
-            },
-            interaction: AnovaSource {
-                ss: ss_ab,
-                df: df_ab as i32,
-                ms: ms_ab,
-                f: Some(f_ab),
-                p: Some(p_ab),
+        let estimated_plots = missing_plots
+            .iter()
+            .map(|&(i, j)| EstimatedPlot {
+                treatment: i,
+                block: j,
+                value: mu + tau[i] + beta[j],
+            })
+            .collect();
+
+        let treatment = AnovaSource {
+            ss: ss_treatment,
+            df: df_treatment as i32,
+            ms: ms_treatment,
+            f: Some(f_treatment),
+            p: Some(p_treatment),
+        };
+        let error = AnovaSource {
+            ss: ss_error,
+            df: df_error as i32,
+            ms: ms_error,
+            f: None,
+            p: None,
+        };
+        let treatment_means: Vec<f64> = tau.iter().map(|ti| mu + ti).collect();
+
+        RcbdAnovaResult {
+            effect_size: Self::effect_sizes(&treatment, &error, ss_total),
+            mean_intervals: Self::mean_intervals(&treatment_means, &treatment_sizes, ms_error, df_error),
+            treatment,
+            block: AnovaSource {
+                ss: ss_block,
+                df: df_block as i32,
+                ms: ms_block,
+                f: Some(f_block),
+                p: Some(p_block),
             },
-            error: AnovaSource {
-                ss: ss_error,
-                df: df_error as i32,
-                ms: ms_error,
+            error,
+            total: AnovaSource {
+                ss: ss_total,
+                df: df_total as i32,
+                ms: 0.0,
                 f: None,
                 p: None,
             },
+            cv_percent,
+            relative_efficiency,
+            r_squared: if ss_total > 0.0 { 1.0 - ss_error / ss_total } else { 0.0 },
+            is_significant_05: p_treatment < 0.05,
+            is_significant_01: p_treatment < 0.01,
+            treatment_means,
+            block_means: beta.iter().map(|bj| mu + bj).collect(),
+            treatment_sizes,
+            grand_mean,
+            missing_plots,
+            estimated_plots,
+        }
+    }
+
+    /// Split-plot ANOVA with main plots arranged in randomized complete blocks.
+    ///
+    /// `data[a][b][r]` holds the value of main-plot level `a`, sub-plot level `b`
+    /// in replication `r` (balanced layout). Main-plot effects are tested against
+    /// Error (a), sub-plot and interaction effects against Error (b).
+    pub fn split_plot_anova(data: &[Vec<Vec<f64>>]) -> SplitPlotAnovaResult {
+        let a = data.len();
+        let b = data.first().map(|m| m.len()).unwrap_or(0);
+        let r = data
+            .first()
+            .and_then(|m| m.first())
+            .map(|s| s.len())
+            .unwrap_or(0);
+        let (af, bf, rf) = (a as f64, b as f64, r as f64);
+        let n = af * bf * rf;
+
+        let grand_total: f64 = data.iter().flatten().flatten().sum();
+        let grand_mean = if n > 0.0 { grand_total / n } else { 0.0 };
+        let cf = if n > 0.0 { grand_total * grand_total / n } else { 0.0 };
+
+        let mut rep_totals = vec![0.0; r];
+        let mut main_totals = vec![0.0; a];
+        let mut sub_totals = vec![0.0; b];
+        let mut main_rep_totals = vec![vec![0.0; r]; a];
+        let mut cell_totals = vec![vec![0.0; b]; a];
+        let mut sum_sq = 0.0;
+
+        for (i, main) in data.iter().enumerate() {
+            for (j, sub) in main.iter().enumerate() {
+                for (k, &y) in sub.iter().enumerate() {
+                    rep_totals[k] += y;
+                    main_totals[i] += y;
+                    sub_totals[j] += y;
+                    main_rep_totals[i][k] += y;
+                    cell_totals[i][j] += y;
+                    sum_sq += y * y;
+                }
+            }
+        }
+
+        let sq_sum = |totals: &[f64], size: f64| -> f64 {
+            if size > 0.0 {
+                totals.iter().map(|t| t * t).sum::<f64>() / size
+            } else {
+                0.0
+            }
+        };
+
+        let ss_total = sum_sq - cf;
+        let ss_rep = sq_sum(&rep_totals, af * bf) - cf;
+        let ss_main = sq_sum(&main_totals, bf * rf) - cf;
+        let ss_main_rep = sq_sum(&main_rep_totals.concat(), bf) - cf;
+        let ss_error_a = (ss_main_rep - ss_rep - ss_main).max(0.0);
+        let ss_sub = sq_sum(&sub_totals, af * rf) - cf;
+        let ss_cells = sq_sum(&cell_totals.concat(), rf) - cf;
+        let ss_interaction = (ss_cells - ss_main - ss_sub).max(0.0);
+        let ss_error_b = (ss_total - ss_main_rep - ss_sub - ss_interaction).max(0.0);
+
+        let df_rep = rf - 1.0;
+        let df_main = af - 1.0;
+        let df_error_a = (rf - 1.0) * (af - 1.0);
+        let df_sub = bf - 1.0;
+        let df_interaction = (af - 1.0) * (bf - 1.0);
+        let df_error_b = af * (rf - 1.0) * (bf - 1.0);
+        let df_total = n - 1.0;
+
+        let mean_square = |ss: f64, df: f64| if df > 0.0 { ss / df } else { 0.0 };
+        let ms_error_a = mean_square(ss_error_a, df_error_a);
+        let ms_error_b = mean_square(ss_error_b, df_error_b);
+
+        let tested = |ss: f64, df: f64, ms_err: f64, df_err: f64| {
+            let ms = mean_square(ss, df);
+            let f = if ms_err > 0.0 { ms / ms_err } else { 0.0 };
+            AnovaSource {
+                ss,
+                df: df as i32,
+                ms,
+                f: Some(f),
+                p: Some(Self::f_p_value(f, df, df_err)),
+            }
+        };
+        let error = |ss: f64, df: f64| AnovaSource {
+            ss,
+            df: df as i32,
+            ms: mean_square(ss, df),
+            f: None,
+            p: None,
+        };
+
+        let cv = |ms: f64| if grand_mean != 0.0 { ms.sqrt() / grand_mean * 100.0 } else { 0.0 };
+
+        // Standard errors of a difference (Gomez & Gomez, Table 5.13)
+        let t_crit = |df: f64| {
+            if df > 0.0 {
+                StudentsT::new(0.0, 1.0, df)
+                    .map(|d| d.inverse_cdf(0.975))
+                    .unwrap_or(1.96)
+            } else {
+                0.0
+            }
+        };
+        let t_a = t_crit(df_error_a);
+        let t_b = t_crit(df_error_b);
+        let comparison = |description: &str, se: f64, t: f64| SplitPlotComparison {
+            description: description.to_string(),
+            std_error: se,
+            t_value: t,
+            lsd_05: t * se,
+        };
+
+        let se_main = if rf * bf > 0.0 { (2.0 * ms_error_a / (rf * bf)).sqrt() } else { 0.0 };
+        let se_sub = if rf * af > 0.0 { (2.0 * ms_error_b / (rf * af)).sqrt() } else { 0.0 };
+        let se_sub_within_main = if rf > 0.0 { (2.0 * ms_error_b / rf).sqrt() } else { 0.0 };
+        let pooled = (bf - 1.0) * ms_error_b + ms_error_a;
+        let se_main_within_sub = if rf * bf > 0.0 { (2.0 * pooled / (rf * bf)).sqrt() } else { 0.0 };
+        // Weighted t-value for the pooled error (Cochran & Cox approximation)
+        let t_weighted = if pooled > 0.0 {
+            ((bf - 1.0) * ms_error_b * t_b + ms_error_a * t_a) / pooled
+        } else {
+            0.0
+        };
+
+        let means = |totals: &[f64], size: f64| -> Vec<f64> {
+            totals
+                .iter()
+                .map(|t| if size > 0.0 { t / size } else { 0.0 })
+                .collect()
+        };
+
+        let main = tested(ss_main, df_main, ms_error_a, df_error_a);
+        let p_main = main.p.unwrap_or(1.0);
+
+        SplitPlotAnovaResult {
+            replication: tested(ss_rep, df_rep, ms_error_a, df_error_a),
+            main_plot: main,
+            error_a: error(ss_error_a, df_error_a),
+            sub_plot: tested(ss_sub, df_sub, ms_error_b, df_error_b),
+            interaction: tested(ss_interaction, df_interaction, ms_error_b, df_error_b),
+            error_b: error(ss_error_b, df_error_b),
             total: AnovaSource {
                 ss: ss_total,
                 df: df_total as i32,
@@ -311,10 +583,86 @@ impl StatisticalAnalysis {
                 f: None,
                 p: None,
             },
-            factor_a_means,
-            factor_b_means,
-            cell_means: cell_means.into_iter().collect(),
+            cv_a_percent: cv(ms_error_a),
+            cv_b_percent: cv(ms_error_b),
+            is_main_significant_05: p_main < 0.05,
+            main_plot_means: means(&main_totals, bf * rf),
+            sub_plot_means: means(&sub_totals, af * rf),
+            cell_means: cell_totals.iter().map(|row| means(row, rf)).collect(),
+            grand_mean,
+            comparisons: vec![
+                comparison("Two main-plot means", se_main, t_a),
+                comparison("Two sub-plot means", se_sub, t_b),
+                comparison("Two sub-plot means at the same main-plot level", se_sub_within_main, t_b),
+                comparison(
+                    "Two main-plot means at the same or different sub-plot levels",
+                    se_main_within_sub,
+                    t_weighted,
+                ),
+            ],
+        }
+    }
+
+    /// Least-squares fit of the additive model `y = mu + tau_i + beta_j` on the
+    /// observed cells of a two-way layout (sum-to-zero effects).
+    fn fit_additive(observed: &[(usize, usize, f64)], t: usize, b: usize) -> (f64, Vec<f64>, Vec<f64>) {
+        let mut mu = if observed.is_empty() {
+            0.0
+        } else {
+            observed.iter().map(|(_, _, y)| y).sum::<f64>() / observed.len() as f64
+        };
+        let mut tau = vec![0.0; t];
+        let mut beta = vec![0.0; b];
+
+        // Backfitting converges to the least-squares solution for connected layouts
+        for _ in 0..1000 {
+            let mut sums = vec![0.0; t];
+            let mut counts = vec![0usize; t];
+            for &(i, j, y) in observed {
+                sums[i] += y - mu - beta[j];
+                counts[i] += 1;
+            }
+            let new_tau: Vec<f64> = sums
+                .iter()
+                .zip(counts.iter())
+                .map(|(s, c)| if *c > 0 { s / *c as f64 } else { 0.0 })
+                .collect();
+
+            let mut sums = vec![0.0; b];
+            let mut counts = vec![0usize; b];
+            for &(i, j, y) in observed {
+                sums[j] += y - mu - new_tau[i];
+                counts[j] += 1;
+            }
+            let new_beta: Vec<f64> = sums
+                .iter()
+                .zip(counts.iter())
+                .map(|(s, c)| if *c > 0 { s / *c as f64 } else { 0.0 })
+                .collect();
+
+            let change = new_tau
+                .iter()
+                .zip(tau.iter())
+                .chain(new_beta.iter().zip(beta.iter()))
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f64::max);
+
+            tau = new_tau;
+            beta = new_beta;
+
+            // Re-centre effects so they sum to zero and absorb the shift into mu
+            let tau_mean = tau.iter().sum::<f64>() / t.max(1) as f64;
+            let beta_mean = beta.iter().sum::<f64>() / b.max(1) as f64;
+            tau.iter_mut().for_each(|v| *v -= tau_mean);
+            beta.iter_mut().for_each(|v| *v -= beta_mean);
+            mu += tau_mean + beta_mean;
+
+            if change < 1e-12 {
+                break;
+            }
         }
+
+        (mu, tau, beta)
     }
 
     fn f_p_value(f: f64, df1: f64, df2: f64) -> f64 {
@@ -336,11 +684,12 @@ impl StatisticalAnalysis {
                 mean_difference: 0.0,
                 ci_lower: 0.0,
                 ci_upper: 0.0,
+                effect_size: StandardizedDifference::default(),
                 is_significant: false,
             };
         }
 
-        let (t_stat, df, mean_diff) = if paired {
+        let (t_stat, df, mean_diff, effect_size) = if paired {
             // Paired t-test
             let differences: Vec<f64> = group1
                 .iter()
@@ -356,7 +705,9 @@ impl StatisticalAnalysis {
                 / (n - 1.0);
             let se_d = (var_d / n).sqrt();
             let t = if se_d > 0.0 { mean_d / se_d } else { 0.0 };
-            (t, n - 1.0, mean_d)
+            // d_z: mean difference over the SD of the differences
+            let effect = Self::hedges_g(mean_d, var_d.sqrt(), 1.0 / n, n - 1.0, 2.0 * n);
+            (t, n - 1.0, mean_d, effect)
         } else {
             // Independent samples t-test (Welch's)
             let n1 = group1.len() as f64;
@@ -374,7 +725,10 @@ impl StatisticalAnalysis {
             let denom = ((var1 / n1).powi(2) / (n1 - 1.0)) + ((var2 / n2).powi(2) / (n2 - 1.0));
             let df = if denom > 0.0 { num / denom } else { n1 + n2 - 2.0 };
 
-            (t, df, mean1 - mean2)
+            let pooled_sd = (((n1 - 1.0) * var1 + (n2 - 1.0) * var2) / (n1 + n2 - 2.0)).sqrt();
+            let effect = Self::hedges_g(mean1 - mean2, pooled_sd, 1.0 / n1 + 1.0 / n2, n1 + n2 - 2.0, 2.0 * (n1 + n2));
+
+            (t, df, mean1 - mean2, effect)
         };
 
         // P-value (two-tailed)
@@ -406,6 +760,7 @@ impl StatisticalAnalysis {
             mean_difference: mean_diff,
             ci_lower,
             ci_upper,
+            effect_size,
             is_significant: p_value < 0.05,
         }
     }
@@ -447,6 +802,9 @@ impl StatisticalAnalysis {
                     t_statistic: t,
                     p_value,
                     lsd,
+                    ci_lower: mean_diff - lsd,
+                    ci_upper: mean_diff + lsd,
+                    effect_size: Self::anova_hedges_g(mean_diff, mse, n1, n2, df_error),
                     is_significant: mean_diff.abs() > lsd,
                 });
             }
@@ -454,6 +812,61 @@ impl StatisticalAnalysis {
 
         comparisons
     }
+
+    /// Variance explained by an ANOVA term. Omega² is the less biased
+    /// estimate and is floored at zero.
+    pub fn effect_sizes(effect: &AnovaSource, error: &AnovaSource, ss_total: f64) -> EffectSizes {
+        let omega = (effect.ss - effect.df as f64 * error.ms) / (ss_total + error.ms);
+        EffectSizes {
+            eta_squared: if ss_total > 0.0 { effect.ss / ss_total } else { 0.0 },
+            partial_eta_squared: if effect.ss + error.ss > 0.0 { effect.ss / (effect.ss + error.ss) } else { 0.0 },
+            omega_squared: if omega.is_finite() { omega.max(0.0) } else { 0.0 },
+        }
+    }
+
+    /// 95% confidence interval of each mean from the pooled error mean square.
+    pub fn mean_intervals(means: &[f64], sizes: &[usize], mse: f64, df_error: f64) -> Vec<ConfidenceInterval> {
+        let t_crit = StudentsT::new(0.0, 1.0, df_error)
+            .map(|d| d.inverse_cdf(0.975))
+            .unwrap_or(1.96);
+        means
+            .iter()
+            .zip(sizes)
+            .map(|(&mean, &n)| {
+                let margin = if n > 0 { t_crit * (mse / n as f64).sqrt() } else { 0.0 };
+                ConfidenceInterval {
+                    lower: mean - margin,
+                    upper: mean + margin,
+                }
+            })
+            .collect()
+    }
+
+    /// Hedges' g for a difference between two treatment means, standardized
+    /// by the root error mean square of the ANOVA.
+    pub fn anova_hedges_g(difference: f64, mse: f64, n1: f64, n2: f64, df_error: f64) -> StandardizedDifference {
+        if n1 <= 0.0 || n2 <= 0.0 {
+            return StandardizedDifference::default();
+        }
+        Self::hedges_g(difference, mse.sqrt(), 1.0 / n1 + 1.0 / n2, df_error, 2.0 * (n1 + n2))
+    }
+
+    /// Small-sample corrected standardized difference with a normal-theory
+    /// 95% CI: var(d) = `inverse_n` + d² / `two_n` (Hedges & Olkin, 1985).
+    fn hedges_g(difference: f64, sd: f64, inverse_n: f64, df: f64, two_n: f64) -> StandardizedDifference {
+        if sd.is_nan() || sd <= 0.0 || df <= 0.0 {
+            return StandardizedDifference::default();
+        }
+        let d = difference / sd;
+        let j = 1.0 - 3.0 / (4.0 * df - 1.0);
+        let g = j * d;
+        let se = j * (inverse_n + d * d / two_n).sqrt();
+        StandardizedDifference {
+            hedges_g: g,
+            ci_lower: g - 1.96 * se,
+            ci_upper: g + 1.96 * se,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -466,7 +879,11 @@ pub struct AnovaResult {
     pub is_significant_01: bool,
     pub group_means: Vec<f64>,
     pub group_sizes: Vec<usize>,
+    pub mean_intervals: Vec<ConfidenceInterval>, // 95%, pooled error
     pub grand_mean: f64,
+    pub effect_size: EffectSizes, // treatment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diagnostics: Option<AnovaDiagnostics>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -476,11 +893,69 @@ pub struct TwoWayAnovaResult {
     pub interaction: AnovaSource,
     pub error: AnovaSource,
     pub total: AnovaSource,
+    pub factor_a_effect: EffectSizes,
+    pub factor_b_effect: EffectSizes,
+    pub interaction_effect: EffectSizes,
     pub factor_a_means: Vec<f64>,
     pub factor_b_means: Vec<f64>,
     pub cell_means: Vec<((usize, usize), f64)>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RcbdAnovaResult {
+    pub treatment: AnovaSource,
+    pub block: AnovaSource,
+    pub error: AnovaSource,
+    pub total: AnovaSource,
+    pub cv_percent: f64,
+    pub relative_efficiency: f64, // vs. a completely randomized design
+    pub r_squared: f64,
+    pub is_significant_05: bool,
+    pub is_significant_01: bool,
+    pub treatment_means: Vec<f64>, // least-squares means, adjusted for blocks
+    pub block_means: Vec<f64>,
+    pub treatment_sizes: Vec<usize>, // observed plots only
+    pub mean_intervals: Vec<ConfidenceInterval>, // 95%, from the error mean square
+    pub effect_size: EffectSizes, // treatment, adjusted for blocks
+    pub grand_mean: f64,
+    pub missing_plots: Vec<(usize, usize)>, // (treatment, block)
+    pub estimated_plots: Vec<EstimatedPlot>, // values estimated, not observed
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EstimatedPlot {
+    pub treatment: usize,
+    pub block: usize,
+    pub value: f64, // Yates / Bartlett missing-plot estimate
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitPlotAnovaResult {
+    pub replication: AnovaSource,
+    pub main_plot: AnovaSource,
+    pub error_a: AnovaSource,
+    pub sub_plot: AnovaSource,
+    pub interaction: AnovaSource,
+    pub error_b: AnovaSource,
+    pub total: AnovaSource,
+    pub cv_a_percent: f64,
+    pub cv_b_percent: f64,
+    pub is_main_significant_05: bool,
+    pub main_plot_means: Vec<f64>,
+    pub sub_plot_means: Vec<f64>,
+    pub cell_means: Vec<Vec<f64>>, // [main][sub]
+    pub grand_mean: f64,
+    pub comparisons: Vec<SplitPlotComparison>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitPlotComparison {
+    pub description: String,
+    pub std_error: f64, // standard error of the difference
+    pub t_value: f64,
+    pub lsd_05: f64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnovaSource {
     pub ss: f64,
@@ -498,6 +973,7 @@ pub struct TTestResult {
     pub mean_difference: f64,
     pub ci_lower: f64,
     pub ci_upper: f64,
+    pub effect_size: StandardizedDifference, // d_z for paired samples
     pub is_significant: bool,
 }
 
@@ -510,538 +986,7216 @@ pub struct LSDComparison {
     pub t_statistic: f64,
     pub p_value: f64,
     pub lsd: f64,
+    pub ci_lower: f64, // 95% CI of the difference
+    pub ci_upper: f64,
+    pub effect_size: StandardizedDifference,
     pub is_significant: bool,
 }
 
-// ==============================================================================
-// AI ANALYSIS SERVICE
-// ==============================================================================
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffectSizes {
+    pub eta_squared: f64,         // SS effect / SS total
+    pub partial_eta_squared: f64, // SS effect / (SS effect + SS error)
+    pub omega_squared: f64,
+}
 
-pub struct AIAnalysisService {
-    client: Client<OpenAIConfig>,
-    model: String,
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct StandardizedDifference {
+    pub hedges_g: f64,
+    pub ci_lower: f64, // 95%
+    pub ci_upper: f64,
 }
 
-impl AIAnalysisService {
-    pub fn new(settings: &Settings) -> Option<Self> {
-        if settings.openai.api_key.is_empty() {
-            return None;
-        }
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ConfidenceInterval {
+    pub lower: f64,
+    pub upper: f64,
+}
 
-        let config = OpenAIConfig::new().with_api_key(&settings.openai.api_key);
-        let client = Client::with_config(config);
+// ==============================================================================
+// GENERAL LINEAR MODEL
+// ==============================================================================
 
-        Some(Self {
-            client,
-            model: settings.openai.model.clone(),
-        })
-    }
+/// A linear model with categorical factors and numeric covariates, one entry
+/// per observation in every vector.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlmModel {
+    pub response: Vec<f64>,
+    #[serde(default)]
+    pub factors: Vec<GlmFactor>,
+    #[serde(default)]
+    pub covariates: Vec<GlmCovariate>,
+    /// Model terms as lists of variable names, e.g. `[["N"], ["K"], ["N", "K"]]`.
+    /// Empty means covariate main effects followed by the full factorial of the factors.
+    #[serde(default)]
+    pub terms: Vec<Vec<String>>,
+}
 
-    pub async fn analyze_experiment_data(
-        &self,
-        pool: &PgPool,
-        project_id: Uuid,
-        analysis_type: &str,
-    ) -> Result<AIAnalysisResult, AppError> {
-        // Fetch project data
-        let project = ProjectService::get_by_id(pool, project_id).await?;
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlmFactor {
+    pub name: String,
+    pub levels: Vec<String>,
+}
 
-        // Fetch monitoring data summary
-        let data_summary = self.fetch_monitoring_summary(pool, project_id).await?;
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlmCovariate {
+    pub name: String,
+    pub values: Vec<f64>,
+}
 
-        // Build prompt based on analysis type
-        let prompt = self.build_analysis_prompt(&project, &data_summary, analysis_type);
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlmResult {
+    pub terms: Vec<GlmTerm>,
+    pub error: AnovaSource,
+    pub total: AnovaSource,
+    pub r_squared: f64,
+    pub cv_percent: f64,
+    pub grand_mean: f64,
+    pub n: usize,
+    pub rank: usize, // rank of the full design matrix, intercept included
+    pub least_squares_means: Vec<GlmFactorMeans>,
+}
 
-        // Call OpenAI API
-        let response = self.call_openai(&prompt).await?;
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlmTerm {
+    pub name: String,
+    pub type_i: AnovaSource,   // sequential, in model order
+    pub type_ii: AnovaSource,  // adjusted for all terms not containing this one
+    pub type_iii: AnovaSource, // adjusted for every other term
+}
 
-        Ok(AIAnalysisResult {
-            analysis_type: analysis_type.to_string(),
-            insights: response.insights,
-            recommendations: response.recommendations,
-            statistical_interpretation: response.statistical_interpretation,
-            confidence_level: response.confidence_level,
-            limitations: response.limitations,
-            next_steps: response.next_steps,
-        })
-    }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlmFactorMeans {
+    pub factor: String,
+    pub levels: Vec<String>,
+    pub means: Vec<f64>, // averaged over the other factors, covariates at their mean
+    pub sizes: Vec<usize>,
+}
 
-    async fn fetch_monitoring_summary(
-        &self,
-        pool: &PgPool,
-        project_id: Uuid,
-    ) -> Result<MonitoringSummary, AppError> {
-        // Fetch parameter statistics
-        let stats: Vec<(Option<String>, Option<String>, String, bool, Option<i64>, Option<f64>, Option<f64>, Option<f64>, Option<f64>)> = sqlx::query_as(
-            r#"
-            SELECT 
-                mp.name as parameter_name,
-                mp.code as parameter_code,
-                eb.block_code,
-                eb.is_control,
-                COUNT(md.id) as data_count,
-                AVG(md.numeric_value)::float8 as avg_value,
-                STDDEV(md.numeric_value)::float8 as std_dev,
-                MIN(md.numeric_value)::float8 as min_value,
-                MAX(md.numeric_value)::float8 as max_value
-            FROM monitoring_data md
-            JOIN experimental_units eu ON md.unit_id = eu.id
-            JOIN experimental_blocks eb ON eu.block_id = eb.id
-            JOIN monitoring_parameters mp ON md.parameter_id = mp.id
-            WHERE eb.project_id = $1
-            GROUP BY mp.name, mp.code, eb.block_code, eb.is_control
-            ORDER BY mp.code, eb.block_code
-            "#
-        )
-        .bind(project_id)
-        .fetch_all(pool)
-        .await
-        .map_err(|e| AppError::Database(e.to_string()))?;
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GlmVar {
+    Factor(usize),
+    Covariate(usize),
+}
 
-        let treatment_stats: Vec<TreatmentStats> = stats
+impl StatisticalAnalysis {
+    /// Fits a general linear model by least squares and reports Type I, II
+    /// and III sums of squares for each term.
+    ///
+    /// Factors use sum-to-zero (effect) coding, so Type III tests stay valid
+    /// for unbalanced data. Rank-deficient designs (empty cells) are handled
+    /// through the SVD, and each term's df is its gain in rank. Terms naming
+    /// unknown variables are ignored.
+    pub fn glm(model: &GlmModel) -> GlmResult {
+        let n = model.response.len();
+        let y = DVector::from_column_slice(&model.response);
+
+        // Factor levels in natural order (numeric when every label parses)
+        let factor_levels: Vec<Vec<String>> = model
+            .factors
             .iter()
-            .map(|row| TreatmentStats {
-                parameter_name: row.0.clone().unwrap_or_default(),
-                parameter_code: row.1.clone().unwrap_or_default(),
-                block_code: row.2.clone(),
-                is_control: row.3,
-                n: row.4.unwrap_or(0) as usize,
-                mean: row.5.unwrap_or(0.0),
-                std_dev: row.6.unwrap_or(0.0),
-                min: row.7.unwrap_or(0.0),
-                max: row.8.unwrap_or(0.0),
+            .map(|f| {
+                let mut levels = f.levels.clone();
+                levels.sort();
+                levels.dedup();
+                if levels.iter().all(|l| l.trim().parse::<f64>().is_ok()) {
+                    levels.sort_by(|a, b| {
+                        let (a, b) = (a.trim().parse::<f64>().unwrap_or(0.0), b.trim().parse::<f64>().unwrap_or(0.0));
+                        a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+                    });
+                }
+                levels
             })
             .collect();
-
+        let factor_index: Vec<Vec<usize>> = model
+            .factors
+            .iter()
+            .zip(factor_levels.iter())
+            .map(|(f, levels)| {
+                f.levels
+                    .iter()
+                    .map(|l| levels.iter().position(|x| x == l).unwrap_or(0))
+                    .collect()
+            })
+            .collect();
+        let level_counts: Vec<usize> = factor_levels.iter().map(|l| l.len()).collect();
+
+        let lookup = |name: &str| -> Option<GlmVar> {
+            model
+                .factors
+                .iter()
+                .position(|f| f.name == name)
+                .map(GlmVar::Factor)
+                .or_else(|| model.covariates.iter().position(|c| c.name == name).map(GlmVar::Covariate))
+        };
+
+        let terms: Vec<Vec<GlmVar>> = if model.terms.is_empty() {
+            let mut terms: Vec<Vec<GlmVar>> = (0..model.covariates.len()).map(|c| vec![GlmVar::Covariate(c)]).collect();
+            let k = model.factors.len();
+            let mut subsets: Vec<Vec<GlmVar>> = (1..(1usize << k))
+                .map(|mask| (0..k).filter(|i| mask & (1 << i) != 0).map(GlmVar::Factor).collect())
+                .collect();
+            subsets.sort_by_key(|s| s.len());
+            terms.extend(subsets);
+            terms
+        } else {
+            model
+                .terms
+                .iter()
+                .filter_map(|t| t.iter().map(|name| lookup(name)).collect::<Option<Vec<_>>>())
+                .filter(|t| !t.is_empty())
+                .collect()
+        };
+
+        let term_name = |term: &[GlmVar]| -> String {
+            term.iter()
+                .map(|v| match v {
+                    GlmVar::Factor(i) => model.factors[*i].name.clone(),
+                    GlmVar::Covariate(i) => model.covariates[*i].name.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join(" × ")
+        };
+
+        let covariate_means: Vec<f64> = model
+            .covariates
+            .iter()
+            .map(|c| if c.values.is_empty() { 0.0 } else { c.values.iter().sum::<f64>() / c.values.len() as f64 })
+            .collect();
+
+        // Full design matrix: intercept, then one column block per term
+        let mut blocks: Vec<Vec<usize>> = Vec::with_capacity(terms.len());
+        let mut width = 1;
+        for term in &terms {
+            let cols = Self::glm_term_columns(term, &level_counts, &vec![0; level_counts.len()], &covariate_means).len();
+            blocks.push((width..width + cols).collect());
+            width += cols;
+        }
+        let mut x = DMatrix::zeros(n, width);
+        for obs in 0..n {
+            let levels: Vec<usize> = factor_index.iter().map(|f| f.get(obs).copied().unwrap_or(0)).collect();
+            let covs: Vec<f64> = model.covariates.iter().map(|c| c.values.get(obs).copied().unwrap_or(0.0)).collect();
+            let row = Self::glm_design_row(&terms, &level_counts, &levels, &covs);
+            for (j, v) in row.into_iter().enumerate() {
+                x[(obs, j)] = v;
+            }
+        }
+
+        let fit = |selected: &[usize]| -> (f64, usize, DVector<f64>) {
+            let mut cols = vec![0];
+            for &t in selected {
+                cols.extend(blocks[t].iter().copied());
+            }
+            Self::glm_least_squares(&x.select_columns(cols.iter()), &y)
+        };
+
+        let all: Vec<usize> = (0..terms.len()).collect();
+        let (sse_full, rank_full, _) = fit(&all);
+        let (_, _, beta) = Self::glm_least_squares(&x, &y);
+
+        let grand_mean = if n > 0 { model.response.iter().sum::<f64>() / n as f64 } else { 0.0 };
+        let ss_total: f64 = model.response.iter().map(|v| (v - grand_mean).powi(2)).sum();
+        let df_error = n.saturating_sub(rank_full) as f64;
+        let ms_error = if df_error > 0.0 { sse_full / df_error } else { 0.0 };
+
+        let source = |(sse_reduced, rank_reduced): (f64, usize), (sse, rank): (f64, usize)| {
+            let ss = (sse_reduced - sse).max(0.0);
+            let df = rank.saturating_sub(rank_reduced) as f64;
+            let ms = if df > 0.0 { ss / df } else { 0.0 };
+            let f = if df > 0.0 && ms_error > 0.0 { Some(ms / ms_error) } else { None };
+            AnovaSource {
+                ss,
+                df: df as i32,
+                ms,
+                f,
+                p: f.map(|f| Self::f_p_value(f, df, df_error)),
+            }
+        };
+        let sse_rank = |selected: &[usize]| {
+            let (sse, rank, _) = fit(selected);
+            (sse, rank)
+        };
+        let contains = |outer: &[GlmVar], inner: &[GlmVar]| {
+            outer.len() > inner.len() && inner.iter().all(|v| outer.contains(v))
+        };
+
+        let glm_terms = terms
+            .iter()
+            .enumerate()
+            .map(|(t, term)| {
+                let before: Vec<usize> = (0..t).collect();
+                let through: Vec<usize> = (0..=t).collect();
+                let marginal: Vec<usize> = (0..terms.len())
+                    .filter(|&u| u != t && !contains(&terms[u], term))
+                    .collect();
+                let mut marginal_with = marginal.clone();
+                marginal_with.push(t);
+                let others: Vec<usize> = (0..terms.len()).filter(|&u| u != t).collect();
+
+                GlmTerm {
+                    name: term_name(term),
+                    type_i: source(sse_rank(&before), sse_rank(&through)),
+                    type_ii: source(sse_rank(&marginal), sse_rank(&marginal_with)),
+                    type_iii: source(sse_rank(&others), (sse_full, rank_full)),
+                }
+            })
+            .collect();
+
+        // Least-squares means: predictions averaged over a balanced grid of the other factors
+        let mut grid: Vec<Vec<usize>> = vec![vec![]];
+        for &k in &level_counts {
+            grid = grid
+                .into_iter()
+                .flat_map(|g| (0..k).map(move |l| [g.clone(), vec![l]].concat()))
+                .collect();
+        }
+        let least_squares_means = terms
+            .iter()
+            .filter_map(|term| match term.as_slice() {
+                [GlmVar::Factor(f)] => Some(*f),
+                _ => None,
+            })
+            .map(|f| {
+                let means = (0..level_counts[f])
+                    .map(|level| {
+                        let cells: Vec<&Vec<usize>> = grid.iter().filter(|g| g[f] == level).collect();
+                        let total: f64 = cells
+                            .iter()
+                            .map(|g| {
+                                let row = Self::glm_design_row(&terms, &level_counts, g, &covariate_means);
+                                row.iter().zip(beta.iter()).map(|(a, b)| a * b).sum::<f64>()
+                            })
+                            .sum();
+                        if cells.is_empty() { 0.0 } else { total / cells.len() as f64 }
+                    })
+                    .collect();
+                let sizes = (0..level_counts[f])
+                    .map(|level| factor_index[f].iter().filter(|&&l| l == level).count())
+                    .collect();
+                GlmFactorMeans {
+                    factor: model.factors[f].name.clone(),
+                    levels: factor_levels[f].clone(),
+                    means,
+                    sizes,
+                }
+            })
+            .collect();
+
+        GlmResult {
+            terms: glm_terms,
+            error: AnovaSource {
+                ss: sse_full,
+                df: df_error as i32,
+                ms: ms_error,
+                f: None,
+                p: None,
+            },
+            total: AnovaSource {
+                ss: ss_total,
+                df: n.saturating_sub(1) as i32,
+                ms: 0.0,
+                f: None,
+                p: None,
+            },
+            r_squared: if ss_total > 0.0 { 1.0 - sse_full / ss_total } else { 0.0 },
+            cv_percent: if grand_mean != 0.0 { ms_error.sqrt() / grand_mean * 100.0 } else { 0.0 },
+            grand_mean,
+            n,
+            rank: rank_full,
+            least_squares_means,
+        }
+    }
+
+    /// One design-matrix row (intercept first) for the given factor levels and covariate values.
+    fn glm_design_row(terms: &[Vec<GlmVar>], level_counts: &[usize], levels: &[usize], covariates: &[f64]) -> Vec<f64> {
+        let mut row = vec![1.0];
+        for term in terms {
+            row.extend(Self::glm_term_columns(term, level_counts, levels, covariates));
+        }
+        row
+    }
+
+    /// Columns of one term: products of the effect-coded factor columns and covariate values.
+    fn glm_term_columns(term: &[GlmVar], level_counts: &[usize], levels: &[usize], covariates: &[f64]) -> Vec<f64> {
+        let mut cols = vec![1.0];
+        for var in term {
+            let var_cols: Vec<f64> = match *var {
+                GlmVar::Factor(f) => {
+                    let k = level_counts[f];
+                    (0..k.saturating_sub(1))
+                        .map(|c| {
+                            if levels[f] == c {
+                                1.0
+                            } else if levels[f] == k - 1 {
+                                -1.0
+                            } else {
+                                0.0
+                            }
+                        })
+                        .collect()
+                }
+                GlmVar::Covariate(c) => vec![covariates[c]],
+            };
+            cols = cols
+                .iter()
+                .flat_map(|a| var_cols.iter().map(move |b| a * b))
+                .collect();
+        }
+        cols
+    }
+
+    /// Minimum-norm least-squares fit; returns (SSE, rank, coefficients).
+    fn glm_least_squares(x: &DMatrix<f64>, y: &DVector<f64>) -> (f64, usize, DVector<f64>) {
+        let svd = x.clone().svd(true, true);
+        let max_sv = svd.singular_values.iter().cloned().fold(0.0, f64::max);
+        let eps = max_sv * x.nrows().max(x.ncols()) as f64 * f64::EPSILON * 16.0;
+        let rank = svd.rank(eps);
+        let beta = svd
+            .solve(y, eps)
+            .unwrap_or_else(|_| DVector::zeros(x.ncols()));
+        let residuals = y - x * &beta;
+        (residuals.norm_squared(), rank, beta)
+    }
+}
+
+// ==============================================================================
+// ANALYSIS OF COVARIANCE
+// ==============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AncovaData {
+    pub response: Vec<f64>,
+    pub treatments: Vec<String>,
+    pub blocks: Option<Vec<String>>, // RAK replication of each observation
+    pub covariates: Vec<GlmCovariate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AncovaResult {
+    pub anova: GlmResult, // Type III lines are the covariate-adjusted tests
+    pub treatment: AnovaSource,
+    pub slopes: Vec<CovariateSlope>,
+    pub slope_homogeneity: SlopeHomogeneityTest,
+    pub treatments: Vec<String>,
+    pub sizes: Vec<usize>,
+    pub unadjusted_means: Vec<f64>,
+    pub adjusted_means: Vec<f64>, // covariates at their overall mean
+    pub covariate_means: Vec<Vec<f64>>, // [treatment][covariate]
+    pub comparisons: Vec<PostHocComparison>, // on adjusted means, t test at α = 0.05
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CovariateSlope {
+    pub covariate: String,
+    pub slope: f64, // pooled within-treatment regression coefficient
+    pub test: AnovaSource,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlopeHomogeneityTest {
+    pub f_statistic: f64,
+    pub df1: i32,
+    pub df2: i32,
+    pub p_value: f64,
+    pub passed: bool, // slopes parallel at α = 0.05
+}
+
+impl StatisticalAnalysis {
+    /// Analysis of covariance: one treatment factor, optional blocks and one
+    /// or more covariates, fitted through [`Self::glm`].
+    ///
+    /// Slope homogeneity compares the parallel-slopes model with one that adds
+    /// a treatment × covariate term for every covariate. Adjusted means are
+    /// the GLM least-squares means. The standard error of a difference of
+    /// adjusted means is `sqrt(MSE (1/n_i + 1/n_j + d' E⁻¹ d))`, where `d` is
+    /// the difference in covariate means and `E` the error sums of squares and
+    /// products of the covariates.
+    pub fn ancova(data: &AncovaData) -> AncovaResult {
+        let treatment = GlmFactor {
+            name: "Treatment".to_string(),
+            levels: data.treatments.clone(),
+        };
+        let block = data.blocks.as_ref().map(|levels| GlmFactor {
+            name: "Block".to_string(),
+            levels: levels.clone(),
+        });
+        let factors: Vec<GlmFactor> = std::iter::once(treatment).chain(block.clone()).collect();
+
+        let mut terms: Vec<Vec<String>> = Vec::new();
+        if block.is_some() {
+            terms.push(vec!["Block".to_string()]);
+        }
+        terms.extend(data.covariates.iter().map(|c| vec![c.name.clone()]));
+        terms.push(vec!["Treatment".to_string()]);
+
+        let model = GlmModel {
+            response: data.response.clone(),
+            factors: factors.clone(),
+            covariates: data.covariates.clone(),
+            terms: terms.clone(),
+        };
+        let anova = Self::glm(&model);
+
+        let mut separate_terms = terms.clone();
+        separate_terms.extend(data.covariates.iter().map(|c| vec!["Treatment".to_string(), c.name.clone()]));
+        let separate = Self::glm(&GlmModel {
+            terms: separate_terms,
+            ..model.clone()
+        });
+        let df1 = anova.error.df - separate.error.df;
+        let f_statistic = if df1 > 0 && separate.error.ms > 0.0 {
+            ((anova.error.ss - separate.error.ss).max(0.0) / df1 as f64) / separate.error.ms
+        } else {
+            0.0
+        };
+        let slope_p = Self::f_p_value(f_statistic, df1 as f64, separate.error.df as f64);
+
+        let term = |name: &str| anova.terms.iter().find(|t| t.name == name).map(|t| t.type_iii.clone());
+        let empty = AnovaSource {
+            ss: 0.0,
+            df: 0,
+            ms: 0.0,
+            f: None,
+            p: None,
+        };
+
+        // Error sums of squares and products after treatments (and blocks),
+        // by polarization of the residual sum of squares.
+        let residual_ss = |values: &[f64]| {
+            Self::glm(&GlmModel {
+                response: values.to_vec(),
+                factors: factors.clone(),
+                covariates: Vec::new(),
+                terms: terms.iter().filter(|t| t[0] == "Treatment" || t[0] == "Block").cloned().collect(),
+            })
+            .error
+            .ss
+        };
+        let cross = |u: &[f64], v: &[f64]| {
+            let sum: Vec<f64> = u.iter().zip(v).map(|(a, b)| a + b).collect();
+            (residual_ss(&sum) - residual_ss(u) - residual_ss(v)) / 2.0
+        };
+        let k = data.covariates.len();
+        let e_xx = DMatrix::from_fn(k, k, |i, j| {
+            if i == j {
+                residual_ss(&data.covariates[i].values)
+            } else {
+                cross(&data.covariates[i].values, &data.covariates[j].values)
+            }
+        });
+        let e_xy = DVector::from_fn(k, |i, _| cross(&data.covariates[i].values, &data.response));
+        let e_inv = e_xx.clone().try_inverse().unwrap_or_else(|| DMatrix::zeros(k, k));
+        let slopes_b = &e_inv * e_xy;
+
+        let slopes = data
+            .covariates
+            .iter()
+            .enumerate()
+            .map(|(i, c)| CovariateSlope {
+                covariate: c.name.clone(),
+                slope: slopes_b[i],
+                test: term(&c.name).unwrap_or_else(|| empty.clone()),
+            })
+            .collect();
+
+        let means = anova.least_squares_means.iter().find(|m| m.factor == "Treatment");
+        let treatments = means.map(|m| m.levels.clone()).unwrap_or_default();
+        let adjusted_means = means.map(|m| m.means.clone()).unwrap_or_default();
+        let members: Vec<Vec<usize>> = treatments
+            .iter()
+            .map(|level| (0..data.treatments.len()).filter(|&o| data.treatments[o] == *level).collect())
+            .collect();
+        let sizes: Vec<usize> = members.iter().map(|m| m.len()).collect();
+        let mean_of = |values: &[f64], idx: &[usize]| {
+            if idx.is_empty() { 0.0 } else { idx.iter().map(|&o| values[o]).sum::<f64>() / idx.len() as f64 }
+        };
+        let unadjusted_means = members.iter().map(|m| mean_of(&data.response, m)).collect();
+        let covariate_means: Vec<Vec<f64>> = members
+            .iter()
+            .map(|m| data.covariates.iter().map(|c| mean_of(&c.values, m)).collect())
+            .collect();
+
+        let df_error = anova.error.df as f64;
+        let t_dist = StudentsT::new(0.0, 1.0, df_error).ok();
+        let critical = t_dist.as_ref().map(|t| t.inverse_cdf(0.975)).unwrap_or(0.0);
+        let mut comparisons = Vec::new();
+        for i in 0..treatments.len() {
+            for j in (i + 1)..treatments.len() {
+                let d = DVector::from_fn(k, |c, _| covariate_means[i][c] - covariate_means[j][c]);
+                let quad = (d.transpose() * &e_inv * &d)[(0, 0)];
+                let std_error = (anova.error.ms
+                    * (1.0 / sizes[i].max(1) as f64 + 1.0 / sizes[j].max(1) as f64 + quad))
+                    .sqrt();
+                let mean_difference = adjusted_means[i] - adjusted_means[j];
+                let p_value = t_dist
+                    .as_ref()
+                    .filter(|_| std_error > 0.0)
+                    .map(|t| 2.0 * (1.0 - t.cdf((mean_difference / std_error).abs())));
+                comparisons.push(PostHocComparison {
+                    group_i: i,
+                    group_j: j,
+                    mean_difference,
+                    std_error,
+                    critical_value: critical * std_error,
+                    p_value,
+                    effect_size: Self::anova_hedges_g(
+                        mean_difference,
+                        anova.error.ms,
+                        sizes[i] as f64,
+                        sizes[j] as f64,
+                        df_error,
+                    ),
+                    is_significant: p_value.map(|p| p < 0.05).unwrap_or(false),
+                });
+            }
+        }
+
+        AncovaResult {
+            treatment: term("Treatment").unwrap_or(empty),
+            slopes,
+            slope_homogeneity: SlopeHomogeneityTest {
+                f_statistic,
+                df1,
+                df2: separate.error.df,
+                p_value: slope_p,
+                passed: slope_p >= 0.05,
+            },
+            treatments,
+            sizes,
+            unadjusted_means,
+            adjusted_means,
+            covariate_means,
+            comparisons,
+            anova,
+        }
+    }
+
+    /// Per-unit response at `session_id` with covariates from the same units
+    /// at `baseline_session_id`. Units missing any value are skipped. Blocks
+    /// are the plots' replication numbers.
+    pub async fn fetch_ancova_data(
+        conn: &mut PgConnection,
+        project_id: Uuid,
+        parameter_id: Uuid,
+        session_id: Uuid,
+        baseline_session_id: Uuid,
+        covariate_parameter_ids: &[Uuid],
+    ) -> Result<AncovaData, AppError> {
+        #[derive(sqlx::FromRow)]
+        struct AncovaRow {
+            unit_id: Uuid,
+            treatment: String,
+            replication: Option<i32>,
+            session_id: Uuid,
+            parameter_id: Uuid,
+            parameter_code: String,
+            value: f64,
+        }
+
+        struct AncovaUnit {
+            unit_id: Uuid,
+            treatment: String,
+            replication: Option<i32>,
+            response: Option<f64>,
+            covariates: Vec<Option<f64>>,
+        }
+
+        let rows: Vec<AncovaRow> = sqlx::query_as(
+            r#"
+            SELECT
+                eu.id as unit_id,
+                COALESCE(f.code, eb.treatment_description, eb.block_code) as treatment,
+                eb.replication,
+                md.session_id,
+                md.parameter_id,
+                mp.code as parameter_code,
+                md.numeric_value::float8 as value
+            FROM monitoring_data md
+            JOIN experimental_units eu ON md.unit_id = eu.id
+            JOIN experimental_blocks eb ON eu.block_id = eb.id
+            JOIN monitoring_parameters mp ON md.parameter_id = mp.id
+            LEFT JOIN formulas f ON eb.formula_id = f.id
+            WHERE eb.project_id = $1
+            AND md.numeric_value IS NOT NULL
+            AND COALESCE(eu.is_active, true)
+            AND eu.excluded_reason IS NULL
+            AND NOT COALESCE(md.is_outlier, false)
+            AND ((md.session_id = $2 AND md.parameter_id = $3)
+                OR (md.session_id = $4 AND md.parameter_id = ANY($5)))
+            ORDER BY eb.block_code, eu.unit_code
+            "#
+        )
+        .bind(project_id)
+        .bind(session_id)
+        .bind(parameter_id)
+        .bind(baseline_session_id)
+        .bind(covariate_parameter_ids)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        let mut units: Vec<AncovaUnit> = Vec::new();
+        let mut names: Vec<String> = (1..=covariate_parameter_ids.len()).map(|c| format!("Baseline {}", c)).collect();
+        for row in rows {
+            let i = match units.iter().position(|u| u.unit_id == row.unit_id) {
+                Some(i) => i,
+                None => {
+                    units.push(AncovaUnit {
+                        unit_id: row.unit_id,
+                        treatment: row.treatment,
+                        replication: row.replication,
+                        response: None,
+                        covariates: vec![None; covariate_parameter_ids.len()],
+                    });
+                    units.len() - 1
+                }
+            };
+            if row.session_id == session_id && row.parameter_id == parameter_id {
+                units[i].response = Some(row.value);
+            }
+            if row.session_id == baseline_session_id {
+                if let Some(c) = covariate_parameter_ids.iter().position(|p| *p == row.parameter_id) {
+                    units[i].covariates[c] = Some(row.value);
+                    names[c] = format!("Baseline {}", row.parameter_code);
+                }
+            }
+        }
+
+        // (treatment, replication, response, covariates) of complete units
+        let complete: Vec<_> = units
+            .into_iter()
+            .filter_map(|unit| {
+                let covariates = unit.covariates.into_iter().collect::<Option<Vec<f64>>>()?;
+                Some((unit.treatment, unit.replication, unit.response?, covariates))
+            })
+            .collect();
+
+        Ok(AncovaData {
+            response: complete.iter().map(|u| u.2).collect(),
+            treatments: complete.iter().map(|u| u.0.clone()).collect(),
+            blocks: complete
+                .iter()
+                .map(|u| u.1.map(|r| r.to_string()))
+                .collect::<Option<Vec<String>>>(),
+            covariates: (0..covariate_parameter_ids.len())
+                .map(|c| GlmCovariate {
+                    name: names[c].clone(),
+                    values: complete.iter().map(|u| u.3[c]).collect(),
+                })
+                .collect(),
+        })
+    }
+}
+
+// ==============================================================================
+// POST-HOC MEAN SEPARATION
+// ==============================================================================
+
+/// Mean separation procedure run after the ANOVA.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PostHocTest {
+    #[default]
+    Lsd,
+    Duncan,
+    Tukey,
+    ScottKnott,
+}
+
+impl PostHocTest {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PostHocTest::Lsd => "LSD",
+            PostHocTest::Duncan => "DMRT",
+            PostHocTest::Tukey => "Tukey HSD",
+            PostHocTest::ScottKnott => "Scott-Knott",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeanSeparationResult {
+    pub test: PostHocTest,
+    pub alpha: f64,
+    pub means: Vec<f64>,
+    pub sizes: Vec<usize>,
+    pub mean_intervals: Vec<ConfidenceInterval>,
+    pub letters: Vec<String>, // compact letter display, "a" on the highest mean
+    pub comparisons: Vec<PostHocComparison>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostHocComparison {
+    pub group_i: usize,
+    pub group_j: usize,
+    pub mean_difference: f64,
+    pub std_error: f64,      // standard error of the difference
+    pub critical_value: f64, // LSD, Duncan range or HSD; 0 for Scott-Knott
+    pub p_value: Option<f64>,
+    pub effect_size: StandardizedDifference,
+    pub is_significant: bool,
+}
+
+impl StatisticalAnalysis {
+    /// Separates treatment means with the chosen test and labels them with
+    /// compact letters.
+    ///
+    /// Takes means and replication counts rather than raw groups so adjusted
+    /// means (RCBD, GLM) can be separated too; `mse` and `df_error` come from
+    /// the ANOVA error line. Unequal replication uses the Tukey-Kramer
+    /// standard error for each pair.
+    pub fn mean_separation(
+        test: PostHocTest,
+        means: &[f64],
+        sizes: &[usize],
+        mse: f64,
+        df_error: f64,
+        alpha: f64,
+    ) -> MeanSeparationResult {
+        let k = means.len();
+        let testable = k >= 2 && mse > 0.0 && df_error >= 2.0;
+
+        let sed = |i: usize, j: usize| {
+            let inv = |n: usize| if n > 0 { 1.0 / n as f64 } else { 0.0 };
+            (mse * (inv(sizes[i]) + inv(sizes[j]))).sqrt()
+        };
+
+        // Positions of the means from highest to lowest
+        let mut order: Vec<usize> = (0..k).collect();
+        order.sort_by(|&a, &b| means[b].partial_cmp(&means[a]).unwrap_or(std::cmp::Ordering::Equal));
+        let mut rank = vec![0usize; k];
+        for (r, &i) in order.iter().enumerate() {
+            rank[i] = r;
+        }
+
+        let mut critical = vec![vec![0.0; k]; k];
+        let mut p_values = vec![vec![None; k]; k];
+        let mut significant = vec![vec![false; k]; k];
+
+        if testable {
+            match test {
+                PostHocTest::Lsd => {
+                    let t_dist = StudentsT::new(0.0, 1.0, df_error).ok();
+                    let t_crit = t_dist.as_ref().map(|d| d.inverse_cdf(1.0 - alpha / 2.0)).unwrap_or(1.96);
+                    for i in 0..k {
+                        for j in (i + 1)..k {
+                            let se = sed(i, j);
+                            let t = if se > 0.0 { (means[i] - means[j]).abs() / se } else { 0.0 };
+                            critical[i][j] = t_crit * se;
+                            p_values[i][j] = t_dist.as_ref().map(|d| 2.0 * (1.0 - d.cdf(t)));
+                        }
+                    }
+                }
+                PostHocTest::Tukey => {
+                    let q_crit = Self::studentized_range_quantile(1.0 - alpha, k as f64, df_error);
+                    for i in 0..k {
+                        for j in (i + 1)..k {
+                            let se = sed(i, j) / std::f64::consts::SQRT_2;
+                            let q = if se > 0.0 { (means[i] - means[j]).abs() / se } else { 0.0 };
+                            critical[i][j] = q_crit * se;
+                            p_values[i][j] = Some(1.0 - Self::studentized_range_cdf(q, k as f64, df_error));
+                        }
+                    }
+                }
+                PostHocTest::Duncan => {
+                    // Protection level 1 - (1 - alpha)^(p - 1) for a range spanning p means
+                    let q_crit: Vec<f64> = (0..=k)
+                        .map(|p| {
+                            if p < 2 {
+                                0.0
+                            } else {
+                                let level = (1.0 - alpha).powi(p as i32 - 1);
+                                Self::studentized_range_quantile(level, p as f64, df_error)
+                            }
+                        })
+                        .collect();
+                    for i in 0..k {
+                        for j in (i + 1)..k {
+                            let p = rank[i].abs_diff(rank[j]) + 1;
+                            let se = sed(i, j) / std::f64::consts::SQRT_2;
+                            let q = if se > 0.0 { (means[i] - means[j]).abs() / se } else { 0.0 };
+                            critical[i][j] = q_crit[p] * se;
+                            let cdf = Self::studentized_range_cdf(q, p as f64, df_error);
+                            p_values[i][j] = Some(1.0 - cdf.max(0.0).powf(1.0 / (p - 1) as f64));
+                        }
+                    }
+                }
+                PostHocTest::ScottKnott => {
+                    let harmonic_n = k as f64 / sizes.iter().map(|&n| 1.0 / n.max(1) as f64).sum::<f64>();
+                    let groups = Self::scott_knott_groups(means, &order, mse / harmonic_n, df_error, alpha);
+                    for i in 0..k {
+                        for j in (i + 1)..k {
+                            significant[i][j] = groups[i] != groups[j];
+                        }
+                    }
+                }
+            }
+
+            if test != PostHocTest::ScottKnott {
+                for i in 0..k {
+                    for j in (i + 1)..k {
+                        significant[i][j] = (means[i] - means[j]).abs() > critical[i][j];
+                    }
+                }
+            }
+
+            // DMRT: no difference is declared inside a range already found homogeneous
+            if test == PostHocTest::Duncan {
+                let mut homogeneous = vec![vec![false; k]; k];
+                for span in (1..k).rev() {
+                    for lo in 0..(k - span) {
+                        let hi = lo + span;
+                        let (a, b) = (order[lo], order[hi]);
+                        let enclosed = (lo > 0 && homogeneous[lo - 1][hi]) || (hi + 1 < k && homogeneous[lo][hi + 1]);
+                        let sig = significant[a.min(b)][a.max(b)] && !enclosed;
+                        homogeneous[lo][hi] = !sig;
+                        significant[a.min(b)][a.max(b)] = sig;
+                    }
+                }
+            }
+        }
+
+        let upper = significant.clone();
+        for (i, row) in significant.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate().take(i) {
+                *cell = upper[j][i];
+            }
+        }
+
+        let mut comparisons = Vec::new();
+        for i in 0..k {
+            for j in (i + 1)..k {
+                comparisons.push(PostHocComparison {
+                    group_i: i,
+                    group_j: j,
+                    mean_difference: means[i] - means[j],
+                    std_error: sed(i, j),
+                    critical_value: critical[i][j],
+                    p_value: p_values[i][j],
+                    effect_size: Self::anova_hedges_g(
+                        means[i] - means[j],
+                        mse,
+                        sizes[i] as f64,
+                        sizes[j] as f64,
+                        df_error,
+                    ),
+                    is_significant: significant[i][j],
+                });
+            }
+        }
+
+        MeanSeparationResult {
+            test,
+            alpha,
+            means: means.to_vec(),
+            sizes: sizes.to_vec(),
+            mean_intervals: Self::mean_intervals(means, sizes, mse, df_error),
+            letters: Self::compact_letters(means, &significant),
+            comparisons,
+        }
+    }
+
+    /// Compact letter display by the insert-absorb algorithm (Piepho, 2004):
+    /// means sharing a letter do not differ. `significant` is a symmetric
+    /// k × k matrix; letters are assigned from the highest mean down.
+    pub fn compact_letters(means: &[f64], significant: &[Vec<bool>]) -> Vec<String> {
+        let k = means.len();
+        let mut columns: Vec<Vec<bool>> = if k > 0 { vec![vec![true; k]] } else { Vec::new() };
+
+        for i in 0..k {
+            for j in (i + 1)..k {
+                if !significant[i][j] {
+                    continue;
+                }
+                // Insert: split every column that still joins i and j
+                let mut split = Vec::with_capacity(columns.len() + 1);
+                for column in columns {
+                    if column[i] && column[j] {
+                        let mut without_i = column.clone();
+                        without_i[i] = false;
+                        let mut without_j = column;
+                        without_j[j] = false;
+                        split.push(without_i);
+                        split.push(without_j);
+                    } else {
+                        split.push(column);
+                    }
+                }
+                // Absorb: drop columns contained in another (keep the first of duplicates)
+                columns = split
+                    .iter()
+                    .enumerate()
+                    .filter(|(c, column)| {
+                        column.iter().any(|&x| x)
+                            && !split.iter().enumerate().any(|(d, other)| {
+                                d != *c
+                                    && column.iter().zip(other.iter()).all(|(&x, &y)| !x || y)
+                                    && (*column != other || d < *c)
+                            })
+                    })
+                    .map(|(_, column)| column.clone())
+                    .collect();
+            }
+        }
+
+        let mut order: Vec<usize> = (0..k).collect();
+        order.sort_by(|&a, &b| means[b].partial_cmp(&means[a]).unwrap_or(std::cmp::Ordering::Equal));
+        columns.sort_by_key(|column| {
+            order
+                .iter()
+                .enumerate()
+                .filter(|(_, &i)| column[i])
+                .map(|(r, _)| r)
+                .collect::<Vec<_>>()
+        });
+
+        let letter = |n: usize| -> String {
+            const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+            if n < ALPHABET.len() {
+                (ALPHABET[n] as char).to_string()
+            } else {
+                format!("{}{}", ALPHABET[n % ALPHABET.len()] as char, n / ALPHABET.len())
+            }
+        };
+
+        (0..k)
+            .map(|i| {
+                columns
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, column)| column[i])
+                    .map(|(n, _)| letter(n))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Scott-Knott clustering: recursively splits the ordered means where the
+    /// between-group SS is largest, as long as the likelihood-ratio statistic
+    /// exceeds its chi-square critical value. Returns a group id per mean.
+    fn scott_knott_groups(means: &[f64], order: &[usize], var_mean: f64, df_error: f64, alpha: f64) -> Vec<usize> {
+        let sorted: Vec<f64> = order.iter().map(|&i| means[i]).collect();
+        let mut groups = vec![0usize; means.len()];
+        let mut next_group = 0;
+        let mut pending = vec![(0, sorted.len())];
+
+        while let Some((lo, hi)) = pending.pop() {
+            let g = hi - lo;
+            let values = &sorted[lo..hi];
+            let total: f64 = values.iter().sum();
+
+            let mut best = (0.0, 0);
+            for cut in 1..g {
+                let t1: f64 = values[..cut].iter().sum();
+                let t2 = total - t1;
+                let b0 = t1 * t1 / cut as f64 + t2 * t2 / (g - cut) as f64 - total * total / g as f64;
+                if b0 > best.0 {
+                    best = (b0, cut);
+                }
+            }
+
+            let split = if g >= 2 && best.1 > 0 {
+                let mean = total / g as f64;
+                let ss: f64 = values.iter().map(|v| (v - mean).powi(2)).sum();
+                let sigma2 = (ss + df_error * var_mean) / (g as f64 + df_error);
+                let pi = std::f64::consts::PI;
+                let lambda = if sigma2 > 0.0 { pi / (2.0 * (pi - 2.0)) * best.0 / sigma2 } else { 0.0 };
+                ChiSquared::new(g as f64 / (pi - 2.0))
+                    .map(|d| lambda > d.inverse_cdf(1.0 - alpha))
+                    .unwrap_or(false)
+            } else {
+                false
+            };
+
+            if split {
+                pending.push((lo + best.1, hi));
+                pending.push((lo, lo + best.1));
+            } else {
+                for &i in &order[lo..hi] {
+                    groups[i] = next_group;
+                }
+                next_group += 1;
+            }
+        }
+
+        groups
+    }
+
+    /// Quantile of the studentized range distribution, by bisection on the CDF.
+    fn studentized_range_quantile(p: f64, k: f64, df: f64) -> f64 {
+        let (mut lo, mut hi) = (0.0, 1.0);
+        while Self::studentized_range_cdf(hi, k, df) < p && hi < 1e3 {
+            lo = hi;
+            hi *= 2.0;
+        }
+        for _ in 0..60 {
+            let mid = 0.5 * (lo + hi);
+            if Self::studentized_range_cdf(mid, k, df) < p {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+            if hi - lo < 1e-7 {
+                break;
+            }
+        }
+        0.5 * (lo + hi)
+    }
+
+    /// CDF of the studentized range for `k` means and `df` error degrees of
+    /// freedom (Copenhaver & Holland, 1988; the algorithm used by R's `ptukey`).
+    fn studentized_range_cdf(q: f64, k: f64, df: f64) -> f64 {
+        const XLEG: [f64; 8] = [
+            0.9894009349916499,
+            0.9445750230732326,
+            0.8656312023878318,
+            0.755404408355003,
+            0.6178762444026438,
+            0.45801677765722737,
+            0.2816035507792589,
+            0.09501250983763744,
+        ];
+        const ALEG: [f64; 8] = [
+            0.027152459411754096,
+            0.062253523938647894,
+            0.09515851168249279,
+            0.12462897125553388,
+            0.14959598881657674,
+            0.16915651939500254,
+            0.18260341504492358,
+            0.1894506104550685,
+        ];
+
+        if q <= 0.0 || df < 1.0 || k < 2.0 {
+            return 0.0;
+        }
+        if !q.is_finite() {
+            return 1.0;
+        }
+        if df > 25000.0 {
+            return Self::studentized_range_wprob(q, k);
+        }
+
+        // Integrate the range CDF over the distribution of s/sigma
+        let f2 = df * 0.5;
+        let f21 = f2 - 1.0;
+        let ff4 = df * 0.25;
+        let ulen: f64 = if df <= 100.0 {
+            1.0
+        } else if df <= 800.0 {
+            0.5
+        } else if df <= 5000.0 {
+            0.25
+        } else {
+            0.125
+        };
+        let f2lf = f2 * df.ln() - df * std::f64::consts::LN_2 - ln_gamma(f2) + ulen.ln();
+
+        let mut ans = 0.0;
+        for i in 1..=50 {
+            let mut otsum = 0.0;
+            let twa1 = (2 * i - 1) as f64 * ulen;
+            for jj in 0..16 {
+                let (x, a) = if jj < 8 {
+                    (-XLEG[jj] * ulen, ALEG[jj])
+                } else {
+                    (XLEG[jj - 8] * ulen, ALEG[jj - 8])
+                };
+                let u = twa1 + x;
+                let t1 = f2lf + f21 * u.ln() - u * ff4;
+                if t1 >= -30.0 {
+                    let wprb = Self::studentized_range_wprob(q * (u * 0.5).sqrt(), k);
+                    otsum += wprb * a * t1.exp();
+                }
+            }
+            if i as f64 * ulen >= 1.0 && otsum <= 1e-14 {
+                break;
+            }
+            ans += otsum;
+        }
+
+        ans.min(1.0)
+    }
+
+    /// Probability that the range of `k` standard normal variates is below `w`.
+    fn studentized_range_wprob(w: f64, k: f64) -> f64 {
+        const XLEG: [f64; 6] = [
+            0.9815606342467192,
+            0.9041172563704749,
+            0.7699026741943047,
+            0.5873179542866175,
+            0.3678314989981802,
+            0.1252334085114689,
+        ];
+        const ALEG: [f64; 6] = [
+            0.04717533638651183,
+            0.10693932599531843,
+            0.16007832854334622,
+            0.20316742672306592,
+            0.2334925365383548,
+            0.24914704581340277,
+        ];
+        const UPPER: f64 = 8.0;
+
+        let qsqz = w * 0.5;
+        if qsqz >= UPPER {
+            return 1.0;
+        }
+        let normal = match Normal::new(0.0, 1.0) {
+            Ok(n) => n,
+            Err(_) => return 0.0,
+        };
+
+        let mut pr_w = 2.0 * normal.cdf(qsqz) - 1.0;
+        pr_w = if pr_w >= (-50.0 / k).exp() { pr_w.powf(k) } else { 0.0 };
+
+        let wincr = if w > 3.0 { 2 } else { 3 };
+        let binc = (UPPER - qsqz) / wincr as f64;
+        let mut blb = qsqz;
+        let mut bub = blb + binc;
+        let mut einsum = 0.0;
+        let cc1 = k - 1.0;
+
+        for _ in 0..wincr {
+            let mut elsum = 0.0;
+            let a = 0.5 * (bub + blb);
+            let b = 0.5 * (bub - blb);
+            for jj in 0..12 {
+                let (xx, weight) = if jj < 6 {
+                    (-XLEG[jj], ALEG[jj])
+                } else {
+                    (XLEG[11 - jj], ALEG[11 - jj])
+                };
+                let ac = a + b * xx;
+                let qexpo = ac * ac;
+                if qexpo > 60.0 {
+                    break;
+                }
+                let rinsum = normal.cdf(ac) - normal.cdf(ac - w);
+                if rinsum >= (-30.0 / cc1).exp() {
+                    elsum += weight * (-0.5 * qexpo).exp() * rinsum.powf(cc1);
+                }
+            }
+            elsum *= 2.0 * b * k / (2.0 * std::f64::consts::PI).sqrt();
+            einsum += elsum;
+            blb = bub;
+            bub += binc;
+        }
+
+        pr_w += einsum;
+        if pr_w <= (-30.0f64).exp() {
+            return 0.0;
+        }
+        pr_w.min(1.0)
+    }
+}
+
+// ==============================================================================
+// DUNNETT'S TEST (TREATMENTS VS. CONTROL)
+// ==============================================================================
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DunnettAlternative {
+    #[default]
+    TwoSided,
+    Greater, // treatment mean above the control
+    Less,    // treatment mean below the control
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DunnettResult {
+    pub alternative: DunnettAlternative,
+    pub alpha: f64,
+    pub control_mean: f64,
+    pub control_n: usize,
+    pub ms_error: f64, // pooled over the control and every treatment
+    pub df_error: f64,
+    pub critical_value: f64,
+    pub comparisons: Vec<DunnettComparison>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DunnettComparison {
+    pub treatment: usize,
+    pub mean: f64,
+    pub n: usize,
+    pub difference: f64, // treatment - control
+    pub std_error: f64,
+    pub t_statistic: f64,
+    pub p_value: f64, // adjusted for the whole family of comparisons
+    pub ci_lower: Option<f64>,
+    pub ci_upper: Option<f64>,
+    pub effect_size: StandardizedDifference,
+    pub is_significant: bool,
+}
+
+/// Plot means of one treatment for a parameter and session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreatmentPlotMeans {
+    pub treatment: String,
+    pub is_control: bool,
+    pub block_codes: Vec<String>,
+    pub values: Vec<f64>, // one mean per plot, aligned with block_codes
+}
+
+impl StatisticalAnalysis {
+    /// Dunnett's many-to-one comparison of every treatment against the control.
+    ///
+    /// The error variance is pooled over all groups. Critical values and
+    /// adjusted p-values come from the multivariate t distribution with the
+    /// exact correlation for unequal replication, so no table lookup is needed.
+    pub fn dunnett_test(
+        control: &[f64],
+        treatments: &[Vec<f64>],
+        alternative: DunnettAlternative,
+        alpha: f64,
+    ) -> DunnettResult {
+        let mean = |v: &[f64]| if v.is_empty() { 0.0 } else { v.iter().sum::<f64>() / v.len() as f64 };
+        let control_mean = mean(control);
+        let n0 = control.len();
+
+        let groups = std::iter::once(control).chain(treatments.iter().map(|t| t.as_slice()));
+        let (ss_error, n_total, k) = groups.fold((0.0, 0, 0), |(ss, n, k), g| {
+            let m = mean(g);
+            (ss + g.iter().map(|y| (y - m).powi(2)).sum::<f64>(), n + g.len(), k + 1)
+        });
+        let df_error = n_total.saturating_sub(k) as f64;
+        let ms_error = if df_error > 0.0 { ss_error / df_error } else { 0.0 };
+
+        // Correlation between comparisons i and j is lambda_i * lambda_j
+        let lambdas: Vec<f64> = treatments
+            .iter()
+            .map(|t| (t.len() as f64 / (t.len() + n0) as f64).sqrt())
+            .collect();
+        let two_sided = alternative == DunnettAlternative::TwoSided;
+        let testable = ms_error > 0.0 && df_error >= 1.0 && n0 > 0 && !treatments.is_empty();
+        let critical_value = if testable {
+            Self::dunnett_quantile(1.0 - alpha, &lambdas, df_error, two_sided)
+        } else {
+            0.0
+        };
+
+        let comparisons = treatments
+            .iter()
+            .enumerate()
+            .map(|(i, t)| {
+                let n = t.len();
+                let m = mean(t);
+                let difference = m - control_mean;
+                let std_error = if n > 0 && n0 > 0 {
+                    (ms_error * (1.0 / n as f64 + 1.0 / n0 as f64)).sqrt()
+                } else {
+                    0.0
+                };
+                let t_statistic = if std_error > 0.0 { difference / std_error } else { 0.0 };
+                let directed = match alternative {
+                    DunnettAlternative::TwoSided => t_statistic.abs(),
+                    DunnettAlternative::Greater => t_statistic,
+                    DunnettAlternative::Less => -t_statistic,
+                };
+                let p_value = if testable {
+                    (1.0 - Self::dunnett_cdf(directed, &lambdas, df_error, two_sided)).clamp(0.0, 1.0)
+                } else {
+                    1.0
+                };
+                let margin = critical_value * std_error;
+                let (ci_lower, ci_upper) = match alternative {
+                    DunnettAlternative::TwoSided => (Some(difference - margin), Some(difference + margin)),
+                    DunnettAlternative::Greater => (Some(difference - margin), None),
+                    DunnettAlternative::Less => (None, Some(difference + margin)),
+                };
+
+                DunnettComparison {
+                    treatment: i,
+                    mean: m,
+                    n,
+                    difference,
+                    std_error,
+                    t_statistic,
+                    p_value,
+                    ci_lower,
+                    ci_upper,
+                    effect_size: Self::anova_hedges_g(difference, ms_error, n as f64, n0 as f64, df_error),
+                    is_significant: testable && directed > critical_value,
+                }
+            })
+            .collect();
+
+        DunnettResult {
+            alternative,
+            alpha,
+            control_mean,
+            control_n: n0,
+            ms_error,
+            df_error,
+            critical_value,
+            comparisons,
+        }
+    }
+
+    /// Loads the plot means of every treatment for one parameter and
+    /// session. Excluded or inactive units and, unless `include_outliers`,
+    /// flagged outliers are left out, as in `fetch_project_dataset`.
+    pub async fn fetch_treatment_plot_means(
+        conn: &mut PgConnection,
+        project_id: Uuid,
+        parameter_id: Uuid,
+        session_id: Uuid,
+        include_outliers: bool,
+    ) -> Result<Vec<TreatmentPlotMeans>, AppError> {
+        let rows: Vec<(String, String, bool, f64)> = sqlx::query_as(
+            r#"
+            SELECT
+                COALESCE(f.code, eb.treatment_description, eb.block_code) AS treatment,
+                eb.block_code,
+                COALESCE(eb.is_control, false),
+                AVG(md.numeric_value)::float8
+            FROM monitoring_data md
+            JOIN experimental_units eu ON md.unit_id = eu.id
+            JOIN experimental_blocks eb ON eu.block_id = eb.id
+            LEFT JOIN formulas f ON eb.formula_id = f.id
+            WHERE eb.project_id = $1
+            AND md.parameter_id = $2
+            AND md.session_id = $3
+            AND md.numeric_value IS NOT NULL
+            AND COALESCE(eu.is_active, true)
+            AND eu.excluded_reason IS NULL
+            AND ($4 OR NOT COALESCE(md.is_outlier, false))
+            GROUP BY eb.id, eb.block_code, f.code, eb.treatment_description, eb.is_control
+            ORDER BY eb.block_code
+            "#
+        )
+        .bind(project_id)
+        .bind(parameter_id)
+        .bind(session_id)
+        .bind(include_outliers)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        let mut treatments: Vec<TreatmentPlotMeans> = Vec::new();
+        for (treatment, block_code, is_control, value) in rows {
+            match treatments.iter_mut().find(|t| t.treatment == treatment) {
+                Some(t) => {
+                    t.is_control |= is_control;
+                    t.block_codes.push(block_code);
+                    t.values.push(value);
+                }
+                None => treatments.push(TreatmentPlotMeans {
+                    treatment,
+                    is_control,
+                    block_codes: vec![block_code],
+                    values: vec![value],
+                }),
+            }
+        }
+
+        Ok(treatments)
+    }
+
+    /// Critical value of the Dunnett statistic, by bisection on the CDF.
+    fn dunnett_quantile(p: f64, lambdas: &[f64], df: f64, two_sided: bool) -> f64 {
+        let (mut lo, mut hi) = (0.0, 1.0);
+        while Self::dunnett_cdf(hi, lambdas, df, two_sided) < p && hi < 1e3 {
+            lo = hi;
+            hi *= 2.0;
+        }
+        for _ in 0..60 {
+            let mid = 0.5 * (lo + hi);
+            if Self::dunnett_cdf(mid, lambdas, df, two_sided) < p {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+            if hi - lo < 1e-6 {
+                break;
+            }
+        }
+        0.5 * (lo + hi)
+    }
+
+    /// P(max T_i <= d), or P(max |T_i| <= d) when two-sided, for Dunnett
+    /// statistics with correlations lambda_i * lambda_j and `df` error df.
+    ///
+    /// Conditional on the control's standardized mean z and on s = sigma_hat / sigma
+    /// the comparisons are independent, so the probability is a double integral
+    /// (Simpson's rule) over the normal density of z and the chi density of s.
+    fn dunnett_cdf(d: f64, lambdas: &[f64], df: f64, two_sided: bool) -> f64 {
+        let normal = match Normal::new(0.0, 1.0) {
+            Ok(n) => n,
+            Err(_) => return 0.0,
+        };
+        if d <= 0.0 && two_sided {
+            return 0.0;
+        }
+
+        let simpson = |lo: f64, hi: f64, intervals: usize, f: &dyn Fn(f64) -> f64| {
+            let h = (hi - lo) / intervals as f64;
+            let inner: f64 = (1..intervals)
+                .map(|i| f(lo + i as f64 * h) * if i % 2 == 1 { 4.0 } else { 2.0 })
+                .sum();
+            (f(lo) + f(hi) + inner) * h / 3.0
+        };
+
+        let given_s = |s: f64| {
+            simpson(-8.0, 8.0, 160, &|z: f64| {
+                let density = (-0.5 * z * z).exp() / (2.0 * std::f64::consts::PI).sqrt();
+                let product: f64 = lambdas
+                    .iter()
+                    .map(|&l| {
+                        let scale = (1.0 - l * l).max(1e-12).sqrt();
+                        let upper = normal.cdf((l * z + d * s) / scale);
+                        if two_sided {
+                            upper - normal.cdf((l * z - d * s) / scale)
+                        } else {
+                            upper
+                        }
+                    })
+                    .product();
+                density * product
+            })
+        };
+
+        if df > 5000.0 {
+            return given_s(1.0).clamp(0.0, 1.0);
+        }
+
+        // Density of s = sqrt(chi2_df / df)
+        let half = df / 2.0;
+        let log_const = std::f64::consts::LN_2 + half * half.ln() - ln_gamma(half);
+        let spread = 12.0 / (2.0 * df).sqrt();
+        let (s_lo, s_hi) = ((1.0 - spread).max(0.0), 1.0 + spread);
+        let probability = simpson(s_lo, s_hi, 120, &|s: f64| {
+            if s <= 0.0 {
+                return 0.0;
+            }
+            let log_density = log_const + (df - 1.0) * s.ln() - df * s * s / 2.0;
+            log_density.exp() * given_s(s)
+        });
+
+        probability.clamp(0.0, 1.0)
+    }
+}
+
+// ==============================================================================
+// NONPARAMETRIC TESTS
+// ==============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NonparametricTest {
+    pub test: String,
+    pub statistic: f64, // H, U, W+ or Friedman chi-square
+    pub z: Option<f64>,
+    pub df: Option<f64>,
+    pub p_value: f64,
+    pub exact: bool,
+    pub effect_size: f64,
+    pub effect_size_name: String,
+    pub is_significant: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KruskalWallisResult {
+    pub test: NonparametricTest,
+    pub mean_ranks: Vec<f64>,
+    pub medians: Vec<f64>,
+    pub sizes: Vec<usize>,
+    pub dunn: Vec<DunnComparison>,
+    pub letters: Vec<String>, // compact letters from Dunn's test, "a" on the highest mean rank
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DunnComparison {
+    pub group_i: usize,
+    pub group_j: usize,
+    pub z: f64,
+    pub p_value: f64,
+    pub p_adjusted: f64, // Holm
+    pub effect_size: f64, // r = z / sqrt(n_i + n_j)
+    pub is_significant: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FriedmanResult {
+    pub test: NonparametricTest,
+    pub mean_ranks: Vec<f64>, // per treatment, ranked within blocks
+    pub medians: Vec<f64>,
+}
+
+impl StatisticalAnalysis {
+    /// Kruskal-Wallis H test with Dunn's pairwise comparisons (Holm-adjusted).
+    /// The effect size is epsilon squared, H / (n - 1).
+    pub fn kruskal_wallis(groups: &[Vec<f64>]) -> KruskalWallisResult {
+        let k = groups.len();
+        let pooled: Vec<f64> = groups.iter().flatten().copied().collect();
+        let n = pooled.len() as f64;
+        let (ranks, ties) = Self::rank_with_ties(&pooled);
+
+        let mut offset = 0;
+        let rank_sums: Vec<f64> = groups
+            .iter()
+            .map(|g| {
+                let sum = ranks[offset..offset + g.len()].iter().sum();
+                offset += g.len();
+                sum
+            })
+            .collect();
+        let sizes: Vec<usize> = groups.iter().map(|g| g.len()).collect();
+        let mean_ranks: Vec<f64> = rank_sums
+            .iter()
+            .zip(sizes.iter())
+            .map(|(r, &s)| if s > 0 { r / s as f64 } else { 0.0 })
+            .collect();
+
+        let tie_factor = if n > 1.0 { 1.0 - ties / (n * n * n - n) } else { 1.0 };
+        let h_raw = if n > 0.0 {
+            12.0 / (n * (n + 1.0))
+                * rank_sums
+                    .iter()
+                    .zip(sizes.iter())
+                    .filter(|(_, &s)| s > 0)
+                    .map(|(r, &s)| r * r / s as f64)
+                    .sum::<f64>()
+                - 3.0 * (n + 1.0)
+        } else {
+            0.0
+        };
+        let h = if tie_factor > 0.0 { h_raw / tie_factor } else { 0.0 };
+        let df = sizes.iter().filter(|&&s| s > 0).count().saturating_sub(1) as f64;
+        let p_value = Self::chi_square_p_value(h, df);
+
+        // Dunn's z for each pair, with the tie-corrected rank variance
+        let variance = if n > 1.0 { (n * (n + 1.0) / 12.0 - ties / (12.0 * (n - 1.0))).max(0.0) } else { 0.0 };
+        let mut dunn: Vec<DunnComparison> = Vec::new();
+        for i in 0..k {
+            for j in (i + 1)..k {
+                let (ni, nj) = (sizes[i] as f64, sizes[j] as f64);
+                let se = if ni > 0.0 && nj > 0.0 { (variance * (1.0 / ni + 1.0 / nj)).sqrt() } else { 0.0 };
+                let z = if se > 0.0 { (mean_ranks[i] - mean_ranks[j]) / se } else { 0.0 };
+                dunn.push(DunnComparison {
+                    group_i: i,
+                    group_j: j,
+                    z,
+                    p_value: Self::normal_two_sided_p(z),
+                    p_adjusted: 1.0,
+                    effect_size: if ni + nj > 0.0 { z / (ni + nj).sqrt() } else { 0.0 },
+                    is_significant: false,
+                });
+            }
+        }
+
+        // Holm step-down adjustment
+        let mut by_p: Vec<usize> = (0..dunn.len()).collect();
+        by_p.sort_by(|&a, &b| dunn[a].p_value.partial_cmp(&dunn[b].p_value).unwrap_or(std::cmp::Ordering::Equal));
+        let m = dunn.len();
+        let mut running: f64 = 0.0;
+        for (step, &c) in by_p.iter().enumerate() {
+            running = running.max(((m - step) as f64 * dunn[c].p_value).min(1.0));
+            dunn[c].p_adjusted = running;
+            dunn[c].is_significant = p_value < 0.05 && running < 0.05;
+        }
+
+        let mut significant = vec![vec![false; k]; k];
+        for c in &dunn {
+            significant[c.group_i][c.group_j] = c.is_significant;
+            significant[c.group_j][c.group_i] = c.is_significant;
+        }
+
+        KruskalWallisResult {
+            test: NonparametricTest {
+                test: "Kruskal-Wallis".to_string(),
+                statistic: h,
+                z: None,
+                df: Some(df),
+                p_value,
+                exact: false,
+                effect_size: if n > 1.0 { h / (n - 1.0) } else { 0.0 },
+                effect_size_name: "epsilon_squared".to_string(),
+                is_significant: p_value < 0.05,
+            },
+            letters: Self::compact_letters(&mean_ranks, &significant),
+            medians: groups.iter().map(|g| Self::median(g)).collect(),
+            mean_ranks,
+            sizes,
+            dunn,
+        }
+    }
+
+    /// Mann-Whitney U test for two independent groups. Uses the exact null
+    /// distribution for small samples without ties, otherwise the normal
+    /// approximation with tie and continuity corrections. The effect size is
+    /// the rank-biserial correlation.
+    pub fn mann_whitney(group1: &[f64], group2: &[f64]) -> NonparametricTest {
+        let (n1, n2) = (group1.len(), group2.len());
+        let pooled: Vec<f64> = group1.iter().chain(group2.iter()).copied().collect();
+        let (ranks, ties) = Self::rank_with_ties(&pooled);
+        let r1: f64 = ranks[..n1].iter().sum();
+        let u1 = r1 - (n1 * (n1 + 1)) as f64 / 2.0;
+        let nn = (n1 * n2) as f64;
+        let n = (n1 + n2) as f64;
+
+        let exact = ties == 0.0 && n1 > 0 && n2 > 0 && n1 + n2 <= 40;
+        let (z, p_value) = if n1 == 0 || n2 == 0 {
+            (None, 1.0)
+        } else if exact {
+            // Number of arrangements giving each U, by the usual recursion
+            let max_u = n1 * n2;
+            let mut table = vec![vec![vec![0.0f64; max_u + 1]; n2 + 1]; n1 + 1];
+            for a in 0..=n1 {
+                for b in 0..=n2 {
+                    if a == 0 || b == 0 {
+                        table[a][b][0] = 1.0;
+                        continue;
+                    }
+                    for u in 0..=a * b {
+                        let with_a = if u >= b { table[a - 1][b][u - b] } else { 0.0 };
+                        table[a][b][u] = with_a + table[a][b - 1][u];
+                    }
+                }
+            }
+            let counts = &table[n1][n2];
+            let total: f64 = counts.iter().sum();
+            let u = u1.round() as usize;
+            let lower: f64 = counts[..=u.min(max_u)].iter().sum::<f64>() / total;
+            let upper: f64 = counts[u.min(max_u)..].iter().sum::<f64>() / total;
+            (None, (2.0 * lower.min(upper)).min(1.0))
+        } else {
+            let mean_u = nn / 2.0;
+            let var_u = nn / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+            let z = if var_u > 0.0 {
+                let d = u1 - mean_u;
+                (d - 0.5 * d.signum()) / var_u.sqrt()
+            } else {
+                0.0
+            };
+            (Some(z), Self::normal_two_sided_p(z))
+        };
+
+        NonparametricTest {
+            test: "Mann-Whitney U".to_string(),
+            statistic: u1,
+            z,
+            df: None,
+            p_value,
+            exact,
+            effect_size: if nn > 0.0 { 2.0 * u1 / nn - 1.0 } else { 0.0 },
+            effect_size_name: "rank_biserial".to_string(),
+            is_significant: p_value < 0.05,
+        }
+    }
+
+    /// Wilcoxon signed-rank test on paired observations; zero differences are
+    /// dropped. Exact for up to 50 pairs without ties, normal approximation
+    /// otherwise. The effect size is the matched-pairs rank-biserial correlation.
+    pub fn wilcoxon_signed_rank(group1: &[f64], group2: &[f64]) -> NonparametricTest {
+        let differences: Vec<f64> = group1
+            .iter()
+            .zip(group2.iter())
+            .map(|(a, b)| a - b)
+            .filter(|d| *d != 0.0)
+            .collect();
+        let n = differences.len();
+        let magnitudes: Vec<f64> = differences.iter().map(|d| d.abs()).collect();
+        let (ranks, ties) = Self::rank_with_ties(&magnitudes);
+        let w_plus: f64 = ranks.iter().zip(differences.iter()).filter(|(_, d)| **d > 0.0).map(|(r, _)| r).sum();
+        let total = (n * (n + 1)) as f64 / 2.0;
+
+        let exact = ties == 0.0 && n > 0 && n <= 50;
+        let (z, p_value) = if n == 0 {
+            (None, 1.0)
+        } else if exact {
+            let max_w = n * (n + 1) / 2;
+            let mut counts = vec![0.0f64; max_w + 1];
+            counts[0] = 1.0;
+            for r in 1..=n {
+                for w in (r..=max_w).rev() {
+                    counts[w] += counts[w - r];
+                }
+            }
+            let all: f64 = counts.iter().sum();
+            let w = w_plus.round() as usize;
+            let lower: f64 = counts[..=w].iter().sum::<f64>() / all;
+            let upper: f64 = counts[w..].iter().sum::<f64>() / all;
+            (None, (2.0 * lower.min(upper)).min(1.0))
+        } else {
+            let nf = n as f64;
+            let mean_w = total / 2.0;
+            let var_w = nf * (nf + 1.0) * (2.0 * nf + 1.0) / 24.0 - ties / 48.0;
+            let z = if var_w > 0.0 {
+                let d = w_plus - mean_w;
+                (d - 0.5 * d.signum()) / var_w.sqrt()
+            } else {
+                0.0
+            };
+            (Some(z), Self::normal_two_sided_p(z))
+        };
+
+        NonparametricTest {
+            test: "Wilcoxon signed-rank".to_string(),
+            statistic: w_plus,
+            z,
+            df: None,
+            p_value,
+            exact,
+            effect_size: if total > 0.0 { (2.0 * w_plus - total) / total } else { 0.0 },
+            effect_size_name: "rank_biserial".to_string(),
+            is_significant: p_value < 0.05,
+        }
+    }
+
+    /// Friedman test for a complete block design; `data[t][b]` is treatment `t`
+    /// in block `b`. Ranks are taken within blocks with the tie correction;
+    /// the effect size is Kendall's W.
+    pub fn friedman(data: &[Vec<f64>]) -> FriedmanResult {
+        let t = data.len();
+        let b = data.iter().map(|row| row.len()).min().unwrap_or(0);
+        let mut rank_sums = vec![0.0; t];
+        let mut ties = 0.0;
+        for j in 0..b {
+            let block: Vec<f64> = data.iter().map(|row| row[j]).collect();
+            let (ranks, block_ties) = Self::rank_with_ties(&block);
+            for (i, r) in ranks.iter().enumerate() {
+                rank_sums[i] += r;
+            }
+            ties += block_ties;
+        }
+
+        let (tf, bf) = (t as f64, b as f64);
+        let expected = bf * (tf + 1.0) / 2.0;
+        let ss: f64 = rank_sums.iter().map(|r| (r - expected).powi(2)).sum();
+        let denominator = bf * tf * (tf + 1.0) / 12.0 - ties / (12.0 * (tf - 1.0).max(1.0));
+        let chi2 = if denominator > 0.0 { ss / denominator } else { 0.0 };
+        let df = (tf - 1.0).max(0.0);
+        let p_value = Self::chi_square_p_value(chi2, df);
+
+        FriedmanResult {
+            test: NonparametricTest {
+                test: "Friedman".to_string(),
+                statistic: chi2,
+                z: None,
+                df: Some(df),
+                p_value,
+                exact: false,
+                effect_size: if bf * df > 0.0 { chi2 / (bf * df) } else { 0.0 },
+                effect_size_name: "kendall_w".to_string(),
+                is_significant: p_value < 0.05,
+            },
+            mean_ranks: rank_sums.iter().map(|r| if b > 0 { r / bf } else { 0.0 }).collect(),
+            medians: data.iter().map(|row| Self::median(&row[..b.min(row.len())])).collect(),
+        }
+    }
+
+    /// Mid-ranks (1-based) of the values and the tie term sum(t^3 - t).
+    fn rank_with_ties(values: &[f64]) -> (Vec<f64>, f64) {
+        let mut order: Vec<usize> = (0..values.len()).collect();
+        order.sort_by(|&a, &b| values[a].partial_cmp(&values[b]).unwrap_or(std::cmp::Ordering::Equal));
+        let mut ranks = vec![0.0; values.len()];
+        let mut ties = 0.0;
+        let mut start = 0;
+        while start < order.len() {
+            let mut end = start + 1;
+            while end < order.len() && values[order[end]] == values[order[start]] {
+                end += 1;
+            }
+            let rank = (start + end + 1) as f64 / 2.0;
+            for &i in &order[start..end] {
+                ranks[i] = rank;
+            }
+            let size = (end - start) as f64;
+            ties += size * size * size - size;
+            start = end;
+        }
+        (ranks, ties)
+    }
+
+    fn median(values: &[f64]) -> f64 {
+        if values.is_empty() {
+            return 0.0;
+        }
+        let mut data = Data::new(values.to_vec());
+        data.median()
+    }
+
+    fn chi_square_p_value(statistic: f64, df: f64) -> f64 {
+        if df > 0.0 {
+            if let Ok(dist) = ChiSquared::new(df) {
+                return 1.0 - dist.cdf(statistic);
+            }
+        }
+        1.0
+    }
+
+    fn normal_two_sided_p(z: f64) -> f64 {
+        Normal::new(0.0, 1.0)
+            .map(|d| 2.0 * (1.0 - d.cdf(z.abs())))
+            .unwrap_or(1.0)
+    }
+}
+
+// ==============================================================================
+// REPEATED-MEASURES ANOVA
+// ==============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepeatedMeasuresResult {
+    pub treatment: AnovaSource,     // tested against subjects within treatments
+    pub error_between: AnovaSource, // subjects (units) within treatments
+    pub time: AnovaSource,
+    pub interaction: AnovaSource, // treatment × time
+    pub error_within: AnovaSource,
+    pub total: AnovaSource,
+    pub sphericity: SphericityTest,
+    pub epsilon_gg: f64, // Greenhouse-Geisser
+    pub epsilon_hf: f64, // Huynh-Feldt (Lecoutre correction), capped at 1
+    pub time_p_gg: Option<f64>,
+    pub time_p_hf: Option<f64>,
+    pub interaction_p_gg: Option<f64>,
+    pub interaction_p_hf: Option<f64>,
+    pub cell_means: Vec<Vec<f64>>, // [treatment][time]
+    pub treatment_means: Vec<f64>,
+    pub time_means: Vec<f64>,
+    pub subjects: Vec<usize>, // units per treatment
+    pub grand_mean: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SphericityTest {
+    pub mauchly_w: f64,
+    pub chi_square: f64,
+    pub df: f64,
+    pub p_value: f64,
+    pub passed: bool, // p >= 0.05; otherwise use the corrected p-values
+}
+
+/// Unit-by-session values of one parameter, shaped for `repeated_measures_anova`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepeatedMeasuresData {
+    pub times: Vec<String>,  // session codes, in order of days after treatment
+    pub groups: Vec<String>, // treatments
+    pub data: Vec<Vec<Vec<f64>>>, // [group][unit][time]
+    pub excluded_units: Vec<String>, // block/unit codes missing one or more sessions
+}
+
+impl StatisticalAnalysis {
+    /// Mixed-design repeated-measures ANOVA: treatments between units, time
+    /// (monitoring sessions) within units.
+    ///
+    /// `data[g][s][t]` is unit `s` of treatment `g` at time `t`; every unit
+    /// must have all times. Groups may differ in size. Sphericity is checked
+    /// with Mauchly's test on the pooled within-treatment covariance, and the
+    /// time and treatment × time tests are also given with Greenhouse-Geisser
+    /// and Huynh-Feldt corrected degrees of freedom.
+    pub fn repeated_measures_anova(data: &[Vec<Vec<f64>>]) -> RepeatedMeasuresResult {
+        let a = data.len();
+        let t = data.iter().flatten().map(|s| s.len()).max().unwrap_or(0);
+        let subjects: Vec<usize> = data.iter().map(|g| g.len()).collect();
+        let n: usize = subjects.iter().sum();
+        let (af, tf, nf) = (a as f64, t as f64, n as f64);
+
+        let mean = |v: &[f64]| if v.is_empty() { 0.0 } else { v.iter().sum::<f64>() / v.len() as f64 };
+        let all: Vec<f64> = data.iter().flatten().flatten().copied().collect();
+        let grand_mean = mean(&all);
+
+        let cell_means: Vec<Vec<f64>> = data
+            .iter()
+            .map(|g| (0..t).map(|j| mean(&g.iter().map(|s| s[j]).collect::<Vec<_>>())).collect())
+            .collect();
+        let treatment_means: Vec<f64> = data.iter().map(|g| mean(&g.iter().flatten().copied().collect::<Vec<_>>())).collect();
+        let time_means: Vec<f64> = (0..t)
+            .map(|j| mean(&data.iter().flatten().map(|s| s[j]).collect::<Vec<_>>()))
+            .collect();
+
+        let ss_total: f64 = all.iter().map(|y| (y - grand_mean).powi(2)).sum();
+        let ss_subjects: f64 = data.iter().flatten().map(|s| tf * (mean(s) - grand_mean).powi(2)).sum();
+        let ss_treatment: f64 = treatment_means
+            .iter()
+            .zip(subjects.iter())
+            .map(|(m, &ns)| tf * ns as f64 * (m - grand_mean).powi(2))
+            .sum();
+        let ss_error_between = (ss_subjects - ss_treatment).max(0.0);
+        let ss_time: f64 = time_means.iter().map(|m| nf * (m - grand_mean).powi(2)).sum();
+        let ss_cells: f64 = cell_means
+            .iter()
+            .zip(subjects.iter())
+            .map(|(row, &ns)| row.iter().map(|m| ns as f64 * (m - grand_mean).powi(2)).sum::<f64>())
+            .sum();
+        let ss_interaction = (ss_cells - ss_treatment - ss_time).max(0.0);
+        let ss_error_within = (ss_total - ss_subjects - ss_time - ss_interaction).max(0.0);
+
+        let df_treatment = (af - 1.0).max(0.0);
+        let df_error_between = (nf - af).max(0.0);
+        let df_time = (tf - 1.0).max(0.0);
+        let df_interaction = df_treatment * df_time;
+        let df_error_within = df_error_between * df_time;
+
+        let mean_square = |ss: f64, df: f64| if df > 0.0 { ss / df } else { 0.0 };
+        let ms_error_between = mean_square(ss_error_between, df_error_between);
+        let ms_error_within = mean_square(ss_error_within, df_error_within);
+
+        let tested = |ss: f64, df: f64, ms_err: f64, df_err: f64| {
+            let ms = mean_square(ss, df);
+            let f = if ms_err > 0.0 { Some(ms / ms_err) } else { None };
+            AnovaSource {
+                ss,
+                df: df as i32,
+                ms,
+                f,
+                p: f.map(|f| Self::f_p_value(f, df, df_err)),
+            }
+        };
+        let error = |ss: f64, df: f64| AnovaSource {
+            ss,
+            df: df as i32,
+            ms: mean_square(ss, df),
+            f: None,
+            p: None,
+        };
+
+        let time = tested(ss_time, df_time, ms_error_within, df_error_within);
+        let interaction = tested(ss_interaction, df_interaction, ms_error_within, df_error_within);
+
+        // Pooled within-treatment covariance of the repeated measures,
+        // projected onto orthonormal (Helmert) contrasts among the times
+        let p = t.saturating_sub(1);
+        let mut covariance = DMatrix::<f64>::zeros(t, t);
+        for (g, units) in data.iter().enumerate() {
+            for s in units {
+                let d = DVector::from_iterator(t, (0..t).map(|j| s[j] - cell_means[g][j]));
+                covariance += &d * d.transpose();
+            }
+        }
+        if df_error_between > 0.0 {
+            covariance /= df_error_between;
+        }
+        let mut contrasts = DMatrix::<f64>::zeros(p, t);
+        for i in 0..p {
+            let norm = (((i + 1) * (i + 2)) as f64).sqrt();
+            for j in 0..=i {
+                contrasts[(i, j)] = 1.0 / norm;
+            }
+            contrasts[(i, i + 1)] = -((i + 1) as f64) / norm;
+        }
+        let projected = &contrasts * &covariance * contrasts.transpose();
+        let pf = p as f64;
+        let trace = projected.trace();
+
+        let epsilon_gg = if p > 0 && trace > 0.0 {
+            let trace_sq = (&projected * &projected).trace();
+            (trace * trace / (pf * trace_sq)).clamp(1.0 / pf, 1.0)
+        } else {
+            1.0
+        };
+        let epsilon_hf = if p > 0 {
+            let numerator = (nf - af + 1.0) * pf * epsilon_gg - 2.0;
+            let denominator = pf * (nf - af - pf * epsilon_gg);
+            if denominator > 0.0 { (numerator / denominator).clamp(epsilon_gg, 1.0) } else { 1.0 }
+        } else {
+            1.0
+        };
+
+        let sphericity = if p >= 2 && trace > 0.0 && df_error_between > pf {
+            let w = (projected.determinant() / (trace / pf).powf(pf)).clamp(0.0, 1.0);
+            let correction = df_error_between - (2.0 * pf * pf + pf + 2.0) / (6.0 * pf);
+            let chi_square = if w > 0.0 { -correction * w.ln() } else { f64::MAX };
+            let df = pf * (pf + 1.0) / 2.0 - 1.0;
+            let p_value = Self::chi_square_p_value(chi_square, df);
+            SphericityTest {
+                mauchly_w: w,
+                chi_square,
+                df,
+                p_value,
+                passed: p_value >= 0.05,
+            }
+        } else {
+            // Two times (or too few units): sphericity holds trivially / cannot be tested
+            SphericityTest {
+                mauchly_w: 1.0,
+                chi_square: 0.0,
+                df: 0.0,
+                p_value: 1.0,
+                passed: true,
+            }
+        };
+
+        let corrected = |source: &AnovaSource, df: f64, epsilon: f64| {
+            source.f.map(|f| Self::f_p_value(f, df * epsilon, df_error_within * epsilon))
+        };
+
+        RepeatedMeasuresResult {
+            treatment: tested(ss_treatment, df_treatment, ms_error_between, df_error_between),
+            error_between: error(ss_error_between, df_error_between),
+            time_p_gg: corrected(&time, df_time, epsilon_gg),
+            time_p_hf: corrected(&time, df_time, epsilon_hf),
+            interaction_p_gg: corrected(&interaction, df_interaction, epsilon_gg),
+            interaction_p_hf: corrected(&interaction, df_interaction, epsilon_hf),
+            time,
+            interaction,
+            error_within: error(ss_error_within, df_error_within),
+            total: AnovaSource {
+                ss: ss_total,
+                df: (nf * tf - 1.0).max(0.0) as i32,
+                ms: 0.0,
+                f: None,
+                p: None,
+            },
+            sphericity,
+            epsilon_gg,
+            epsilon_hf,
+            cell_means,
+            treatment_means,
+            time_means,
+            subjects,
+            grand_mean,
+        }
+    }
+
+    /// Loads one parameter across all monitoring sessions, one series per
+    /// experimental unit grouped by treatment. Excluded or inactive units and
+    /// flagged outliers are skipped; units without a value in every session
+    /// that has data are left out and listed.
+    pub async fn fetch_repeated_measures(
+        conn: &mut PgConnection,
+        project_id: Uuid,
+        parameter_id: Uuid,
+    ) -> Result<RepeatedMeasuresData, AppError> {
+        let rows: Vec<(Uuid, String, String, Uuid, String, Option<f64>)> = sqlx::query_as(
+            r#"
+            SELECT
+                eu.id,
+                eb.block_code || '/' || eu.unit_code,
+                COALESCE(f.code, eb.treatment_description, eb.block_code),
+                ms.id,
+                ms.session_code,
+                md.numeric_value::float8
+            FROM monitoring_data md
+            JOIN experimental_units eu ON md.unit_id = eu.id
+            JOIN experimental_blocks eb ON eu.block_id = eb.id
+            LEFT JOIN formulas f ON eb.formula_id = f.id
+            JOIN monitoring_sessions ms ON md.session_id = ms.id
+            WHERE eb.project_id = $1
+            AND md.parameter_id = $2
+            AND md.numeric_value IS NOT NULL
+            AND COALESCE(eu.is_active, true)
+            AND eu.excluded_reason IS NULL
+            AND NOT COALESCE(md.is_outlier, false)
+            ORDER BY ms.days_after_treatment NULLS LAST, ms.scheduled_date, eb.block_code, eu.unit_code
+            "#
+        )
+        .bind(project_id)
+        .bind(parameter_id)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        let mut sessions: Vec<(Uuid, String)> = Vec::new();
+        let mut groups: Vec<String> = Vec::new();
+        let mut units: Vec<(Uuid, String, usize, HashMap<usize, f64>)> = Vec::new();
+        for (unit_id, unit_code, treatment, session_id, session_code, value) in rows {
+            let Some(value) = value else { continue };
+            let time = match sessions.iter().position(|(id, _)| *id == session_id) {
+                Some(i) => i,
+                None => {
+                    sessions.push((session_id, session_code));
+                    sessions.len() - 1
+                }
+            };
+            let group = match groups.iter().position(|g| *g == treatment) {
+                Some(i) => i,
+                None => {
+                    groups.push(treatment);
+                    groups.len() - 1
+                }
+            };
+            match units.iter_mut().find(|(id, _, _, _)| *id == unit_id) {
+                Some((_, _, _, values)) => {
+                    values.insert(time, value);
+                }
+                None => units.push((unit_id, unit_code, group, HashMap::from([(time, value)]))),
+            }
+        }
+
+        let mut data = vec![Vec::new(); groups.len()];
+        let mut excluded_units = Vec::new();
+        for (_, unit_code, group, values) in units {
+            let series: Option<Vec<f64>> = (0..sessions.len()).map(|t| values.get(&t).copied()).collect();
+            match series {
+                Some(series) => data[group].push(series),
+                None => excluded_units.push(unit_code),
+            }
+        }
+
+        Ok(RepeatedMeasuresData {
+            times: sessions.into_iter().map(|(_, code)| code).collect(),
+            groups,
+            data,
+            excluded_units,
+        })
+    }
+}
+
+// ==============================================================================
+// GROWTH CURVES
+// ==============================================================================
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GrowthModel {
+    #[default]
+    Logistic, // y = A / (1 + exp(-k (x - xi)))
+    Gompertz, // y = A exp(-exp(-k (x - xi)))
+    Richards, // y = A (1 + nu exp(-k (x - xi)))^(-1 / nu)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrowthCurveFit {
+    pub model: GrowthModel,
+    pub asymptote: f64,
+    pub rate_constant: f64,
+    pub inflection_time: f64, // days after treatment
+    pub inflection_value: f64,
+    pub max_growth_rate: f64, // per day, at the inflection point
+    pub shape: Option<f64>, // Richards nu
+    pub r_squared: f64,
+    pub rmse: f64,
+    pub aic: f64,
+    pub n: usize,
+    pub converged: bool,
+}
+
+/// Observed growth series of one treatment's plots.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrowthTreatmentData {
+    pub treatment: String,
+    pub plots: Vec<GrowthPlotData>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrowthPlotData {
+    pub plot: String,
+    pub points: Vec<(f64, f64)>, // (days after treatment, plot mean)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrowthCurveAnalysis {
+    pub model: GrowthModel,
+    pub treatments: Vec<TreatmentGrowth>,
+    pub comparisons: Vec<GrowthParameterComparison>,
+    pub dropped_treatments: Vec<String>, // fewer than 2 converged plot fits, left out of the comparisons
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreatmentGrowth {
+    pub treatment: String,
+    pub fit: Option<GrowthCurveFit>, // all plots' points pooled
+    pub plots: Vec<PlotGrowth>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlotGrowth {
+    pub plot: String,
+    pub fit: Option<GrowthCurveFit>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrowthParameterComparison {
+    pub parameter: String,
+    pub treatments: Vec<String>, // compared treatments, aligned with the ANOVA groups
+    pub anova: AnovaResult, // on per-plot estimates
+    pub mean_separation: Option<MeanSeparationResult>,
+}
+
+impl GrowthModel {
+    fn value(&self, theta: &[f64], x: f64) -> f64 {
+        let (a, k, xi) = (theta[0], theta[1], theta[2]);
+        match self {
+            GrowthModel::Logistic => a / (1.0 + (-k * (x - xi)).exp()),
+            GrowthModel::Gompertz => a * (-(-k * (x - xi)).exp()).exp(),
+            GrowthModel::Richards => {
+                let nu = theta[3].max(1e-3);
+                a * (1.0 + nu * (-k * (x - xi)).exp()).powf(-1.0 / nu)
+            }
+        }
+    }
+}
+
+impl GrowthCurveFit {
+    /// Fitted value at `x` days after treatment.
+    pub fn predict(&self, x: f64) -> f64 {
+        let mut theta = vec![self.asymptote, self.rate_constant, self.inflection_time];
+        theta.extend(self.shape);
+        self.model.value(&theta, x)
+    }
+}
+
+impl StatisticalAnalysis {
+    /// Fits a growth curve by Levenberg-Marquardt nonlinear least squares.
+    ///
+    /// Starting values come from the linearized curve (logit or log-log of
+    /// y / A); Richards starts from the logistic solution. Returns `None` when
+    /// there are fewer points than parameters plus one.
+    pub fn fit_growth_curve(model: GrowthModel, points: &[(f64, f64)]) -> Option<GrowthCurveFit> {
+        let n_params = if model == GrowthModel::Richards { 4 } else { 3 };
+        let n = points.len();
+        if n <= n_params {
+            return None;
+        }
+
+        let (x_min, x_max) = points
+            .iter()
+            .fold((f64::MAX, f64::MIN), |(lo, hi), p| (lo.min(p.0), hi.max(p.0)));
+        let y_max = points.iter().map(|p| p.1).fold(f64::MIN, f64::max);
+        if y_max <= 0.0 || x_max <= x_min {
+            return None;
+        }
+
+        let start = match model {
+            GrowthModel::Richards => {
+                let logistic = Self::fit_growth_curve(GrowthModel::Logistic, points)?;
+                vec![logistic.asymptote, logistic.rate_constant, logistic.inflection_time, 1.0]
+            }
+            _ => {
+                let a0 = y_max * 1.1;
+                let linearized: Vec<(f64, f64)> = points
+                    .iter()
+                    .filter(|p| p.1 > 0.0 && p.1 < a0)
+                    .map(|&(x, y)| {
+                        let z = if model == GrowthModel::Logistic {
+                            (y / (a0 - y)).ln()
+                        } else {
+                            -(-(y / a0).ln()).ln()
+                        };
+                        (x, z)
+                    })
+                    .collect();
+                let (k0, xi0) = Self::simple_regression(&linearized)
+                    .filter(|(_, slope)| *slope > 0.0)
+                    .map(|(intercept, slope)| (slope, -intercept / slope))
+                    .unwrap_or((4.0 / (x_max - x_min), 0.5 * (x_min + x_max)));
+                vec![a0, k0, xi0]
+            }
+        };
+
+        let sse = |theta: &[f64]| -> f64 {
+            points.iter().map(|&(x, y)| (y - model.value(theta, x)).powi(2)).sum()
+        };
+
+        let mut theta = start;
+        let mut current = sse(&theta);
+        let mut lambda = 1e-3;
+        let mut converged = false;
+        for _ in 0..500 {
+            // Forward-difference Jacobian of the fitted values
+            let mut jacobian = DMatrix::<f64>::zeros(n, n_params);
+            for j in 0..n_params {
+                let h = 1e-6 * theta[j].abs().max(1e-3);
+                let mut shifted = theta.clone();
+                shifted[j] += h;
+                for (i, &(x, _)) in points.iter().enumerate() {
+                    jacobian[(i, j)] = (model.value(&shifted, x) - model.value(&theta, x)) / h;
+                }
+            }
+            let residuals = DVector::from_iterator(n, points.iter().map(|&(x, y)| y - model.value(&theta, x)));
+            let jtj = jacobian.transpose() * &jacobian;
+            let jtr = jacobian.transpose() * residuals;
+
+            let mut improved = false;
+            while lambda < 1e12 {
+                let mut damped = jtj.clone();
+                for j in 0..n_params {
+                    damped[(j, j)] += lambda * jtj[(j, j)].max(1e-12);
+                }
+                let Some(step) = damped.lu().solve(&jtr) else {
+                    lambda *= 10.0;
+                    continue;
+                };
+                let candidate: Vec<f64> = theta.iter().zip(step.iter()).map(|(t, s)| t + s).collect();
+                let value = sse(&candidate);
+                if value.is_finite() && value < current {
+                    let relative = (current - value) / current.max(1e-300);
+                    theta = candidate;
+                    current = value;
+                    lambda = (lambda / 10.0).max(1e-12);
+                    improved = true;
+                    converged = relative < 1e-10;
+                    break;
+                }
+                lambda *= 10.0;
+            }
+            if !improved {
+                converged = true;
+                break;
+            }
+            if converged {
+                break;
+            }
+        }
+
+        let (a, k, xi) = (theta[0], theta[1], theta[2]);
+        let (inflection_value, max_growth_rate, shape) = match model {
+            GrowthModel::Logistic => (a / 2.0, a * k / 4.0, None),
+            GrowthModel::Gompertz => (a / std::f64::consts::E, a * k / std::f64::consts::E, None),
+            GrowthModel::Richards => {
+                let nu = theta[3].max(1e-3);
+                (a * (1.0 + nu).powf(-1.0 / nu), a * k * (1.0 + nu).powf(-1.0 / nu - 1.0), Some(nu))
+            }
+        };
+
+        let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n as f64;
+        let sst: f64 = points.iter().map(|p| (p.1 - mean_y).powi(2)).sum();
+        let nf = n as f64;
+
+        Some(GrowthCurveFit {
+            model,
+            asymptote: a,
+            rate_constant: k,
+            inflection_time: xi,
+            inflection_value,
+            max_growth_rate,
+            shape,
+            r_squared: if sst > 0.0 { 1.0 - current / sst } else { 0.0 },
+            rmse: (current / nf).sqrt(),
+            aic: nf * (current.max(1e-300) / nf).ln() + 2.0 * (n_params + 1) as f64,
+            n,
+            converged: converged && a.is_finite() && k > 0.0,
+        })
+    }
+
+    /// Fits the growth model to every plot and to each treatment's pooled
+    /// points, then compares the per-plot asymptote, maximum growth rate and
+    /// inflection time between treatments by one-way ANOVA. Treatments with
+    /// fewer than 2 converged plot fits are left out of the comparisons.
+    pub fn growth_curve_analysis(
+        model: GrowthModel,
+        treatments: &[GrowthTreatmentData],
+        post_hoc: PostHocTest,
+    ) -> GrowthCurveAnalysis {
+        let fitted: Vec<TreatmentGrowth> = treatments
+            .iter()
+            .map(|t| {
+                let pooled: Vec<(f64, f64)> = t.plots.iter().flat_map(|p| p.points.iter().copied()).collect();
+                TreatmentGrowth {
+                    treatment: t.treatment.clone(),
+                    fit: Self::fit_growth_curve(model, &pooled),
+                    plots: t
+                        .plots
+                        .iter()
+                        .map(|p| PlotGrowth {
+                            plot: p.plot.clone(),
+                            fit: Self::fit_growth_curve(model, &p.points),
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        let converged = |t: &TreatmentGrowth| {
+            t.plots.iter().filter(|p| p.fit.as_ref().map(|f| f.converged).unwrap_or(false)).count()
+        };
+        let (compared, dropped): (Vec<&TreatmentGrowth>, Vec<&TreatmentGrowth>) =
+            fitted.iter().partition(|t| converged(t) >= 2);
+
+        type Estimate = fn(&GrowthCurveFit) -> f64;
+        let estimates: [(&str, Estimate); 3] = [
+            ("asymptote", |f| f.asymptote),
+            ("max_growth_rate", |f| f.max_growth_rate),
+            ("inflection_time", |f| f.inflection_time),
+        ];
+        let comparisons = estimates
+            .iter()
+            .filter_map(|(name, estimate)| {
+                let groups: Vec<Vec<f64>> = compared
+                    .iter()
+                    .map(|t| {
+                        t.plots
+                            .iter()
+                            .filter_map(|p| p.fit.as_ref().filter(|f| f.converged).map(estimate))
+                            .collect()
+                    })
+                    .collect();
+                if groups.len() < 2 {
+                    return None;
+                }
+                let anova = Self::one_way_anova(&groups);
+                let mean_separation = anova.is_significant_05.then(|| {
+                    Self::mean_separation(
+                        post_hoc,
+                        &anova.group_means,
+                        &anova.group_sizes,
+                        anova.source_within.ms,
+                        anova.source_within.df as f64,
+                        0.05,
+                    )
+                });
+                Some(GrowthParameterComparison {
+                    parameter: name.to_string(),
+                    treatments: compared.iter().map(|t| t.treatment.clone()).collect(),
+                    anova,
+                    mean_separation,
+                })
+            })
+            .collect();
+
+        let dropped_treatments = dropped.iter().map(|t| t.treatment.clone()).collect();
+
+        GrowthCurveAnalysis {
+            model,
+            treatments: fitted,
+            comparisons,
+            dropped_treatments,
+        }
+    }
+
+    /// Loads each plot's (days after treatment, mean value) series for one
+    /// parameter, grouped by treatment so plots are the replicates, as in the
+    /// report. Sessions without a DAT, excluded or inactive units and flagged
+    /// outliers are skipped.
+    pub async fn fetch_growth_series(
+        conn: &mut PgConnection,
+        project_id: Uuid,
+        parameter_id: Uuid,
+    ) -> Result<Vec<GrowthTreatmentData>, AppError> {
+        let rows: Vec<(String, String, i32, Option<f64>)> = sqlx::query_as(
+            r#"
+            SELECT
+                COALESCE(f.code, eb.treatment_description, eb.block_code) AS treatment,
+                eb.block_code,
+                ms.days_after_treatment,
+                AVG(md.numeric_value)::float8
+            FROM monitoring_data md
+            JOIN experimental_units eu ON md.unit_id = eu.id
+            JOIN experimental_blocks eb ON eu.block_id = eb.id
+            LEFT JOIN formulas f ON eb.formula_id = f.id
+            JOIN monitoring_sessions ms ON md.session_id = ms.id
+            WHERE eb.project_id = $1
+            AND md.parameter_id = $2
+            AND md.numeric_value IS NOT NULL
+            AND ms.days_after_treatment IS NOT NULL
+            AND COALESCE(eu.is_active, true)
+            AND eu.excluded_reason IS NULL
+            AND NOT COALESCE(md.is_outlier, false)
+            GROUP BY f.code, eb.treatment_description, eb.block_code, ms.days_after_treatment
+            ORDER BY treatment, eb.block_code, ms.days_after_treatment
+            "#
+        )
+        .bind(project_id)
+        .bind(parameter_id)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        let mut treatments: Vec<GrowthTreatmentData> = Vec::new();
+        for (treatment_key, block_code, dat, value) in rows {
+            let Some(value) = value else { continue };
+            let point = (dat as f64, value);
+            let treatment = match treatments.iter_mut().find(|t| t.treatment == treatment_key) {
+                Some(t) => t,
+                None => {
+                    treatments.push(GrowthTreatmentData { treatment: treatment_key, plots: Vec::new() });
+                    treatments.last_mut().expect("just pushed")
+                }
+            };
+            match treatment.plots.iter_mut().find(|p| p.plot == block_code) {
+                Some(plot) => plot.points.push(point),
+                None => treatment.plots.push(GrowthPlotData {
+                    plot: block_code,
+                    points: vec![point],
+                }),
+            }
+        }
+
+        Ok(treatments)
+    }
+
+    /// Ordinary least-squares line through the points; returns (intercept, slope).
+    fn simple_regression(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+        if points.len() < 2 {
+            return None;
+        }
+        let n = points.len() as f64;
+        let mx = points.iter().map(|p| p.0).sum::<f64>() / n;
+        let my = points.iter().map(|p| p.1).sum::<f64>() / n;
+        let sxx: f64 = points.iter().map(|p| (p.0 - mx).powi(2)).sum();
+        let sxy: f64 = points.iter().map(|p| (p.0 - mx) * (p.1 - my)).sum();
+        if sxx <= 0.0 {
+            return None;
+        }
+        let slope = sxy / sxx;
+        Some((my - slope * mx, slope))
+    }
+}
+
+// ==============================================================================
+// DOSE-RESPONSE REGRESSION
+// ==============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DoseResponseModel {
+    Linear,        // y = a + b x
+    Quadratic,     // y = a + b x + c x²
+    LinearPlateau, // y = a + b min(x, x0)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoseResponseFit {
+    pub model: DoseResponseModel,
+    pub coefficients: Vec<(String, f64)>, // a, b, c or x0
+    pub r_squared: f64,
+    pub adjusted_r_squared: f64,
+    pub rmse: f64,
+    pub p_value: f64, // overall regression F test
+    pub agronomic_optimum: Option<f64>, // dose of maximum response within the tested range
+    pub max_response: Option<f64>,
+    pub economic_optimum: Option<f64>, // dose where marginal revenue equals marginal cost
+    pub economic_response: Option<f64>,
+}
+
+impl DoseResponseFit {
+    fn coefficient(&self, name: &str) -> f64 {
+        self.coefficients.iter().find(|(n, _)| n == name).map(|(_, v)| *v).unwrap_or(0.0)
+    }
+
+    /// Predicted response at `dose`.
+    pub fn predict(&self, dose: f64) -> f64 {
+        let (a, b) = (self.coefficient("a"), self.coefficient("b"));
+        match self.model {
+            DoseResponseModel::Linear => a + b * dose,
+            DoseResponseModel::Quadratic => a + b * dose + self.coefficient("c") * dose * dose,
+            DoseResponseModel::LinearPlateau => a + b * dose.min(self.coefficient("x0")),
+        }
+    }
+}
+
+impl StatisticalAnalysis {
+    /// Fits a response model over numeric (dose, response) points.
+    ///
+    /// Optima are restricted to the tested dose range: a linear response has
+    /// its agronomic optimum at the highest dose when the slope is positive,
+    /// a quadratic at the vertex when it is a maximum, and a linear-plateau
+    /// at the join point.
+    pub fn dose_response(model: DoseResponseModel, points: &[(f64, f64)]) -> Option<DoseResponseFit> {
+        let n_params = match model {
+            DoseResponseModel::Linear => 2,
+            DoseResponseModel::Quadratic | DoseResponseModel::LinearPlateau => 3,
+        };
+        let n = points.len();
+        let mut doses: Vec<f64> = points.iter().map(|p| p.0).collect();
+        doses.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        doses.dedup();
+        if n <= n_params || doses.len() < n_params {
+            return None;
+        }
+        let (min_dose, max_dose) = (doses[0], doses[doses.len() - 1]);
+
+        // Least squares on the given regressor columns; returns (coefficients, SSE)
+        let ols = |columns: &[fn(f64, f64) -> f64], x0: f64| -> Option<(Vec<f64>, f64)> {
+            let x = DMatrix::from_fn(n, columns.len(), |i, j| columns[j](points[i].0, x0));
+            let y = DVector::from_iterator(n, points.iter().map(|p| p.1));
+            let beta = (x.transpose() * &x).lu().solve(&(x.transpose() * &y))?;
+            let residuals = &y - &x * &beta;
+            Some((beta.iter().copied().collect(), residuals.dot(&residuals)))
+        };
+        let intercept: fn(f64, f64) -> f64 = |_, _| 1.0;
+        let linear: fn(f64, f64) -> f64 = |x, _| x;
+        let square: fn(f64, f64) -> f64 = |x, _| x * x;
+        let clipped: fn(f64, f64) -> f64 = |x, x0| x.min(x0);
+
+        let (coefficients, sse) = match model {
+            DoseResponseModel::Linear => {
+                let (beta, sse) = ols(&[intercept, linear], 0.0)?;
+                (vec![("a".to_string(), beta[0]), ("b".to_string(), beta[1])], sse)
+            }
+            DoseResponseModel::Quadratic => {
+                let (beta, sse) = ols(&[intercept, linear, square], 0.0)?;
+                (
+                    vec![("a".to_string(), beta[0]), ("b".to_string(), beta[1]), ("c".to_string(), beta[2])],
+                    sse,
+                )
+            }
+            DoseResponseModel::LinearPlateau => {
+                // Grid over the join point, then golden-section refinement
+                // around the best grid cell.
+                let columns = [intercept, clipped];
+                let sse_at = |x0: f64| ols(&columns, x0).map(|(_, s)| s).unwrap_or(f64::MAX);
+                let (low, high) = (doses[1], max_dose);
+                let steps = 100;
+                let step = (high - low) / steps as f64;
+                let best = (0..=steps)
+                    .map(|i| low + step * i as f64)
+                    .min_by(|a, b| sse_at(*a).partial_cmp(&sse_at(*b)).unwrap_or(std::cmp::Ordering::Equal))?;
+                let (mut lo, mut hi) = ((best - step).max(low), (best + step).min(high));
+                let ratio = (5f64.sqrt() - 1.0) / 2.0;
+                for _ in 0..60 {
+                    let (c, d) = (hi - ratio * (hi - lo), lo + ratio * (hi - lo));
+                    if sse_at(c) < sse_at(d) {
+                        hi = d;
+                    } else {
+                        lo = c;
+                    }
+                }
+                let x0 = 0.5 * (lo + hi);
+                let (beta, sse) = ols(&columns, x0)?;
+                (
+                    vec![("a".to_string(), beta[0]), ("b".to_string(), beta[1]), ("x0".to_string(), x0)],
+                    sse,
+                )
+            }
+        };
+
+        let nf = n as f64;
+        let mean_y = points.iter().map(|p| p.1).sum::<f64>() / nf;
+        let sst: f64 = points.iter().map(|p| (p.1 - mean_y).powi(2)).sum();
+        let r_squared = if sst > 0.0 { 1.0 - sse / sst } else { 0.0 };
+        let df_model = (n_params - 1) as f64;
+        let df_error = nf - n_params as f64;
+        let f = if sse > 0.0 { ((sst - sse) / df_model) / (sse / df_error) } else { f64::INFINITY };
+
+        let mut fit = DoseResponseFit {
+            model,
+            coefficients,
+            r_squared,
+            adjusted_r_squared: 1.0 - (1.0 - r_squared) * (nf - 1.0) / df_error,
+            rmse: (sse / df_error).sqrt(),
+            p_value: Self::f_p_value(f, df_model, df_error),
+            agronomic_optimum: None,
+            max_response: None,
+            economic_optimum: None,
+            economic_response: None,
+        };
+
+        let b = fit.coefficient("b");
+        fit.agronomic_optimum = match model {
+            DoseResponseModel::Linear => (b > 0.0).then_some(max_dose),
+            DoseResponseModel::Quadratic => {
+                let c = fit.coefficient("c");
+                (c < 0.0).then(|| (-b / (2.0 * c)).clamp(min_dose, max_dose))
+            }
+            DoseResponseModel::LinearPlateau => (b > 0.0).then(|| fit.coefficient("x0")),
+        };
+        fit.max_response = fit.agronomic_optimum.map(|x| fit.predict(x));
+
+        Some(fit)
+    }
+
+    /// Sets the economic optimum: the dose at which the marginal response is
+    /// worth exactly the marginal cost, i.e. dy/dx = cost per dose unit /
+    /// crop price. Falls back to the lowest tested dose when no tested dose
+    /// pays for itself.
+    pub fn economic_optimum(fit: &mut DoseResponseFit, price_ratio: f64, dose_range: (f64, f64)) {
+        let (min_dose, max_dose) = dose_range;
+        let b = fit.coefficient("b");
+        let dose = match fit.model {
+            DoseResponseModel::Linear => {
+                if b > price_ratio { max_dose } else { min_dose }
+            }
+            DoseResponseModel::Quadratic => {
+                let c = fit.coefficient("c");
+                if c < 0.0 {
+                    ((price_ratio - b) / (2.0 * c)).clamp(min_dose, max_dose)
+                } else if b + 2.0 * c * max_dose > price_ratio {
+                    max_dose
+                } else {
+                    min_dose
+                }
+            }
+            DoseResponseModel::LinearPlateau => {
+                if b > price_ratio { fit.coefficient("x0") } else { min_dose }
+            }
+        };
+        fit.economic_optimum = Some(dose);
+        fit.economic_response = Some(fit.predict(dose));
+    }
+
+    /// Reads the leading number of a free-text application rate such as
+    /// "2.5 L/ha" or "2,5 ml/L".
+    pub fn parse_dose(rate: &str) -> Option<f64> {
+        let start = rate.find(|c: char| c.is_ascii_digit())?;
+        let number: String = rate[start..]
+            .chars()
+            .take_while(|c| c.is_ascii_digit() || *c == '.' || *c == ',')
+            .map(|c| if c == ',' { '.' } else { c })
+            .collect();
+        number.trim_end_matches('.').parse().ok()
+    }
+}
+
+// ==============================================================================
+// REPLICATION PLANNING
+// ==============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoricalCv {
+    pub project_code: String,
+    pub session_code: String,
+    pub cv_percent: f64,
+    pub error_df: usize,
+    pub level: String, // "plot" (replicate plots) or "unit" (units within plots)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerPoint {
+    pub replications: usize,
+    pub error_df: usize,
+    pub power: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MainEffectPower {
+    pub factor: usize, // index into factor_levels
+    pub levels: usize,
+    pub power: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicationPlan {
+    pub design: ExperimentDesign,
+    pub treatments_count: usize,
+    pub cv_percent: f64,
+    pub detectable_difference_percent: f64,
+    pub alpha: f64,
+    pub target_power: f64,
+    pub recommended_replications: Option<usize>, // None when 20 replications are not enough
+    pub error_df: Option<usize>,
+    pub achieved_power: Option<f64>,
+    pub power_curve: Vec<PowerPoint>, // 2 to 20 replications
+    pub main_effects: Vec<MainEffectPower>, // factorial only, at the recommended replications
+}
+
+impl StatisticalAnalysis {
+    /// Power of the treatment F test to detect a difference of
+    /// `difference_percent` between two treatment means, for a residual
+    /// coefficient of variation `cv_percent`.
+    ///
+    /// Uses the least favourable configuration (the two means d apart, all
+    /// others midway), so the noncentrality is λ = r·(d/CV)²/2 with r plots per
+    /// treatment mean. RAL has t(r - 1) error df; RAK and factorial trials
+    /// (laid out in complete blocks) have (t - 1)(r - 1).
+    pub fn replication_plan(
+        design: ExperimentDesign,
+        treatments_count: usize,
+        factor_levels: &[usize],
+        cv_percent: f64,
+        difference_percent: f64,
+        alpha: f64,
+        target_power: f64,
+    ) -> ReplicationPlan {
+        let t = treatments_count;
+        let effect = difference_percent / cv_percent;
+        let error_df = |r: usize| match design {
+            ExperimentDesign::Ral => t * (r - 1),
+            _ => (t - 1) * (r - 1),
+        };
+        let power_at = |df1: usize, df2: usize, plots: f64| {
+            Self::f_test_power(df1 as f64, df2 as f64, plots * effect * effect / 2.0, alpha)
+        };
+
+        let power_curve: Vec<PowerPoint> = (2..=20)
+            .map(|r| PowerPoint {
+                replications: r,
+                error_df: error_df(r),
+                power: power_at(t - 1, error_df(r), r as f64),
+            })
+            .collect();
+        let recommended = power_curve.iter().find(|p| p.power >= target_power);
+
+        // A main-effect mean averages r * t / levels plots
+        let main_effects = match (&design, recommended) {
+            (ExperimentDesign::Factorial, Some(plan)) => factor_levels
+                .iter()
+                .enumerate()
+                .map(|(factor, &levels)| MainEffectPower {
+                    factor,
+                    levels,
+                    power: power_at(levels - 1, plan.error_df, (plan.replications * t) as f64 / levels as f64),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        ReplicationPlan {
+            design,
+            treatments_count: t,
+            cv_percent,
+            detectable_difference_percent: difference_percent,
+            alpha,
+            target_power,
+            recommended_replications: recommended.map(|p| p.replications),
+            error_df: recommended.map(|p| p.error_df),
+            achieved_power: recommended.map(|p| p.power),
+            power_curve,
+            main_effects,
+        }
+    }
+
+    /// Power of an F test with noncentrality `lambda`.
+    fn f_test_power(df1: f64, df2: f64, lambda: f64, alpha: f64) -> f64 {
+        let Ok(f_dist) = FisherSnedecor::new(df1, df2) else {
+            return 0.0;
+        };
+        let critical = f_dist.inverse_cdf(1.0 - alpha);
+        (1.0 - Self::noncentral_f_cdf(critical, df1, df2, lambda)).clamp(0.0, 1.0)
+    }
+
+    /// Noncentral F CDF as a Poisson mixture of regularized incomplete betas.
+    fn noncentral_f_cdf(f: f64, df1: f64, df2: f64, lambda: f64) -> f64 {
+        let x = df1 * f / (df1 * f + df2);
+        let half = lambda / 2.0;
+        let terms = (half + 10.0 * half.sqrt() + 50.0) as usize;
+        (0..terms)
+            .map(|j| {
+                let j = j as f64;
+                let weight = if half > 0.0 {
+                    (-half + j * half.ln() - ln_gamma(j + 1.0)).exp()
+                } else if j == 0.0 {
+                    1.0
+                } else {
+                    0.0
+                };
+                weight * beta_reg(df1 / 2.0 + j, df2 / 2.0, x)
+            })
+            .sum()
+    }
+
+    /// Residual CV% of one parameter in completed past projects of the same
+    /// crop, per project and session, leaving out `exclude_project_id`. The
+    /// parameter is matched by code or, failing that, by monitoring type.
+    /// Where treatments have replicate plots the CV comes from plot means,
+    /// with the RCBD error when a RAK project records replications;
+    /// otherwise from units within plots, which tends to overstate the
+    /// plot-level error.
+    pub async fn historical_cv(
+        pool: &PgPool,
+        crop_type: &str,
+        parameter_code: Option<&str>,
+        parameter_type: Option<MonitoringType>,
+        exclude_project_id: Option<Uuid>,
+    ) -> Result<Vec<HistoricalCv>, AppError> {
+        #[derive(sqlx::FromRow)]
+        struct CvRow {
+            project_code: String,
+            session_code: String,
+            is_rak: bool,
+            block_id: Uuid,
+            treatment: String,
+            replication: Option<i32>,
+            value: f64,
+        }
+
+        struct CvPlot {
+            block_id: Uuid,
+            treatment: String,
+            replication: Option<i32>,
+            values: Vec<f64>,
+        }
+
+        struct CvTrial {
+            project_code: String,
+            session_code: String,
+            is_rak: bool,
+            plots: Vec<CvPlot>,
+        }
+
+        let rows: Vec<CvRow> = sqlx::query_as(
+            r#"
+            SELECT
+                p.code as project_code,
+                ms.session_code,
+                COALESCE(p.experiment_design = 'rak', false) as is_rak,
+                eb.id as block_id,
+                COALESCE(eb.formula_id::text, eb.treatment_description, eb.block_code) as treatment,
+                eb.replication,
+                md.numeric_value::float8 as value
+            FROM monitoring_data md
+            JOIN experimental_units eu ON md.unit_id = eu.id
+            JOIN experimental_blocks eb ON eu.block_id = eb.id
+            JOIN projects p ON eb.project_id = p.id
+            JOIN monitoring_sessions ms ON md.session_id = ms.id
+            JOIN monitoring_parameters mp ON md.parameter_id = mp.id
+            WHERE LOWER(p.crop_type) = LOWER($1)
+            AND p.status = 'completed'
+            AND ($4::UUID IS NULL OR p.id <> $4)
+            AND md.numeric_value IS NOT NULL
+            AND (mp.code = $2 OR ($2 IS NULL AND mp.parameter_type = $3))
+            AND COALESCE(eu.is_active, true)
+            AND eu.excluded_reason IS NULL
+            AND NOT COALESCE(md.is_outlier, false)
+            ORDER BY p.code, ms.session_code, eb.id
+            "#
+        )
+        .bind(crop_type)
+        .bind(parameter_code)
+        .bind(parameter_type)
+        .bind(exclude_project_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        let mut trials: Vec<CvTrial> = Vec::new();
+        for row in rows {
+            let same_trial = trials
+                .last()
+                .is_some_and(|t| t.project_code == row.project_code && t.session_code == row.session_code);
+            if !same_trial {
+                trials.push(CvTrial {
+                    project_code: row.project_code,
+                    session_code: row.session_code,
+                    is_rak: row.is_rak,
+                    plots: Vec::new(),
+                });
+            }
+            let plots = &mut trials.last_mut().expect("just pushed").plots;
+            match plots.iter_mut().find(|p| p.block_id == row.block_id) {
+                Some(plot) => plot.values.push(row.value),
+                None => plots.push(CvPlot {
+                    block_id: row.block_id,
+                    treatment: row.treatment,
+                    replication: row.replication,
+                    values: vec![row.value],
+                }),
+            }
+        }
+
+        Ok(trials
+            .into_iter()
+            .filter_map(|trial| {
+                let mut treatments: Vec<&str> = trial.plots.iter().map(|p| p.treatment.as_str()).collect();
+                treatments.sort_unstable();
+                treatments.dedup();
+                if treatments.len() < 2 {
+                    return None;
+                }
+                let plot_mean = |p: &CvPlot| p.values.iter().sum::<f64>() / p.values.len() as f64;
+                let replicated = treatments
+                    .iter()
+                    .any(|t| trial.plots.iter().filter(|p| p.treatment == *t).count() >= 2);
+
+                // (residual MS, error df, grand mean, level)
+                let (ms_error, df_error, grand_mean, level) =
+                    if replicated && trial.is_rak && trial.plots.iter().all(|p| p.replication.is_some()) {
+                        let mut replications: Vec<i32> = trial.plots.iter().filter_map(|p| p.replication).collect();
+                        replications.sort_unstable();
+                        replications.dedup();
+                        let mut cells = vec![vec![None; replications.len()]; treatments.len()];
+                        for plot in &trial.plots {
+                            let t = treatments.binary_search(&plot.treatment.as_str()).ok()?;
+                            let r = replications.binary_search(&plot.replication?).ok()?;
+                            if cells[t][r].replace(plot_mean(plot)).is_some() {
+                                return None; // two plots of a treatment in one replication
+                            }
+                        }
+                        let anova = Self::rcbd_anova(&cells);
+                        (anova.error.ms, anova.error.df, anova.grand_mean, "plot")
+                    } else {
+                        let (groups, level): (Vec<Vec<f64>>, &str) = if replicated {
+                            let groups = treatments
+                                .iter()
+                                .map(|t| trial.plots.iter().filter(|p| p.treatment == *t).map(plot_mean).collect())
+                                .collect();
+                            (groups, "plot")
+                        } else {
+                            (trial.plots.iter().map(|p| p.values.clone()).collect(), "unit")
+                        };
+                        let anova = Self::one_way_anova(&groups);
+                        (anova.source_within.ms, anova.source_within.df, anova.grand_mean, level)
+                    };
+
+                (df_error > 0 && grand_mean > 0.0).then(|| HistoricalCv {
+                    project_code: trial.project_code,
+                    session_code: trial.session_code,
+                    cv_percent: ms_error.sqrt() / grand_mean * 100.0,
+                    error_df: df_error as usize,
+                    level: level.to_string(),
+                })
+            })
+            .collect())
+    }
+}
+
+// ==============================================================================
+// CORRELATION & PRINCIPAL COMPONENTS
+// ==============================================================================
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CorrelationMethod {
+    #[default]
+    Pearson,
+    Spearman,
+}
+
+/// Observation level for multivariate analysis.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataLevel {
+    Unit,
+    #[default]
+    TreatmentMean,
+}
+
+/// Observations × variables table: `values[obs][var]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultivariateData {
+    pub labels: Vec<String>,    // unit codes or treatment names
+    pub variables: Vec<String>, // parameter codes
+    pub values: Vec<Vec<f64>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorrelationMatrix {
+    pub method: CorrelationMethod,
+    pub variables: Vec<String>,
+    pub coefficients: Vec<Vec<f64>>,
+    pub p_values: Vec<Vec<f64>>, // two-sided, t approximation with n - 2 df
+    pub n: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PcaResult {
+    pub variables: Vec<String>,
+    pub labels: Vec<String>,
+    pub eigenvalues: Vec<f64>,
+    pub explained_variance: Vec<f64>, // proportion per component
+    pub cumulative_variance: Vec<f64>,
+    pub loadings: Vec<Vec<f64>>, // [variable][component], eigenvectors of the correlation matrix
+    pub scores: Vec<Vec<f64>>,   // [observation][component], standardized data × loadings
+}
+
+impl StatisticalAnalysis {
+    /// Pairwise correlation matrix. Spearman correlates mid-ranks.
+    pub fn correlation_matrix(data: &MultivariateData, method: CorrelationMethod) -> CorrelationMatrix {
+        let n = data.values.len();
+        let k = data.variables.len();
+        let columns: Vec<Vec<f64>> = (0..k)
+            .map(|v| {
+                let column: Vec<f64> = data.values.iter().map(|row| row[v]).collect();
+                match method {
+                    CorrelationMethod::Pearson => column,
+                    CorrelationMethod::Spearman => Self::rank_with_ties(&column).0,
+                }
+            })
+            .collect();
+
+        let t_dist = StudentsT::new(0.0, 1.0, n.saturating_sub(2) as f64).ok();
+        let mut coefficients = vec![vec![1.0; k]; k];
+        let mut p_values = vec![vec![0.0; k]; k];
+        for i in 0..k {
+            for j in (i + 1)..k {
+                let r = Self::pearson(&columns[i], &columns[j]);
+                let p = match (&t_dist, r.abs() < 1.0) {
+                    (Some(t), true) => {
+                        let statistic = r * ((n as f64 - 2.0) / (1.0 - r * r)).sqrt();
+                        2.0 * (1.0 - t.cdf(statistic.abs()))
+                    }
+                    (Some(_), false) => 0.0,
+                    (None, _) => 1.0,
+                };
+                coefficients[i][j] = r;
+                coefficients[j][i] = r;
+                p_values[i][j] = p;
+                p_values[j][i] = p;
+            }
+        }
+
+        CorrelationMatrix {
+            method,
+            variables: data.variables.clone(),
+            coefficients,
+            p_values,
+            n,
+        }
+    }
+
+    /// Principal component analysis of the standardized variables (the
+    /// eigen-decomposition of the correlation matrix). Each component's sign
+    /// is fixed so its largest loading is positive.
+    pub fn pca(data: &MultivariateData) -> PcaResult {
+        let n = data.values.len();
+        let k = data.variables.len();
+
+        let mut z = DMatrix::from_fn(n, k, |i, j| data.values[i][j]);
+        for j in 0..k {
+            let column = z.column(j);
+            let mean = column.mean();
+            let sd = if n > 1 {
+                (column.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1) as f64).sqrt()
+            } else {
+                0.0
+            };
+            for i in 0..n {
+                z[(i, j)] = if sd > 0.0 { (z[(i, j)] - mean) / sd } else { 0.0 };
+            }
+        }
+        let correlation = if n > 1 { z.transpose() * &z / (n - 1) as f64 } else { DMatrix::zeros(k, k) };
+
+        let eigen = correlation.symmetric_eigen();
+        let mut order: Vec<usize> = (0..k).collect();
+        order.sort_by(|&a, &b| {
+            eigen.eigenvalues[b].partial_cmp(&eigen.eigenvalues[a]).unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let eigenvalues: Vec<f64> = order.iter().map(|&c| eigen.eigenvalues[c].max(0.0)).collect();
+        let total: f64 = eigenvalues.iter().sum();
+        let explained_variance: Vec<f64> =
+            eigenvalues.iter().map(|e| if total > 0.0 { e / total } else { 0.0 }).collect();
+        let cumulative_variance = explained_variance
+            .iter()
+            .scan(0.0, |acc, e| {
+                *acc += e;
+                Some(*acc)
+            })
+            .collect();
+
+        let mut vectors = DMatrix::zeros(k, k);
+        for (component, &c) in order.iter().enumerate() {
+            let column = eigen.eigenvectors.column(c);
+            let largest = column.iter().cloned().fold(0.0, |m: f64, v| if v.abs() > m.abs() { v } else { m });
+            let sign = if largest < 0.0 { -1.0 } else { 1.0 };
+            for v in 0..k {
+                vectors[(v, component)] = sign * column[v];
+            }
+        }
+        let scores = &z * &vectors;
+
+        PcaResult {
+            variables: data.variables.clone(),
+            labels: data.labels.clone(),
+            eigenvalues,
+            explained_variance,
+            cumulative_variance,
+            loadings: (0..k).map(|v| vectors.row(v).iter().copied().collect()).collect(),
+            scores: (0..n).map(|i| scores.row(i).iter().copied().collect()).collect(),
+        }
+    }
+
+    fn pearson(x: &[f64], y: &[f64]) -> f64 {
+        let n = x.len().min(y.len());
+        if n < 2 {
+            return 0.0;
+        }
+        let mx = x.iter().sum::<f64>() / n as f64;
+        let my = y.iter().sum::<f64>() / n as f64;
+        let sxy: f64 = x.iter().zip(y).map(|(a, b)| (a - mx) * (b - my)).sum();
+        let sxx: f64 = x.iter().map(|a| (a - mx).powi(2)).sum();
+        let syy: f64 = y.iter().map(|b| (b - my).powi(2)).sum();
+        if sxx > 0.0 && syy > 0.0 { sxy / (sxx * syy).sqrt() } else { 0.0 }
+    }
+
+    /// Numeric parameters of one session, per unit or averaged per treatment.
+    /// Parameters without data in the session are dropped, then units missing
+    /// any remaining parameter are skipped. Units are labelled "block/unit",
+    /// as unit codes repeat across blocks.
+    pub async fn fetch_multivariate_data(
+        conn: &mut PgConnection,
+        project_id: Uuid,
+        session_id: Uuid,
+        level: DataLevel,
+    ) -> Result<MultivariateData, AppError> {
+        #[derive(sqlx::FromRow)]
+        struct MultivariateRow {
+            unit_id: Uuid,
+            label: String,
+            treatment: String,
+            parameter: String,
+            value: f64,
+        }
+
+        let rows: Vec<MultivariateRow> = sqlx::query_as(
+            r#"
+            SELECT
+                eu.id as unit_id,
+                eb.block_code || '/' || eu.unit_code as label,
+                COALESCE(f.code, eb.treatment_description, eb.block_code) as treatment,
+                mp.code as parameter,
+                md.numeric_value::float8 as value
+            FROM monitoring_data md
+            JOIN experimental_units eu ON md.unit_id = eu.id
+            JOIN experimental_blocks eb ON eu.block_id = eb.id
+            JOIN monitoring_parameters mp ON md.parameter_id = mp.id
+            LEFT JOIN formulas f ON eb.formula_id = f.id
+            WHERE eb.project_id = $1
+            AND md.session_id = $2
+            AND md.numeric_value IS NOT NULL
+            AND mp.data_type = 'numeric'
+            AND COALESCE(eu.is_active, true)
+            AND eu.excluded_reason IS NULL
+            AND NOT COALESCE(md.is_outlier, false)
+            ORDER BY mp.sort_order, mp.code, eb.block_code, eu.unit_code
+            "#
+        )
+        .bind(project_id)
+        .bind(session_id)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        let mut variables: Vec<String> = Vec::new();
+        // unit id -> (label, treatment, values by parameter)
+        let mut units: Vec<(Uuid, String, String, HashMap<String, f64>)> = Vec::new();
+        for row in rows {
+            if !variables.contains(&row.parameter) {
+                variables.push(row.parameter.clone());
+            }
+            match units.iter_mut().find(|u| u.0 == row.unit_id) {
+                Some(unit) => {
+                    unit.3.insert(row.parameter, row.value);
+                }
+                None => units.push((row.unit_id, row.label, row.treatment, HashMap::from([(row.parameter, row.value)]))),
+            }
+        }
+
+        let complete: Vec<(String, String, Vec<f64>)> = units
+            .into_iter()
+            .filter_map(|(_, label, treatment, values)| {
+                let row = variables.iter().map(|v| values.get(v).copied()).collect::<Option<Vec<f64>>>()?;
+                Some((label, treatment, row))
+            })
+            .collect();
+
+        Ok(match level {
+            DataLevel::Unit => MultivariateData {
+                labels: complete.iter().map(|u| u.0.clone()).collect(),
+                variables,
+                values: complete.into_iter().map(|u| u.2).collect(),
+            },
+            DataLevel::TreatmentMean => {
+                let mut labels: Vec<String> = Vec::new();
+                let mut sums: Vec<(Vec<f64>, usize)> = Vec::new();
+                for (_, treatment, row) in complete {
+                    let i = match labels.iter().position(|l| *l == treatment) {
+                        Some(i) => i,
+                        None => {
+                            labels.push(treatment);
+                            sums.push((vec![0.0; row.len()], 0));
+                            labels.len() - 1
+                        }
+                    };
+                    sums[i].0.iter_mut().zip(&row).for_each(|(s, v)| *s += v);
+                    sums[i].1 += 1;
+                }
+                MultivariateData {
+                    labels,
+                    variables,
+                    values: sums.into_iter().map(|(s, n)| s.into_iter().map(|v| v / n as f64).collect()).collect(),
+                }
+            }
+        })
+    }
+}
+
+// ==============================================================================
+// MULTI-ENVIRONMENT TRIALS (G × E)
+// ==============================================================================
+
+/// Plot values of the same treatments grown in several environments
+/// (projects at different locations or seasons). Parallel vectors, one entry
+/// per plot.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiEnvironmentData {
+    pub response: Vec<f64>,
+    pub environments: Vec<String>,
+    pub treatments: Vec<String>,
+    pub replications: Vec<String>, // labels only need to be unique within an environment
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiEnvironmentResult {
+    pub environments: Vec<String>,
+    pub treatments: Vec<String>,          // grown in every environment
+    pub excluded_treatments: Vec<String>, // missing from at least one environment
+    pub environment: AnovaSource,         // tested against replications within environments
+    pub replication: AnovaSource,         // replications within environments
+    pub treatment: AnovaSource,
+    pub interaction: AnovaSource, // treatment × environment
+    pub error: AnovaSource,       // pooled over environments
+    pub total: AnovaSource,
+    pub cv_percent: f64,
+    pub treatment_means: Vec<f64>,
+    pub environment_means: Vec<f64>,
+    pub environment_index: Vec<f64>, // environment mean - grand mean
+    pub cell_means: Vec<Vec<f64>>,   // [treatment][environment]
+    pub regression: Vec<EberhartRussellStability>, // empty with fewer than 3 environments
+    pub ammi: AmmiAnalysis,
+    pub ranking: Vec<TreatmentRanking>, // best combined rank first
+}
+
+/// Eberhart & Russell (1966) stability parameters. A stable treatment has
+/// b close to 1 and S²d close to 0.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EberhartRussellStability {
+    pub treatment: String,
+    pub mean: f64,
+    pub slope: f64, // b, response to the environment index
+    pub slope_std_error: f64,
+    pub slope_p_value: f64,      // H0: b = 1
+    pub deviation_variance: f64, // S²d, deviations from regression
+    pub deviation_p_value: f64,  // H0: S²d = 0
+    pub r_squared: f64,
+}
+
+/// Additive main effects and multiplicative interaction model: the
+/// interaction matrix is decomposed by SVD into interaction principal
+/// components (IPCA), with Gollob's degrees of freedom.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AmmiAnalysis {
+    pub axes: Vec<AmmiAxis>,
+    pub residual: AnovaSource, // interaction not captured by the axes
+    pub treatment_scores: Vec<Vec<f64>>,   // [treatment][axis]
+    pub environment_scores: Vec<Vec<f64>>, // [environment][axis]
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AmmiAxis {
+    pub source: AnovaSource,
+    pub interaction_percent: f64, // share of the treatment × environment SS
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreatmentRanking {
+    pub treatment: String,
+    pub mean: f64,
+    pub mean_rank: usize,
+    pub asv: f64,              // AMMI stability value, small is stable
+    pub stability_rank: usize, // by ASV
+    pub combined_rank: usize,  // by mean rank + stability rank
+}
+
+impl StatisticalAnalysis {
+    /// Combined analysis over environments followed by Eberhart-Russell and
+    /// AMMI stability analysis.
+    ///
+    /// Only treatments present in every environment are analysed. Treatment
+    /// and interaction are tested against the pooled error, the environment
+    /// against replications within environments. `lower_is_better` flips the
+    /// mean ranking for traits such as disease severity.
+    pub fn multi_environment_analysis(data: &MultiEnvironmentData, lower_is_better: bool) -> MultiEnvironmentResult {
+        let mut environments: Vec<String> = Vec::new();
+        for env in &data.environments {
+            if !environments.contains(env) {
+                environments.push(env.clone());
+            }
+        }
+        let mut all_treatments: Vec<String> = Vec::new();
+        for t in &data.treatments {
+            if !all_treatments.contains(t) {
+                all_treatments.push(t.clone());
+            }
+        }
+        let present = |t: &String, env: &String| {
+            (0..data.response.len()).any(|o| data.treatments[o] == *t && data.environments[o] == *env)
+        };
+        let (treatments, excluded_treatments): (Vec<String>, Vec<String>) = all_treatments
+            .into_iter()
+            .partition(|t| environments.iter().all(|env| present(t, env)));
+
+        let observations: Vec<(usize, usize, &String, f64)> = (0..data.response.len())
+            .filter_map(|o| {
+                let g = treatments.iter().position(|t| *t == data.treatments[o])?;
+                let e = environments.iter().position(|env| *env == data.environments[o])?;
+                Some((g, e, &data.replications[o], data.response[o]))
+            })
+            .collect();
+        let (g, e) = (treatments.len(), environments.len());
+
+        let model = GlmModel {
+            response: observations.iter().map(|o| o.3).collect(),
+            factors: vec![
+                GlmFactor {
+                    name: "Environment".to_string(),
+                    levels: observations.iter().map(|o| o.1.to_string()).collect(),
+                },
+                GlmFactor {
+                    name: "Replication".to_string(),
+                    levels: observations.iter().map(|o| format!("{}|{}", o.1, o.2)).collect(),
+                },
+                GlmFactor {
+                    name: "Treatment".to_string(),
+                    levels: observations.iter().map(|o| o.0.to_string()).collect(),
+                },
+            ],
+            covariates: Vec::new(),
+            terms: vec![
+                vec!["Environment".to_string()],
+                vec!["Replication".to_string()],
+                vec!["Treatment".to_string()],
+                vec!["Environment".to_string(), "Treatment".to_string()],
+            ],
+        };
+        let glm = Self::glm(&model);
+        let empty = AnovaSource {
+            ss: 0.0,
+            df: 0,
+            ms: 0.0,
+            f: None,
+            p: None,
+        };
+        // Environment and replications are sequential (replications are nested),
+        // treatment and interaction adjusted
+        let term = |i: usize, sequential: bool| {
+            glm.terms
+                .get(i)
+                .map(|t| if sequential { t.type_i.clone() } else { t.type_ii.clone() })
+                .unwrap_or_else(|| empty.clone())
+        };
+        let replication = term(1, true);
+        let mut environment = term(0, true);
+        environment.f = (replication.ms > 0.0 && environment.df > 0).then(|| environment.ms / replication.ms);
+        environment.p = environment
+            .f
+            .map(|f| Self::f_p_value(f, environment.df as f64, replication.df as f64));
+        let treatment = term(2, false);
+        let interaction = term(3, false);
+
+        // Cell means and main-effect means on the balanced treatment × environment table
+        let mut cell_sums = vec![vec![(0.0, 0usize); e]; g];
+        for &(gi, ej, _, y) in &observations {
+            cell_sums[gi][ej].0 += y;
+            cell_sums[gi][ej].1 += 1;
+        }
+        let cell_means: Vec<Vec<f64>> = cell_sums
+            .iter()
+            .map(|row| row.iter().map(|(s, n)| if *n > 0 { s / *n as f64 } else { 0.0 }).collect())
+            .collect();
+        let treatment_means: Vec<f64> = cell_means.iter().map(|row| row.iter().sum::<f64>() / e.max(1) as f64).collect();
+        let environment_means: Vec<f64> = (0..e)
+            .map(|j| cell_means.iter().map(|row| row[j]).sum::<f64>() / g.max(1) as f64)
+            .collect();
+        let grand_mean = if g > 0 { treatment_means.iter().sum::<f64>() / g as f64 } else { 0.0 };
+        let environment_index: Vec<f64> = environment_means.iter().map(|m| m - grand_mean).collect();
+
+        // Replicates per cell (harmonic mean) scale cell-mean variances to plot level
+        let cells: Vec<usize> = cell_sums.iter().flatten().map(|c| c.1).filter(|n| *n > 0).collect();
+        let reps = if cells.is_empty() {
+            1.0
+        } else {
+            cells.len() as f64 / cells.iter().map(|n| 1.0 / *n as f64).sum::<f64>()
+        };
+        let (ms_error, df_error) = (glm.error.ms, glm.error.df as f64);
+
+        let regression = if e >= 3 {
+            let sum_i2: f64 = environment_index.iter().map(|i| i * i).sum();
+            let t_dist = StudentsT::new(0.0, 1.0, (e - 2) as f64).ok();
+            treatments
+                .iter()
+                .enumerate()
+                .map(|(gi, name)| {
+                    let row = &cell_means[gi];
+                    let slope = if sum_i2 > 0.0 {
+                        row.iter().zip(&environment_index).map(|(y, i)| y * i).sum::<f64>() / sum_i2
+                    } else {
+                        0.0
+                    };
+                    let ss_row: f64 = row.iter().map(|y| (y - treatment_means[gi]).powi(2)).sum();
+                    let ss_deviation: f64 = row
+                        .iter()
+                        .zip(&environment_index)
+                        .map(|(y, i)| (y - treatment_means[gi] - slope * i).powi(2))
+                        .sum();
+                    let ms_deviation = ss_deviation / (e - 2) as f64;
+                    let slope_std_error = if sum_i2 > 0.0 { (ms_deviation / sum_i2).sqrt() } else { 0.0 };
+                    let slope_p_value = match &t_dist {
+                        Some(t) if slope_std_error > 0.0 => {
+                            2.0 * (1.0 - t.cdf(((slope - 1.0) / slope_std_error).abs()))
+                        }
+                        _ => 1.0,
+                    };
+                    let pooled = ms_error / reps;
+                    EberhartRussellStability {
+                        treatment: name.clone(),
+                        mean: treatment_means[gi],
+                        slope,
+                        slope_std_error,
+                        slope_p_value,
+                        deviation_variance: ms_deviation - pooled,
+                        deviation_p_value: if pooled > 0.0 {
+                            Self::f_p_value(ms_deviation / pooled, (e - 2) as f64, df_error)
+                        } else {
+                            1.0
+                        },
+                        r_squared: if ss_row > 0.0 { 1.0 - ss_deviation / ss_row } else { 0.0 },
+                    }
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let ammi = Self::ammi(&cell_means, &treatment_means, &environment_means, grand_mean, reps, ms_error, df_error);
+
+        // Rankings: 1 is best
+        let rank = |values: &[f64], descending: bool| -> Vec<usize> {
+            let mut order: Vec<usize> = (0..values.len()).collect();
+            order.sort_by(|&a, &b| {
+                let cmp = values[a].partial_cmp(&values[b]).unwrap_or(std::cmp::Ordering::Equal);
+                if descending { cmp.reverse() } else { cmp }
+            });
+            let mut ranks = vec![0; values.len()];
+            for (position, &i) in order.iter().enumerate() {
+                ranks[i] = position + 1;
+            }
+            ranks
+        };
+        let asv: Vec<f64> = (0..g).map(|gi| Self::ammi_stability_value(&ammi, gi)).collect();
+        let mean_ranks = rank(&treatment_means, !lower_is_better);
+        let stability_ranks = rank(&asv, false);
+        let sums: Vec<f64> = (0..g).map(|gi| (mean_ranks[gi] + stability_ranks[gi]) as f64).collect();
+        let combined_ranks = rank(&sums, false);
+        let mut ranking: Vec<TreatmentRanking> = (0..g)
+            .map(|gi| TreatmentRanking {
+                treatment: treatments[gi].clone(),
+                mean: treatment_means[gi],
+                mean_rank: mean_ranks[gi],
+                asv: asv[gi],
+                stability_rank: stability_ranks[gi],
+                combined_rank: combined_ranks[gi],
+            })
+            .collect();
+        ranking.sort_by_key(|r| r.combined_rank);
+
+        MultiEnvironmentResult {
+            environments,
+            treatments,
+            excluded_treatments,
+            environment,
+            replication,
+            treatment,
+            interaction,
+            error: glm.error,
+            total: glm.total,
+            cv_percent: glm.cv_percent,
+            treatment_means,
+            environment_means,
+            environment_index,
+            cell_means,
+            regression,
+            ammi,
+            ranking,
+        }
+    }
+
+    fn ammi(
+        cell_means: &[Vec<f64>],
+        treatment_means: &[f64],
+        environment_means: &[f64],
+        grand_mean: f64,
+        reps: f64,
+        ms_error: f64,
+        df_error: f64,
+    ) -> AmmiAnalysis {
+        let (g, e) = (treatment_means.len(), environment_means.len());
+        let axes_count = g.min(e).saturating_sub(1);
+        if axes_count == 0 {
+            return AmmiAnalysis {
+                axes: Vec::new(),
+                residual: AnovaSource {
+                    ss: 0.0,
+                    df: 0,
+                    ms: 0.0,
+                    f: None,
+                    p: None,
+                },
+                treatment_scores: vec![Vec::new(); g],
+                environment_scores: vec![Vec::new(); e],
+            };
+        }
+
+        let interaction = DMatrix::from_fn(g, e, |i, j| {
+            cell_means[i][j] - treatment_means[i] - environment_means[j] + grand_mean
+        });
+        let ss_interaction = reps * interaction.iter().map(|v| v * v).sum::<f64>();
+        let svd = interaction.svd(true, true);
+        let (u, v_t) = match (svd.u, svd.v_t) {
+            (Some(u), Some(v_t)) => (u, v_t),
+            _ => (DMatrix::zeros(g, g.min(e)), DMatrix::zeros(g.min(e), e)),
+        };
+        let mut order: Vec<usize> = (0..svd.singular_values.len()).collect();
+        order.sort_by(|&a, &b| {
+            svd.singular_values[b].partial_cmp(&svd.singular_values[a]).unwrap_or(std::cmp::Ordering::Equal)
+        });
+        order.truncate(axes_count);
+
+        let mut axes = Vec::new();
+        let mut treatment_scores = vec![Vec::new(); g];
+        let mut environment_scores = vec![Vec::new(); e];
+        let mut ss_axes = 0.0;
+        let mut df_axes = 0.0;
+        for (k, &axis) in order.iter().enumerate() {
+            let lambda = svd.singular_values[axis];
+            let ss = reps * lambda * lambda;
+            // Gollob: g + e - 1 - 2k for axis k = 1, 2, ...
+            let df = (g + e) as f64 - 3.0 - 2.0 * k as f64;
+            if df <= 0.0 {
+                break;
+            }
+            let ms = ss / df;
+            let f = (ms_error > 0.0).then(|| ms / ms_error);
+            axes.push(AmmiAxis {
+                source: AnovaSource {
+                    ss,
+                    df: df as i32,
+                    ms,
+                    f,
+                    p: f.map(|f| Self::f_p_value(f, df, df_error)),
+                },
+                interaction_percent: if ss_interaction > 0.0 { ss / ss_interaction * 100.0 } else { 0.0 },
+            });
+            // Largest treatment score positive so signs are reproducible
+            let largest = (0..g).map(|i| u[(i, axis)]).fold(0.0, |m: f64, v| if v.abs() > m.abs() { v } else { m });
+            let sign = if largest < 0.0 { -1.0 } else { 1.0 };
+            for (i, scores) in treatment_scores.iter_mut().enumerate() {
+                scores.push(sign * u[(i, axis)] * lambda.sqrt());
+            }
+            for (j, scores) in environment_scores.iter_mut().enumerate() {
+                scores.push(sign * v_t[(axis, j)] * lambda.sqrt());
+            }
+            ss_axes += ss;
+            df_axes += df;
+        }
+
+        let df_residual = ((g - 1) * (e - 1)) as f64 - df_axes;
+        let ss_residual = (ss_interaction - ss_axes).max(0.0);
+        let ms_residual = if df_residual > 0.0 { ss_residual / df_residual } else { 0.0 };
+        let f_residual = (df_residual > 0.0 && ms_error > 0.0).then(|| ms_residual / ms_error);
+
+        AmmiAnalysis {
+            axes,
+            residual: AnovaSource {
+                ss: ss_residual,
+                df: df_residual.max(0.0) as i32,
+                ms: ms_residual,
+                f: f_residual,
+                p: f_residual.map(|f| Self::f_p_value(f, df_residual, df_error)),
+            },
+            treatment_scores,
+            environment_scores,
+        }
+    }
+
+    /// AMMI stability value (Purchase et al., 2000): distance from the origin
+    /// in the IPCA1-IPCA2 plane, IPCA1 weighted by SS(IPCA1) / SS(IPCA2).
+    fn ammi_stability_value(ammi: &AmmiAnalysis, treatment: usize) -> f64 {
+        let scores = &ammi.treatment_scores[treatment];
+        match (scores.first(), scores.get(1)) {
+            (Some(first), Some(second)) => {
+                let (ss1, ss2) = (ammi.axes[0].source.ss, ammi.axes[1].source.ss);
+                let weight = if ss2 > 0.0 { ss1 / ss2 } else { 1.0 };
+                ((weight * first).powi(2) + second.powi(2)).sqrt()
+            }
+            (Some(first), None) => first.abs(),
+            _ => 0.0,
+        }
+    }
+
+    /// Plot means of one parameter (matched by code) across projects, one
+    /// environment per project. Each project uses its session at
+    /// `days_after_treatment`, or its latest session with data when not given.
+    /// Treatments are matched across projects by formula code. Replications
+    /// give the pooled error its df, so projects whose plots have no
+    /// replication number are rejected.
+    pub async fn fetch_multi_environment_data(
+        conn: &mut PgConnection,
+        project_ids: &[Uuid],
+        parameter_code: &str,
+        days_after_treatment: Option<i32>,
+    ) -> Result<MultiEnvironmentData, AppError> {
+        #[derive(sqlx::FromRow)]
+        struct PlotMeanRow {
+            project_code: String,
+            location_name: Option<String>,
+            start_date: Option<chrono::NaiveDate>,
+            treatment: String,
+            replication: Option<i32>,
+            value: f64,
+        }
+
+        let rows: Vec<PlotMeanRow> = sqlx::query_as(
+            r#"
+            WITH chosen AS (
+                SELECT DISTINCT ON (ms.project_id) ms.project_id, ms.id
+                FROM monitoring_sessions ms
+                JOIN monitoring_data md ON md.session_id = ms.id
+                JOIN monitoring_parameters mp ON md.parameter_id = mp.id
+                JOIN experimental_units eu ON md.unit_id = eu.id
+                WHERE ms.project_id = ANY($1)
+                AND mp.code = $2
+                AND md.numeric_value IS NOT NULL
+                AND COALESCE(eu.is_active, true)
+                AND eu.excluded_reason IS NULL
+                AND NOT COALESCE(md.is_outlier, false)
+                AND ($3::int IS NULL OR ms.days_after_treatment = $3)
+                ORDER BY ms.project_id, ms.days_after_treatment DESC NULLS LAST, ms.scheduled_date DESC
+            )
+            SELECT
+                p.code as project_code,
+                p.location_name,
+                p.start_date,
+                COALESCE(f.code, eb.treatment_description, eb.block_code) as treatment,
+                eb.replication,
+                AVG(md.numeric_value)::float8 as value
+            FROM chosen c
+            JOIN projects p ON p.id = c.project_id
+            JOIN monitoring_data md ON md.session_id = c.id
+            JOIN monitoring_parameters mp ON md.parameter_id = mp.id AND mp.code = $2
+            JOIN experimental_units eu ON md.unit_id = eu.id
+            JOIN experimental_blocks eb ON eu.block_id = eb.id
+            LEFT JOIN formulas f ON eb.formula_id = f.id
+            WHERE md.numeric_value IS NOT NULL
+            AND COALESCE(eu.is_active, true)
+            AND eu.excluded_reason IS NULL
+            AND NOT COALESCE(md.is_outlier, false)
+            GROUP BY p.code, p.location_name, p.start_date, eb.id, f.code
+            ORDER BY p.code, eb.block_code
+            "#
+        )
+        .bind(project_ids)
+        .bind(parameter_code)
+        .bind(days_after_treatment)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        let mut unreplicated: Vec<&str> =
+            rows.iter().filter(|r| r.replication.is_none()).map(|r| r.project_code.as_str()).collect();
+        unreplicated.dedup();
+        if !unreplicated.is_empty() {
+            return Err(AppError::Validation(format!(
+                "Projects {} have plots without a replication number; the combined analysis needs \
+                 replications to pool the error. Record them or leave these projects out",
+                unreplicated.join(", ")
+            )));
+        }
+
+        let environment = |row: &PlotMeanRow| {
+            let mut label = row.location_name.clone().unwrap_or_else(|| row.project_code.clone());
+            if let Some(start) = row.start_date {
+                label.push_str(&format!(" {}", start.format("%Y-%m")));
+            }
+            format!("{} ({})", label, row.project_code)
+        };
+
+        Ok(MultiEnvironmentData {
+            response: rows.iter().map(|r| r.value).collect(),
+            environments: rows.iter().map(environment).collect(),
+            treatments: rows.iter().map(|r| r.treatment.clone()).collect(),
+            replications: rows.iter().filter_map(|r| r.replication).map(|r| r.to_string()).collect(),
+        })
+    }
+}
+
+// ==============================================================================
+// RESAMPLING (BOOTSTRAP & PERMUTATION)
+// ==============================================================================
+
+/// Resamples are drawn in chunks of this size, each from its own generator
+/// seeded from the request seed, so results do not depend on the number of
+/// worker threads.
+const RESAMPLE_CHUNK: usize = 500;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BootstrapStatistic {
+    #[default]
+    Mean,       // mean of the sample
+    Difference, // mean(sample) - mean(reference)
+    Ratio,      // mean(sample) / mean(reference)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootstrapResult {
+    pub statistic: BootstrapStatistic,
+    pub seed: u64,
+    pub resamples: usize,
+    pub confidence: f64,
+    pub estimate: f64,
+    pub std_error: f64, // SD of the bootstrap distribution
+    pub bias: f64,      // bootstrap mean - estimate
+    pub percentile: ConfidenceInterval,
+    pub bca: Option<ConfidenceInterval>, // None when the bias correction is undefined
+    pub bias_correction: Option<f64>,    // z0
+    pub acceleration: f64,               // jackknife estimate
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermutationTestResult {
+    pub seed: u64,
+    pub permutations: usize,
+    pub blocked: bool, // labels shuffled within blocks
+    pub f_statistic: f64,
+    pub p_value: f64, // (exceedances + 1) / (permutations + 1)
+    pub parametric_p_value: f64,
+}
+
+impl StatisticalAnalysis {
+    /// Seeded bootstrap CI for a mean, a difference of means or a ratio of
+    /// means. Two-sample statistics resample each sample separately. BCa uses
+    /// the jackknife over every observation for the acceleration.
+    pub fn bootstrap(
+        statistic: BootstrapStatistic,
+        sample: &[f64],
+        reference: &[f64],
+        resamples: usize,
+        confidence: f64,
+        seed: u64,
+    ) -> BootstrapResult {
+        let mean = |v: &[f64]| if v.is_empty() { f64::NAN } else { v.iter().sum::<f64>() / v.len() as f64 };
+        let compute = |a: &[f64], b: &[f64]| match statistic {
+            BootstrapStatistic::Mean => mean(a),
+            BootstrapStatistic::Difference => mean(a) - mean(b),
+            BootstrapStatistic::Ratio => mean(a) / mean(b),
+        };
+        let two_sample = statistic != BootstrapStatistic::Mean;
+        let estimate = compute(sample, reference);
+
+        let mut replicates: Vec<f64> = Self::resample_chunks(resamples, seed, |rng, count| {
+            let mut a = vec![0.0; sample.len()];
+            let mut b = vec![0.0; if two_sample { reference.len() } else { 0 }];
+            (0..count)
+                .map(|_| {
+                    a.iter_mut().for_each(|x| *x = sample[rng.gen_range(0..sample.len())]);
+                    b.iter_mut().for_each(|x| *x = reference[rng.gen_range(0..reference.len())]);
+                    compute(&a, &b)
+                })
+                .collect()
+        });
+        replicates.retain(|v| v.is_finite());
+        replicates.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+        let b = replicates.len();
+        let boot_mean = if b > 0 { replicates.iter().sum::<f64>() / b as f64 } else { f64::NAN };
+        let std_error = if b > 1 {
+            (replicates.iter().map(|v| (v - boot_mean).powi(2)).sum::<f64>() / (b - 1) as f64).sqrt()
+        } else {
+            0.0
+        };
+
+        let alpha = (1.0 - confidence) / 2.0;
+        let quantile = |q: f64| {
+            if b == 0 {
+                return f64::NAN;
+            }
+            let pos = q.clamp(0.0, 1.0) * (b - 1) as f64;
+            let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+            replicates[lo] + (replicates[hi] - replicates[lo]) * (pos - lo as f64)
+        };
+        let percentile = ConfidenceInterval {
+            lower: quantile(alpha),
+            upper: quantile(1.0 - alpha),
+        };
+
+        // Jackknife: leave out each observation of either sample in turn
+        let mut jackknife = Vec::new();
+        for i in 0..sample.len() {
+            let rest: Vec<f64> = sample.iter().enumerate().filter(|(j, _)| *j != i).map(|(_, v)| *v).collect();
+            jackknife.push(compute(&rest, reference));
+        }
+        if two_sample {
+            for i in 0..reference.len() {
+                let rest: Vec<f64> = reference.iter().enumerate().filter(|(j, _)| *j != i).map(|(_, v)| *v).collect();
+                jackknife.push(compute(sample, &rest));
+            }
+        }
+        jackknife.retain(|v| v.is_finite());
+        let jack_mean = jackknife.iter().sum::<f64>() / jackknife.len().max(1) as f64;
+        let (num, den) = jackknife.iter().fold((0.0, 0.0), |(n, d), v| {
+            let diff = jack_mean - v;
+            (n + diff.powi(3), d + diff.powi(2))
+        });
+        let acceleration = if den > 0.0 { num / (6.0 * den.powf(1.5)) } else { 0.0 };
+
+        let normal = Normal::new(0.0, 1.0).unwrap();
+        let below = replicates.iter().filter(|v| **v < estimate).count() as f64
+            + 0.5 * replicates.iter().filter(|v| **v == estimate).count() as f64;
+        let bias_correction = (b > 0 && below > 0.0 && below < b as f64).then(|| normal.inverse_cdf(below / b as f64));
+        let bca = bias_correction.map(|z0| {
+            let adjusted = |q: f64| {
+                let z = z0 + normal.inverse_cdf(q);
+                normal.cdf(z0 + z / (1.0 - acceleration * z))
+            };
+            ConfidenceInterval {
+                lower: quantile(adjusted(alpha)),
+                upper: quantile(adjusted(1.0 - alpha)),
+            }
+        });
+
+        BootstrapResult {
+            statistic,
+            seed,
+            resamples: b,
+            confidence,
+            estimate,
+            std_error,
+            bias: boot_mean - estimate,
+            percentile,
+            bca,
+            bias_correction,
+            acceleration,
+        }
+    }
+
+    /// Permutation test of the treatment effect. Treatment labels are
+    /// shuffled over all plots, or within blocks when `blocks` is given, and
+    /// each permutation is scored with the same F as the observed layout:
+    /// the treatment effect adjusted for blocks (Type II), which stays exact
+    /// when plots are missing. Only observed treatments count towards its df.
+    pub fn permutation_test(
+        values: &[f64],
+        treatments: &[usize],
+        blocks: Option<&[usize]>,
+        permutations: usize,
+        seed: u64,
+    ) -> PermutationTestResult {
+        // Without blocks every plot sits in one stratum, which reduces the
+        // adjusted F to the one-way F
+        let one_stratum = vec![0; values.len()];
+        let scorer = AdjustedTreatmentF::new(values, treatments, blocks.unwrap_or(&one_stratum));
+
+        // Positions sharing a block are shuffled among themselves
+        let mut strata: Vec<Vec<usize>> = Vec::new();
+        for (i, &block) in blocks.unwrap_or(&one_stratum).iter().enumerate() {
+            if strata.len() <= block {
+                strata.resize(block + 1, Vec::new());
+            }
+            strata[block].push(i);
+        }
+
+        let f_statistic = scorer.f(treatments);
+        let tolerance = f_statistic.abs() * 1e-12;
+        let exceedances: usize = Self::resample_chunks(permutations, seed, |rng, count| {
+            let mut labels = treatments.to_vec();
+            let mut shuffled: Vec<usize> = Vec::new();
+            (0..count)
+                .map(|_| {
+                    for stratum in &strata {
+                        shuffled.clear();
+                        shuffled.extend(stratum.iter().map(|&i| treatments[i]));
+                        shuffled.shuffle(rng);
+                        for (&i, &l) in stratum.iter().zip(&shuffled) {
+                            labels[i] = l;
+                        }
+                    }
+                    if scorer.f(&labels) >= f_statistic - tolerance { 1.0 } else { 0.0 }
+                })
+                .collect()
+        })
+        .iter()
+        .filter(|v| **v > 0.0)
+        .count();
+
+        PermutationTestResult {
+            seed,
+            permutations,
+            blocked: blocks.is_some(),
+            f_statistic,
+            p_value: (exceedances + 1) as f64 / (permutations + 1) as f64,
+            parametric_p_value: Self::f_p_value(f_statistic, scorer.df_treatment, scorer.df_error),
+        }
+    }
+
+    /// Runs `total` resamples in parallel chunks; chunk `c` draws from a
+    /// generator seeded with the `c`-th value of a generator seeded by `seed`,
+    /// and results come back in chunk order. ChaCha8's stream is fixed across
+    /// rand releases, so a stored seed keeps reproducing its run.
+    fn resample_chunks<F>(total: usize, seed: u64, draw: F) -> Vec<f64>
+    where
+        F: Fn(&mut ChaCha8Rng, usize) -> Vec<f64> + Sync,
+    {
+        let mut master = ChaCha8Rng::seed_from_u64(seed);
+        let chunks: Vec<(u64, usize)> = (0..total.div_ceil(RESAMPLE_CHUNK))
+            .map(|c| (master.gen::<u64>(), RESAMPLE_CHUNK.min(total - c * RESAMPLE_CHUNK)))
+            .collect();
+        chunks
+            .into_par_iter()
+            .map(|(chunk_seed, count)| draw(&mut ChaCha8Rng::seed_from_u64(chunk_seed), count))
+            .collect::<Vec<Vec<f64>>>()
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+/// F for treatments adjusted for blocks in the additive model, from the
+/// intra-block equations: SS(T | B) = Q' C⁻ Q with adjusted treatment totals
+/// Q = T - N' K⁻¹ B and C = R - N' K⁻¹ N. Shuffling labels within blocks
+/// keeps the block-treatment counts N, so C, its df and the block-only
+/// residual SS are fixed and each permutation only recomputes Q.
+struct AdjustedTreatmentF {
+    values: Vec<f64>,
+    treatment_count: usize,
+    block_adjustment: Vec<f64>, // (N' K⁻¹ B) per treatment
+    c_inverse: DMatrix<f64>,    // Moore-Penrose inverse of C
+    ss_within_blocks: f64,      // residual SS of the block-only model
+    df_treatment: f64,
+    df_error: f64,
+}
+
+impl AdjustedTreatmentF {
+    fn new(values: &[f64], treatments: &[usize], blocks: &[usize]) -> Self {
+        let t = treatments.iter().max().map_or(0, |m| m + 1);
+        let b = blocks.iter().max().map_or(0, |m| m + 1);
+
+        let mut counts = DMatrix::<f64>::zeros(b, t);
+        let mut block_totals = vec![0.0; b];
+        let mut block_sizes = vec![0.0; b];
+        for ((&v, &tr), &bl) in values.iter().zip(treatments).zip(blocks) {
+            counts[(bl, tr)] += 1.0;
+            block_totals[bl] += v;
+            block_sizes[bl] += 1.0;
+        }
+
+        let mut c = DMatrix::<f64>::zeros(t, t);
+        let mut block_adjustment = vec![0.0; t];
+        for bl in (0..b).filter(|&bl| block_sizes[bl] > 0.0) {
+            let row = counts.row(bl);
+            for i in 0..t {
+                block_adjustment[i] += row[i] * block_totals[bl] / block_sizes[bl];
+                c[(i, i)] += row[i];
+                for j in 0..t {
+                    c[(i, j)] -= row[i] * row[j] / block_sizes[bl];
+                }
+            }
+        }
+
+        let svd = c.svd(true, true);
+        let max_sv = svd.singular_values.iter().cloned().fold(0.0, f64::max);
+        let eps = max_sv * t.max(1) as f64 * f64::EPSILON * 16.0;
+        let df_treatment = svd.rank(eps) as f64;
+        let c_inverse = svd.pseudo_inverse(eps).unwrap_or_else(|_| DMatrix::zeros(t, t));
+
+        let ss_within_blocks: f64 = values
+            .iter()
+            .zip(blocks)
+            .map(|(v, &bl)| (v - block_totals[bl] / block_sizes[bl]).powi(2))
+            .sum();
+        let observed_blocks = block_sizes.iter().filter(|&&k| k > 0.0).count() as f64;
+
+        Self {
+            values: values.to_vec(),
+            treatment_count: t,
+            block_adjustment,
+            c_inverse,
+            ss_within_blocks,
+            df_treatment,
+            df_error: values.len() as f64 - observed_blocks - df_treatment,
+        }
+    }
+
+    /// Adjusted treatment F for one labelling of the plots; 0 when undefined
+    fn f(&self, labels: &[usize]) -> f64 {
+        let mut q = DVector::<f64>::zeros(self.treatment_count);
+        for (&v, &l) in self.values.iter().zip(labels) {
+            q[l] += v;
+        }
+        for (qi, adj) in q.iter_mut().zip(&self.block_adjustment) {
+            *qi -= adj;
+        }
+        let ss_treatment = q.dot(&(&self.c_inverse * &q)).max(0.0);
+        let ss_error = (self.ss_within_blocks - ss_treatment).max(0.0);
+        if self.df_treatment > 0.0 && self.df_error > 0.0 && ss_error > 0.0 {
+            (ss_treatment / self.df_treatment) / (ss_error / self.df_error)
+        } else {
+            0.0
+        }
+    }
+}
+
+// ==============================================================================
+// DISEASE & PEST SEVERITY
+// ==============================================================================
+
+/// How the scores of a pest or disease parameter map to severity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeverityScale {
+    #[serde(default)]
+    pub min_class: f64, // lowest score of the scale, 0% severity
+    pub max_class: f64,
+    #[serde(default)]
+    pub healthy_class: f64, // scores at or below this count as not infected
+    /// % severity of each class, indexed by score; empty scales the score
+    /// linearly between the min and max class
+    #[serde(default)]
+    pub class_midpoints: Vec<f64>,
+}
+
+impl SeverityScale {
+    /// The parameter's configured scale, or one from its min/max values
+    /// (0-5 when those are not set either).
+    pub fn for_parameter(parameter: &MonitoringParameter) -> Self {
+        let decimal = |v: Option<rust_decimal::Decimal>| v.and_then(|v| v.to_string().parse::<f64>().ok());
+        parameter
+            .severity_scale
+            .clone()
+            .and_then(|v| serde_json::from_value::<SeverityScale>(v).ok())
+            .filter(|s| s.max_class > s.min_class)
+            .unwrap_or_else(|| {
+                let min_class = decimal(parameter.min_value).unwrap_or(0.0);
+                SeverityScale {
+                    min_class,
+                    max_class: decimal(parameter.max_value)
+                        .filter(|v| *v > min_class)
+                        .unwrap_or(min_class + 5.0),
+                    healthy_class: min_class,
+                    class_midpoints: Vec::new(),
+                }
+            })
+    }
+
+    /// Severity (%) of one unit's score.
+    pub fn severity_percent(&self, score: f64) -> f64 {
+        if self.class_midpoints.is_empty() {
+            ((score - self.min_class) / (self.max_class - self.min_class) * 100.0).clamp(0.0, 100.0)
+        } else {
+            let class = score.round().clamp(0.0, (self.class_midpoints.len() - 1) as f64) as usize;
+            self.class_midpoints[class]
+        }
+    }
+}
+
+/// Unit scores per plot and session for one treatment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeverityTreatmentData {
+    pub treatment: String,
+    pub plots: Vec<SeverityPlotData>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeverityPlotData {
+    pub plot: String,
+    pub replication: Option<i32>,
+    pub sessions: Vec<SeveritySession>,
+}
+
+/// Unit scores of one plot in one session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeveritySession {
+    pub days_after_treatment: f64,
+    pub scores: Vec<f64>,
+}
+
+impl SeverityPlotData {
+    /// Adds a unit score to the session at `days_after_treatment`.
+    pub fn push_score(&mut self, days_after_treatment: f64, score: f64) {
+        match self.sessions.iter_mut().find(|s| s.days_after_treatment == days_after_treatment) {
+            Some(session) => session.scores.push(score),
+            None => self.sessions.push(SeveritySession {
+                days_after_treatment,
+                scores: vec![score],
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeverityPoint {
+    pub days_after_treatment: f64,
+    pub units: usize,
+    pub severity_index: f64, // mean % severity (McKinney index without class midpoints)
+    pub incidence: f64,      // % of units above the healthy class
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlotSeverity {
+    pub plot: String,
+    pub replication: Option<i32>,
+    pub points: Vec<SeverityPoint>,
+    pub audpc: f64, // area under the severity index curve, %-days
+    pub audps: f64, // area under the disease progress stairs
+    pub incidence_audpc: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreatmentSeverity {
+    pub treatment: String,
+    pub points: Vec<SeverityPoint>, // mean of the plots at each DAT
+    pub plots: Vec<PlotSeverity>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeverityIndexComparison {
+    pub index: String,
+    pub anova: SeverityAnova, // on plot values
+    pub mean_separation: Option<MeanSeparationResult>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "design", rename_all = "snake_case")]
+pub enum SeverityAnova {
+    Crd { result: AnovaResult },
+    Rcbd { result: RcbdAnovaResult },
+}
+
+/// A pest or disease parameter's scale and plot scores, with the project
+/// design that decides how the plots are compared.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeverityDataset {
+    pub design: Option<ExperimentDesign>,
+    pub scale: SeverityScale,
+    pub treatments: Vec<SeverityTreatmentData>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeverityAnalysis {
+    pub scale: SeverityScale,
+    pub treatments: Vec<TreatmentSeverity>,
+    pub comparisons: Vec<SeverityIndexComparison>,
+}
+
+impl StatisticalAnalysis {
+    /// Severity index and incidence of one plot in one session.
+    pub fn severity_point(scale: &SeverityScale, days_after_treatment: f64, scores: &[f64]) -> SeverityPoint {
+        let n = scores.len();
+        let (severity, infected) = scores.iter().fold((0.0, 0usize), |(s, i), &score| {
+            (s + scale.severity_percent(score), i + usize::from(score > scale.healthy_class))
+        });
+        SeverityPoint {
+            days_after_treatment,
+            units: n,
+            severity_index: if n > 0 { severity / n as f64 } else { 0.0 },
+            incidence: if n > 0 { infected as f64 / n as f64 * 100.0 } else { 0.0 },
+        }
+    }
+
+    /// Area under the disease progress curve by the trapezoid rule over
+    /// (DAT, value) points sorted by DAT.
+    pub fn audpc(points: &[(f64, f64)]) -> f64 {
+        points
+            .windows(2)
+            .map(|w| (w[0].1 + w[1].1) / 2.0 * (w[1].0 - w[0].0))
+            .sum()
+    }
+
+    /// Area under the disease progress stairs (Simko & Piepho, 2012), which
+    /// gives the first and last assessments their full weight.
+    pub fn audps(points: &[(f64, f64)]) -> f64 {
+        let n = points.len();
+        if n < 2 {
+            return 0.0;
+        }
+        let span = points[n - 1].0 - points[0].0;
+        Self::audpc(points) + (points[0].1 + points[n - 1].1) / 2.0 * span / (n - 1) as f64
+    }
+
+    /// Severity index, incidence, AUDPC and AUDPS per plot, then a treatment
+    /// ANOVA with mean separation on the final severity index, final
+    /// incidence, AUDPC and AUDPS, replicate plots serving as units.
+    /// `blocked` (RAK) fits replications as blocks; every plot then needs a
+    /// replication number and a treatment at most one plot per replication.
+    pub fn severity_analysis(
+        scale: &SeverityScale,
+        treatments: &[SeverityTreatmentData],
+        blocked: bool,
+        post_hoc: PostHocTest,
+    ) -> Result<SeverityAnalysis, AppError> {
+        let summarized: Vec<TreatmentSeverity> = treatments
+            .iter()
+            .map(|t| {
+                let plots: Vec<PlotSeverity> = t
+                    .plots
+                    .iter()
+                    .map(|plot| {
+                        let mut points: Vec<SeverityPoint> = plot
+                            .sessions
+                            .iter()
+                            .filter(|s| !s.scores.is_empty())
+                            .map(|s| Self::severity_point(scale, s.days_after_treatment, &s.scores))
+                            .collect();
+                        points.sort_by(|a, b| {
+                            a.days_after_treatment
+                                .partial_cmp(&b.days_after_treatment)
+                                .unwrap_or(std::cmp::Ordering::Equal)
+                        });
+                        let severity: Vec<(f64, f64)> =
+                            points.iter().map(|p| (p.days_after_treatment, p.severity_index)).collect();
+                        let incidence: Vec<(f64, f64)> =
+                            points.iter().map(|p| (p.days_after_treatment, p.incidence)).collect();
+                        PlotSeverity {
+                            plot: plot.plot.clone(),
+                            replication: plot.replication,
+                            audpc: Self::audpc(&severity),
+                            audps: Self::audps(&severity),
+                            incidence_audpc: Self::audpc(&incidence),
+                            points,
+                        }
+                    })
+                    .filter(|p| !p.points.is_empty())
+                    .collect();
+
+                let mut days: Vec<f64> = plots.iter().flat_map(|p| p.points.iter().map(|x| x.days_after_treatment)).collect();
+                days.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+                days.dedup();
+                let points = days
+                    .iter()
+                    .map(|&dat| {
+                        let at: Vec<&SeverityPoint> = plots
+                            .iter()
+                            .filter_map(|p| p.points.iter().find(|x| x.days_after_treatment == dat))
+                            .collect();
+                        let n = at.len().max(1) as f64;
+                        SeverityPoint {
+                            days_after_treatment: dat,
+                            units: at.iter().map(|x| x.units).sum(),
+                            severity_index: at.iter().map(|x| x.severity_index).sum::<f64>() / n,
+                            incidence: at.iter().map(|x| x.incidence).sum::<f64>() / n,
+                        }
+                    })
+                    .collect();
+
+                TreatmentSeverity {
+                    treatment: t.treatment.clone(),
+                    points,
+                    plots,
+                }
+            })
+            .filter(|t| !t.plots.is_empty())
+            .collect();
+
+        type PlotIndex = fn(&PlotSeverity) -> Option<f64>;
+        let indices: [(&str, PlotIndex); 4] = [
+            ("final_severity_index", |p| p.points.last().map(|x| x.severity_index)),
+            ("final_incidence", |p| p.points.last().map(|x| x.incidence)),
+            ("audpc", |p| (p.points.len() >= 2).then_some(p.audpc)),
+            ("audps", |p| (p.points.len() >= 2).then_some(p.audps)),
+        ];
+        let mut reps: Vec<i32> = summarized.iter().flat_map(|t| t.plots.iter().filter_map(|p| p.replication)).collect();
+        reps.sort();
+        reps.dedup();
+        if blocked {
+            if reps.len() < 2 || summarized.iter().any(|t| t.plots.iter().any(|p| p.replication.is_none())) {
+                return Err(AppError::Validation(
+                    "RCBD analysis needs a replication number on every plot and at least 2 replications".to_string(),
+                ));
+            }
+            for t in &summarized {
+                for (i, plot) in t.plots.iter().enumerate() {
+                    if t.plots[..i].iter().any(|p| p.replication == plot.replication) {
+                        return Err(AppError::Validation(format!(
+                            "Treatment {} has more than one plot in replication {}",
+                            t.treatment,
+                            plot.replication.unwrap_or_default()
+                        )));
+                    }
+                }
+            }
+        }
+
+        let mut comparisons = Vec::new();
+        for (name, value) in &indices {
+            let groups: Vec<Vec<f64>> = summarized
+                .iter()
+                .map(|t| t.plots.iter().filter_map(value).collect())
+                .collect();
+            if groups.len() < 2 || groups.iter().any(|g| g.len() < 2) {
+                continue;
+            }
+            let separate = |means: &[f64], sizes: &[usize], error: &AnovaSource| {
+                Self::mean_separation(post_hoc, means, sizes, error.ms, error.df as f64, 0.05)
+            };
+            let (anova, mean_separation) = if blocked {
+                // [treatment][replication], missing plots left to the RCBD fit
+                let cells: Vec<Vec<Option<f64>>> = summarized
+                    .iter()
+                    .map(|t| {
+                        reps.iter()
+                            .map(|r| t.plots.iter().find(|p| p.replication == Some(*r)).and_then(value))
+                            .collect()
+                    })
+                    .collect();
+                let result = Self::rcbd_anova(&cells);
+                let mean_separation = result
+                    .is_significant_05
+                    .then(|| separate(&result.treatment_means, &result.treatment_sizes, &result.error));
+                (SeverityAnova::Rcbd { result }, mean_separation)
+            } else {
+                let result = Self::one_way_anova(&groups);
+                let mean_separation = result
+                    .is_significant_05
+                    .then(|| separate(&result.group_means, &result.group_sizes, &result.source_within));
+                (SeverityAnova::Crd { result }, mean_separation)
+            };
+            comparisons.push(SeverityIndexComparison {
+                index: name.to_string(),
+                anova,
+                mean_separation,
+            });
+        }
+
+        Ok(SeverityAnalysis {
+            scale: scale.clone(),
+            treatments: summarized,
+            comparisons,
+        })
+    }
+
+    /// Loads the scale and unit scores of a pest or disease parameter,
+    /// grouped by treatment and plot. Sessions without a DAT, excluded or
+    /// inactive units and flagged outliers are skipped, as in the report.
+    pub async fn fetch_severity_scores(
+        conn: &mut PgConnection,
+        project_id: Uuid,
+        parameter_id: Uuid,
+    ) -> Result<SeverityDataset, AppError> {
+        let design: Option<ExperimentDesign> =
+            sqlx::query_scalar::<_, Option<ExperimentDesign>>("SELECT experiment_design FROM projects WHERE id = $1")
+                .bind(project_id)
+                .fetch_optional(&mut *conn)
+                .await
+                .map_err(|e| AppError::Database(e.to_string()))?
+                .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+
+        let parameter = sqlx::query_as::<_, MonitoringParameter>(
+            "SELECT * FROM monitoring_parameters WHERE id = $1 AND project_id = $2",
+        )
+        .bind(parameter_id)
+        .bind(project_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound("Parameter not found in this project".to_string()))?;
+
+        #[derive(sqlx::FromRow)]
+        struct ScoreRow {
+            treatment: String,
+            block_code: String,
+            replication: Option<i32>,
+            days_after_treatment: i32,
+            score: f64,
+        }
+
+        let rows: Vec<ScoreRow> = sqlx::query_as(
+            r#"
+            SELECT
+                COALESCE(f.code, eb.treatment_description, eb.block_code) as treatment,
+                eb.block_code,
+                eb.replication,
+                ms.days_after_treatment,
+                md.numeric_value::float8 as score
+            FROM monitoring_data md
+            JOIN experimental_units eu ON md.unit_id = eu.id
+            JOIN experimental_blocks eb ON eu.block_id = eb.id
+            JOIN monitoring_sessions ms ON md.session_id = ms.id
+            LEFT JOIN formulas f ON eb.formula_id = f.id
+            WHERE eb.project_id = $1
+            AND md.parameter_id = $2
+            AND md.numeric_value IS NOT NULL
+            AND ms.days_after_treatment IS NOT NULL
+            AND eu.excluded_reason IS NULL
+            AND COALESCE(eu.is_active, true)
+            AND NOT COALESCE(md.is_outlier, false)
+            ORDER BY eb.block_code, ms.days_after_treatment
+            "#
+        )
+        .bind(project_id)
+        .bind(parameter_id)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        let mut treatments: Vec<SeverityTreatmentData> = Vec::new();
+        for row in rows {
+            let t = match treatments.iter().position(|t| t.treatment == row.treatment) {
+                Some(t) => t,
+                None => {
+                    treatments.push(SeverityTreatmentData {
+                        treatment: row.treatment,
+                        plots: Vec::new(),
+                    });
+                    treatments.len() - 1
+                }
+            };
+            let plots = &mut treatments[t].plots;
+            let p = match plots.iter().position(|p| p.plot == row.block_code) {
+                Some(p) => p,
+                None => {
+                    plots.push(SeverityPlotData {
+                        plot: row.block_code,
+                        replication: row.replication,
+                        sessions: Vec::new(),
+                    });
+                    plots.len() - 1
+                }
+            };
+            plots[p].push_score(row.days_after_treatment as f64, row.score);
+        }
+
+        Ok(SeverityDataset {
+            design,
+            scale: SeverityScale::for_parameter(&parameter),
+            treatments,
+        })
+    }
+}
+
+// ==============================================================================
+// PROJECT DATASETS
+// ==============================================================================
+
+/// A monitoring_data row that went into an analysis.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatasetRow {
+    pub data_id: Uuid,
+    pub unit_id: Uuid,
+    pub unit_code: String,
+    pub block_id: Uuid,
+    pub block_code: String,
+    pub treatment: String,
+    pub replication: Option<i32>,
+    pub value: f64,
+}
+
+/// A monitoring_data row that was left out, and why.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExcludedRow {
+    pub data_id: Uuid,
+    pub unit_code: String,
+    pub block_code: String,
+    pub reason: String,
+}
+
+/// Plot (experimental block) mean of the used unit values.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlotValue {
+    pub block_id: Uuid,
+    pub block_code: String,
+    pub treatment: String,
+    pub replication: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub factor_levels: Option<serde_json::Value>,
+    pub position_row: Option<i32>,
+    pub position_column: Option<i32>,
+    pub units: usize,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectDataset {
+    pub project_id: Uuid,
+    pub parameter_id: Uuid,
+    pub session_id: Uuid,
+    pub project_design: Option<ExperimentDesign>,
+    pub parameter_data_type: String, // "rating" parameters get rank tests
+    pub used: Vec<DatasetRow>,
+    pub excluded: Vec<ExcludedRow>,
+    pub plots: Vec<PlotValue>,
+}
+
+impl ProjectDataset {
+    /// Treatments in first-seen plot order.
+    pub fn treatments(&self) -> Vec<String> {
+        let mut treatments: Vec<String> = Vec::new();
+        for plot in &self.plots {
+            if !treatments.contains(&plot.treatment) {
+                treatments.push(plot.treatment.clone());
+            }
+        }
+        treatments
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "design", rename_all = "snake_case")]
+pub enum ProjectAnova {
+    Crd {
+        anova: AnovaResult,
+        mean_separation: Option<MeanSeparationResult>,
+    },
+    Rcbd {
+        anova: RcbdAnovaResult,
+        mean_separation: Option<MeanSeparationResult>,
+    },
+    Factorial {
+        anova: GlmResult,
+    },
+    SplitPlot {
+        anova: SplitPlotAnovaResult,
+        main_plot_levels: Vec<String>,
+        sub_plot_levels: Vec<String>,
+    },
+    KruskalWallis {
+        result: KruskalWallisResult,
+    },
+    Friedman {
+        result: FriedmanResult,
+    },
+}
+
+impl StatisticalAnalysis {
+    /// Loads one parameter of one session for a project. Rows of excluded or
+    /// inactive units, flagged outliers (unless `include_outliers`) and rows
+    /// without a numeric value are listed in `excluded`; the rest are used and
+    /// averaged per plot.
+    pub async fn fetch_project_dataset(
+        conn: &mut PgConnection,
+        project_id: Uuid,
+        parameter_id: Uuid,
+        session_id: Uuid,
+        include_outliers: bool,
+    ) -> Result<ProjectDataset, AppError> {
+        let project_design: Option<Option<ExperimentDesign>> =
+            sqlx::query_scalar("SELECT experiment_design FROM projects WHERE id = $1")
+                .bind(project_id)
+                .fetch_optional(&mut *conn)
+                .await
+                .map_err(|e| AppError::Database(e.to_string()))?;
+        let project_design = project_design.ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+
+        let parameter_data_type: String =
+            sqlx::query_scalar("SELECT data_type FROM monitoring_parameters WHERE id = $1 AND project_id = $2")
+                .bind(parameter_id)
+                .bind(project_id)
+                .fetch_optional(&mut *conn)
+                .await
+                .map_err(|e| AppError::Database(e.to_string()))?
+                .ok_or_else(|| AppError::NotFound("Parameter not found".to_string()))?;
+
+        #[derive(sqlx::FromRow)]
+        struct Row {
+            data_id: Uuid,
+            unit_id: Uuid,
+            unit_code: String,
+            block_id: Uuid,
+            block_code: String,
+            treatment: String,
+            replication: Option<i32>,
+            factor_levels: Option<serde_json::Value>,
+            position_row: Option<i32>,
+            position_column: Option<i32>,
+            value: Option<f64>,
+            is_outlier: Option<bool>,
+            outlier_reason: Option<String>,
+            unit_active: Option<bool>,
+            excluded_reason: Option<String>,
+        }
+
+        let rows = sqlx::query_as::<_, Row>(
+            r#"
+            SELECT
+                md.id AS data_id,
+                eu.id AS unit_id,
+                eu.unit_code,
+                eb.id AS block_id,
+                eb.block_code,
+                COALESCE(f.code, eb.treatment_description, eb.block_code) AS treatment,
+                eb.replication,
+                eb.factor_levels,
+                eb.position_row,
+                eb.position_column,
+                md.numeric_value::float8 AS value,
+                md.is_outlier,
+                md.outlier_reason,
+                eu.is_active AS unit_active,
+                eu.excluded_reason
+            FROM monitoring_data md
+            JOIN experimental_units eu ON md.unit_id = eu.id
+            JOIN experimental_blocks eb ON eu.block_id = eb.id
+            LEFT JOIN formulas f ON eb.formula_id = f.id
+            WHERE eb.project_id = $1
+            AND md.parameter_id = $2
+            AND md.session_id = $3
+            ORDER BY eb.block_code, eu.unit_code
+            "#
+        )
+        .bind(project_id)
+        .bind(parameter_id)
+        .bind(session_id)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        let mut used = Vec::new();
+        let mut excluded = Vec::new();
+        let mut plots: Vec<PlotValue> = Vec::new();
+        for row in rows {
+            let reason = if let Some(reason) = &row.excluded_reason {
+                Some(format!("Unit excluded: {}", reason))
+            } else if row.unit_active == Some(false) {
+                Some("Unit inactive".to_string())
+            } else if row.is_outlier == Some(true) && !include_outliers {
+                Some(format!(
+                    "Flagged outlier: {}",
+                    row.outlier_reason.as_deref().unwrap_or("no reason recorded")
+                ))
+            } else if row.value.is_none() {
+                Some("No numeric value".to_string())
+            } else {
+                None
+            };
+
+            match (reason, row.value) {
+                (None, Some(value)) => {
+                    match plots.iter_mut().find(|p| p.block_id == row.block_id) {
+                        Some(plot) => {
+                            plot.value += value;
+                            plot.units += 1;
+                        }
+                        None => plots.push(PlotValue {
+                            block_id: row.block_id,
+                            block_code: row.block_code.clone(),
+                            treatment: row.treatment.clone(),
+                            replication: row.replication,
+                            factor_levels: row.factor_levels.clone(),
+                            position_row: row.position_row,
+                            position_column: row.position_column,
+                            units: 1,
+                            value,
+                        }),
+                    }
+                    used.push(DatasetRow {
+                        data_id: row.data_id,
+                        unit_id: row.unit_id,
+                        unit_code: row.unit_code,
+                        block_id: row.block_id,
+                        block_code: row.block_code,
+                        treatment: row.treatment,
+                        replication: row.replication,
+                        value,
+                    });
+                }
+                (reason, _) => excluded.push(ExcludedRow {
+                    data_id: row.data_id,
+                    unit_code: row.unit_code,
+                    block_code: row.block_code,
+                    reason: reason.unwrap_or_default(),
+                }),
+            }
+        }
+        for plot in &mut plots {
+            plot.value /= plot.units as f64;
+        }
+
+        Ok(ProjectDataset {
+            project_id,
+            parameter_id,
+            session_id,
+            project_design,
+            parameter_data_type,
+            used,
+            excluded,
+            plots,
+        })
+    }
+
+    /// Runs the ANOVA that matches `design` on the plot means of a dataset:
+    /// one-way for RAL, RCBD for RAK (replication numbers as blocks), a
+    /// general linear model over the plots' factor levels for factorial
+    /// projects and split-plot ANOVA on `main_plot` / `sub_plot` levels.
+    /// Rating parameters get rank tests instead, as in the report: Friedman
+    /// for a complete RAK layout, Kruskal-Wallis otherwise.
+    pub fn project_anova(
+        dataset: &ProjectDataset,
+        design: ExperimentDesign,
+        post_hoc: PostHocTest,
+        alpha: f64,
+    ) -> Result<ProjectAnova, AppError> {
+        let treatments = dataset.treatments();
+        let plots = &dataset.plots;
+        let separate = |means: &[f64], sizes: &[usize], error: &AnovaSource| {
+            Self::mean_separation(post_hoc, means, sizes, error.ms, error.df as f64, alpha)
+        };
+        let mut reps: Vec<i32> = plots.iter().filter_map(|p| p.replication).collect();
+        reps.sort();
+        reps.dedup();
+        // [treatment][replication] plot means; a plot per cell at most
+        let block_table = || -> Result<Vec<Vec<Option<f64>>>, AppError> {
+            let mut cells = vec![vec![None; reps.len()]; treatments.len()];
+            for plot in plots {
+                let t = treatments.iter().position(|x| *x == plot.treatment).unwrap_or(0);
+                let r = reps.iter().position(|x| Some(*x) == plot.replication).unwrap_or(0);
+                if cells[t][r].replace(plot.value).is_some() {
+                    return Err(AppError::Validation(format!(
+                        "Treatment {} has more than one plot in replication {}",
+                        plot.treatment, reps[r]
+                    )));
+                }
+            }
+            Ok(cells)
+        };
+
+        if dataset.parameter_data_type == "rating" {
+            let groups: Vec<Vec<f64>> = treatments
+                .iter()
+                .map(|t| plots.iter().filter(|p| p.treatment == *t).map(|p| p.value).collect())
+                .collect();
+            if groups.len() < 2 || groups.iter().any(|g| g.len() < 2) {
+                return Err(AppError::Validation(
+                    "Rank tests need at least 2 treatments with replicate plots".to_string(),
+                ));
+            }
+            if design == ExperimentDesign::Rak && reps.len() >= 2 && plots.iter().all(|p| p.replication.is_some()) {
+                let complete: Option<Vec<Vec<f64>>> =
+                    block_table()?.into_iter().map(|row| row.into_iter().collect()).collect();
+                if let Some(data) = complete {
+                    return Ok(ProjectAnova::Friedman { result: Self::friedman(&data) });
+                }
+            }
+            return Ok(ProjectAnova::KruskalWallis { result: Self::kruskal_wallis(&groups) });
+        }
+        let level = |plot: &PlotValue, key: &str| -> Option<String> {
+            plot.factor_levels
+                .as_ref()
+                .and_then(|f| f.get(key))
+                .and_then(|v| v.as_str().map(str::to_string).or_else(|| Some(v.to_string())))
+        };
+
+        match design {
+            ExperimentDesign::Ral => {
+                let groups: Vec<Vec<f64>> = treatments
+                    .iter()
+                    .map(|t| plots.iter().filter(|p| p.treatment == *t).map(|p| p.value).collect())
+                    .collect();
+                if groups.len() < 2 || groups.iter().all(|g| g.len() < 2) {
+                    return Err(AppError::Validation(
+                        "CRD analysis needs at least 2 treatments with replicate plots".to_string(),
+                    ));
+                }
+                let anova = Self::one_way_anova(&groups);
+                let significant = anova.source_between.p.map(|p| p < alpha).unwrap_or(false);
+                let mean_separation =
+                    significant.then(|| separate(&anova.group_means, &anova.group_sizes, &anova.source_within));
+                Ok(ProjectAnova::Crd { anova, mean_separation })
+            }
+            ExperimentDesign::Rak => {
+                if reps.len() < 2 || plots.iter().any(|p| p.replication.is_none()) {
+                    return Err(AppError::Validation(
+                        "RCBD analysis needs a replication number on every plot and at least 2 replications".to_string(),
+                    ));
+                }
+                let anova = Self::rcbd_anova(&block_table()?);
+                let significant = anova.treatment.p.map(|p| p < alpha).unwrap_or(false);
+                let mean_separation =
+                    significant.then(|| separate(&anova.treatment_means, &anova.treatment_sizes, &anova.error));
+                Ok(ProjectAnova::Rcbd { anova, mean_separation })
+            }
+            ExperimentDesign::Factorial => {
+                let mut factors: Vec<String> = plots
+                    .first()
+                    .and_then(|p| p.factor_levels.as_ref())
+                    .and_then(|f| f.as_object())
+                    .map(|o| o.keys().cloned().collect())
+                    .unwrap_or_default();
+                factors.sort();
+                if factors.is_empty() || plots.iter().any(|p| factors.iter().any(|f| level(p, f).is_none())) {
+                    return Err(AppError::Validation(
+                        "Factorial analysis needs the same factor levels recorded on every plot".to_string(),
+                    ));
+                }
+                let blocked = plots.iter().all(|p| p.replication.is_some());
+                let mut model = GlmModel {
+                    response: plots.iter().map(|p| p.value).collect(),
+                    factors: factors
+                        .iter()
+                        .map(|f| GlmFactor {
+                            name: f.clone(),
+                            levels: plots.iter().map(|p| level(p, f).unwrap_or_default()).collect(),
+                        })
+                        .collect(),
+                    covariates: Vec::new(),
+                    terms: Vec::new(),
+                };
+                let k = factors.len();
+                let mut subsets: Vec<Vec<String>> = (1..(1usize << k))
+                    .map(|mask| (0..k).filter(|i| mask & (1 << i) != 0).map(|i| factors[i].clone()).collect())
+                    .collect();
+                subsets.sort_by_key(|s| s.len());
+                if blocked {
+                    model.factors.push(GlmFactor {
+                        name: "Replication".to_string(),
+                        levels: plots.iter().map(|p| p.replication.unwrap_or(0).to_string()).collect(),
+                    });
+                    model.terms.push(vec!["Replication".to_string()]);
+                }
+                model.terms.extend(subsets);
+                Ok(ProjectAnova::Factorial { anova: Self::glm(&model) })
+            }
+            ExperimentDesign::SplitPlot => {
+                let keyed: Option<Vec<(String, String, i32, f64)>> = plots
+                    .iter()
+                    .map(|p| Some((level(p, "main_plot")?, level(p, "sub_plot")?, p.replication?, p.value)))
+                    .collect();
+                let keyed = keyed.ok_or_else(|| {
+                    AppError::Validation(
+                        "Split-plot analysis needs main_plot/sub_plot levels and a replication number on every plot"
+                            .to_string(),
+                    )
+                })?;
+                let distinct = |mut v: Vec<String>| {
+                    v.sort();
+                    v.dedup();
+                    v
+                };
+                let main_plot_levels = distinct(keyed.iter().map(|p| p.0.clone()).collect());
+                let sub_plot_levels = distinct(keyed.iter().map(|p| p.1.clone()).collect());
+                let mut reps: Vec<i32> = keyed.iter().map(|p| p.2).collect();
+                reps.sort();
+                reps.dedup();
+                let mut cells = vec![vec![vec![None; reps.len()]; sub_plot_levels.len()]; main_plot_levels.len()];
+                for (m, s, r, value) in &keyed {
+                    let a = main_plot_levels.iter().position(|l| l == m).unwrap_or(0);
+                    let b = sub_plot_levels.iter().position(|l| l == s).unwrap_or(0);
+                    let k = reps.iter().position(|x| x == r).unwrap_or(0);
+                    if cells[a][b][k].replace(*value).is_some() {
+                        return Err(AppError::Validation(format!(
+                            "Main plot {} / sub plot {} has more than one plot in replication {}",
+                            m, s, r
+                        )));
+                    }
+                }
+                let complete: Option<Vec<Vec<Vec<f64>>>> = cells
+                    .iter()
+                    .map(|m| m.iter().map(|s| s.iter().copied().collect()).collect())
+                    .collect();
+                let values = complete
+                    .filter(|_| main_plot_levels.len() >= 2 && sub_plot_levels.len() >= 2 && reps.len() >= 2)
+                    .ok_or_else(|| {
+                        AppError::Validation(
+                            "Split-plot analysis needs a complete, balanced layout with at least 2 levels and 2 replications"
+                                .to_string(),
+                        )
+                    })?;
+                Ok(ProjectAnova::SplitPlot {
+                    anova: Self::split_plot_anova(&values),
+                    main_plot_levels,
+                    sub_plot_levels,
+                })
+            }
+            ExperimentDesign::Custom => Err(AppError::Validation(
+                "Custom designs have no default analysis; use /analysis/glm".to_string(),
+            )),
+        }
+    }
+}
+
+// ==============================================================================
+// YIELD PREDICTION
+// ==============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct YieldPlot {
+    pub project_id: Uuid,
+    pub block_code: String,
+    pub treatment: String,
+    pub is_control: bool,
+    pub features: Vec<Option<f64>>, // predictor plot means, then days after treatment
+    pub yield_value: Option<f64>,   // plot mean of the yield parameter
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct YieldPredictionData {
+    pub as_of_day: i32,
+    pub predictors: Vec<MonitoringType>,
+    pub training_projects: Vec<Uuid>,
+    pub training: Vec<YieldPlot>,
+    pub target: Vec<YieldPlot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegressionCoefficient {
+    pub term: String,
+    pub estimate: f64,
+    pub std_error: f64,
+    pub t_value: f64,
+    pub p_value: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrossValidation {
+    pub method: String, // leave_one_project_out or leave_one_plot_out
+    pub folds: usize,
+    pub predictions: usize,
+    pub rmse: f64,
+    pub mae: f64,
+    pub mape_percent: Option<f64>,
+    pub q_squared: f64,         // 1 - PRESS / SS total
+    pub interval_coverage: f64, // share of held-out plots inside their prediction interval
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct YieldModel {
+    pub coefficients: Vec<RegressionCoefficient>, // intercept first; constant predictors are dropped
+    pub n: usize,
+    pub training_projects: usize,
+    pub r_squared: f64,
+    pub adjusted_r_squared: f64,
+    pub residual_se: f64,
+    pub df_error: usize,
+    pub cross_validation: Option<CrossValidation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreatmentYieldPrediction {
+    pub treatment: String,
+    pub is_control: bool,
+    pub plots: usize,
+    pub predicted_yield: f64,
+    pub lower: f64, // prediction interval for the treatment's mean yield over its plots
+    pub upper: f64,
+    pub vs_control_percent: Option<f64>,
+    pub likely_below_control: bool, // upper bound below the control's prediction
+    pub extrapolated: bool,         // leverage beyond any training plot
+    pub observed_yield: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct YieldPrediction {
+    pub as_of_day: i32,
+    pub predictors: Vec<MonitoringType>,
+    pub confidence_level: f64,
+    pub model: YieldModel,
+    pub treatments: Vec<TreatmentYieldPrediction>,
+    pub incomplete_plots: Vec<String>, // target plots missing a predictor
+}
+
+/// Least-squares fit kept for prediction
+struct YieldFit {
+    columns: Vec<usize>, // feature indices after the intercept
+    beta: DVector<f64>,
+    xtx_inv: DMatrix<f64>,
+    mse: f64,
+    df_error: usize,
+    max_leverage: f64,
+}
+
+impl YieldFit {
+    fn design_row(&self, features: &[f64]) -> DVector<f64> {
+        DVector::from_iterator(
+            self.columns.len() + 1,
+            std::iter::once(1.0).chain(self.columns.iter().map(|c| features[*c])),
+        )
+    }
+
+    fn leverage(&self, x: &DVector<f64>) -> f64 {
+        (x.transpose() * &self.xtx_inv * x)[(0, 0)]
+    }
+
+    /// Prediction and interval half-width for the mean of `m` new plots
+    fn predict(&self, features: &[f64], m: usize, t_crit: f64) -> (f64, f64, f64) {
+        let x = self.design_row(features);
+        let h = self.leverage(&x);
+        let prediction = x.dot(&self.beta);
+        (prediction, t_crit * (self.mse * (1.0 / m as f64 + h)).sqrt(), h)
+    }
+}
+
+impl StatisticalAnalysis {
+    /// Multiple linear regression of plot yield on early-season plot means
+    fn fit_yield(rows: &[(&[f64], f64)], columns: &[usize]) -> Option<YieldFit> {
+        let n = rows.len();
+        let p = columns.len() + 1;
+        if n <= p {
+            return None;
+        }
+        let x = DMatrix::from_fn(n, p, |i, j| if j == 0 { 1.0 } else { rows[i].0[columns[j - 1]] });
+        let y = DVector::from_iterator(n, rows.iter().map(|r| r.1));
+        let xtx_inv = (x.transpose() * &x).try_inverse()?;
+        let beta = &xtx_inv * x.transpose() * &y;
+        let residuals = &y - &x * &beta;
+        let df_error = n - p;
+        let max_leverage = (0..n)
+            .map(|i| {
+                let row = x.row(i).transpose();
+                (row.transpose() * &xtx_inv * &row)[(0, 0)]
+            })
+            .fold(0.0, f64::max);
+
+        Some(YieldFit {
+            columns: columns.to_vec(),
+            beta,
+            xtx_inv,
+            mse: residuals.dot(&residuals) / df_error as f64,
+            df_error,
+            max_leverage,
+        })
+    }
+
+    /// Trains on completed projects of the same crop and predicts the mean
+    /// yield of each treatment in the target project from early-season plot
+    /// means. Accuracy is cross-validated leaving one training project out
+    /// at a time (one plot at a time when there are fewer than 3 projects),
+    /// which is how the model is used on a new trial.
+    pub fn yield_prediction(data: &YieldPredictionData, confidence_level: f64) -> Option<YieldPrediction> {
+        let k = data.predictors.len() + 1;
+        let complete = |plot: &YieldPlot| -> Option<Vec<f64>> { plot.features.iter().copied().collect() };
+        let training: Vec<(Vec<f64>, f64, Uuid)> = data
+            .training
+            .iter()
+            .filter_map(|plot| Some((complete(plot)?, plot.yield_value?, plot.project_id)))
+            .collect();
+
+        // Predictors that do not vary across training plots cannot be estimated
+        let columns: Vec<usize> = (0..k)
+            .filter(|c| {
+                let first = training.first().map(|t| t.0[*c]);
+                training.iter().any(|t| Some(t.0[*c]) != first)
+            })
+            .collect();
+        let rows: Vec<(&[f64], f64)> = training.iter().map(|t| (t.0.as_slice(), t.1)).collect();
+        let fit = Self::fit_yield(&rows, &columns)?;
+
+        let t_at = |df: usize| {
+            StudentsT::new(0.0, 1.0, df as f64)
+                .map(|d| d.inverse_cdf(1.0 - (1.0 - confidence_level) / 2.0))
+                .unwrap_or(1.96)
+        };
+        let t_crit = t_at(fit.df_error);
+
+        let mut names: Vec<String> = data
+            .predictors
+            .iter()
+            .map(|p| serde_json::to_value(p).ok().and_then(|v| v.as_str().map(String::from)).unwrap_or_default())
+            .collect();
+        names.push("days_after_treatment".to_string());
+        let terms = std::iter::once("intercept".to_string()).chain(columns.iter().map(|c| names[*c].clone()));
+        let coefficients: Vec<RegressionCoefficient> = terms
+            .enumerate()
+            .map(|(j, term)| {
+                let estimate = fit.beta[j];
+                let std_error = (fit.mse * fit.xtx_inv[(j, j)]).sqrt();
+                let t_value = if std_error > 0.0 { estimate / std_error } else { 0.0 };
+                let p_value = StudentsT::new(0.0, 1.0, fit.df_error as f64)
+                    .map(|d| 2.0 * (1.0 - d.cdf(t_value.abs())))
+                    .unwrap_or(1.0);
+                RegressionCoefficient { term, estimate, std_error, t_value, p_value }
+            })
+            .collect();
+
+        let n = training.len();
+        let mean_y = training.iter().map(|t| t.1).sum::<f64>() / n as f64;
+        let ss_total: f64 = training.iter().map(|t| (t.1 - mean_y).powi(2)).sum();
+        let sse = fit.mse * fit.df_error as f64;
+        let r_squared = if ss_total > 0.0 { 1.0 - sse / ss_total } else { 0.0 };
+        let adjusted_r_squared = 1.0 - (1.0 - r_squared) * (n - 1) as f64 / fit.df_error as f64;
+
+        let mut projects: Vec<Uuid> = training.iter().map(|t| t.2).collect();
+        projects.sort();
+        projects.dedup();
+
+        // Held-out folds: one project, or one plot, at a time
+        let folds: Vec<Vec<usize>> = if projects.len() >= 3 {
+            projects.iter().map(|p| (0..n).filter(|i| training[*i].2 == *p).collect()).collect()
+        } else {
+            (0..n).map(|i| vec![i]).collect()
+        };
+        let mut held_out: Vec<(f64, f64, f64)> = Vec::new(); // (observed, predicted, half-width)
+        for fold in &folds {
+            let rest: Vec<(&[f64], f64)> = (0..n)
+                .filter(|i| !fold.contains(i))
+                .map(|i| (training[i].0.as_slice(), training[i].1))
+                .collect();
+            if let Some(fold_fit) = Self::fit_yield(&rest, &columns) {
+                let t_fold = t_at(fold_fit.df_error);
+                for i in fold {
+                    let (predicted, half_width, _) = fold_fit.predict(&training[*i].0, 1, t_fold);
+                    held_out.push((training[*i].1, predicted, half_width));
+                }
+            }
+        }
+        let cross_validation = (!held_out.is_empty()).then(|| {
+            let m = held_out.len() as f64;
+            let press: f64 = held_out.iter().map(|(o, p, _)| (o - p).powi(2)).sum();
+            let positive: Vec<f64> =
+                held_out.iter().filter(|(o, _, _)| *o > 0.0).map(|(o, p, _)| ((o - p) / o).abs()).collect();
+            let held_mean = held_out.iter().map(|h| h.0).sum::<f64>() / m;
+            let held_ss: f64 = held_out.iter().map(|h| (h.0 - held_mean).powi(2)).sum();
+            CrossValidation {
+                method: if projects.len() >= 3 { "leave_one_project_out" } else { "leave_one_plot_out" }.to_string(),
+                folds: folds.len(),
+                predictions: held_out.len(),
+                rmse: (press / m).sqrt(),
+                mae: held_out.iter().map(|(o, p, _)| (o - p).abs()).sum::<f64>() / m,
+                mape_percent: (!positive.is_empty())
+                    .then(|| positive.iter().sum::<f64>() / positive.len() as f64 * 100.0),
+                q_squared: if held_ss > 0.0 { 1.0 - press / held_ss } else { 0.0 },
+                interval_coverage: held_out.iter().filter(|(o, p, w)| (o - p).abs() <= *w).count() as f64 / m,
+            }
+        });
+
+        // Treatment predictions at the mean of each treatment's plots
+        let mut incomplete_plots = Vec::new();
+        struct TreatmentPlots {
+            treatment: String,
+            is_control: bool,
+            features: Vec<Vec<f64>>,
+            observed: Vec<f64>,
+        }
+        let mut groups: Vec<TreatmentPlots> = Vec::new();
+        for plot in &data.target {
+            let Some(features) = complete(plot) else {
+                incomplete_plots.push(plot.block_code.clone());
+                continue;
+            };
+            let index = match groups.iter().position(|g| g.treatment == plot.treatment) {
+                Some(index) => index,
+                None => {
+                    groups.push(TreatmentPlots {
+                        treatment: plot.treatment.clone(),
+                        is_control: plot.is_control,
+                        features: Vec::new(),
+                        observed: Vec::new(),
+                    });
+                    groups.len() - 1
+                }
+            };
+            groups[index].features.push(features);
+            groups[index].observed.extend(plot.yield_value);
+        }
+
+        let mut treatments: Vec<TreatmentYieldPrediction> = groups
+            .into_iter()
+            .map(|TreatmentPlots { treatment, is_control, features: plots, observed }| {
+                let m = plots.len();
+                let means: Vec<f64> = (0..k).map(|c| plots.iter().map(|f| f[c]).sum::<f64>() / m as f64).collect();
+                let (predicted_yield, half_width, leverage) = fit.predict(&means, m, t_crit);
+                TreatmentYieldPrediction {
+                    treatment,
+                    is_control,
+                    plots: m,
+                    predicted_yield,
+                    lower: predicted_yield - half_width,
+                    upper: predicted_yield + half_width,
+                    vs_control_percent: None,
+                    likely_below_control: false,
+                    extrapolated: leverage > fit.max_leverage,
+                    observed_yield: (!observed.is_empty()).then(|| observed.iter().sum::<f64>() / observed.len() as f64),
+                }
+            })
+            .collect();
+
+        if let Some(control) = treatments.iter().find(|t| t.is_control).map(|t| t.predicted_yield) {
+            for t in treatments.iter_mut().filter(|t| !t.is_control) {
+                if control != 0.0 {
+                    t.vs_control_percent = Some((t.predicted_yield - control) / control * 100.0);
+                }
+                t.likely_below_control = t.upper < control;
+            }
+        }
+        treatments.sort_by(|a, b| b.predicted_yield.partial_cmp(&a.predicted_yield).unwrap_or(std::cmp::Ordering::Equal));
+
+        Some(YieldPrediction {
+            as_of_day: data.as_of_day,
+            predictors: data.predictors.clone(),
+            confidence_level,
+            model: YieldModel {
+                coefficients,
+                n,
+                training_projects: projects.len(),
+                r_squared,
+                adjusted_r_squared,
+                residual_se: fit.mse.sqrt(),
+                df_error: fit.df_error,
+                cross_validation,
+            },
+            treatments,
+            incomplete_plots,
+        })
+    }
+
+    /// Plot-level predictors and yield for the target project and for every
+    /// completed project of the same crop. Each predictor is taken from the
+    /// project's latest session on or before `as_of_day` (by default the
+    /// target's latest session with predictor data), so training plots are
+    /// seen at the same growth stage as the target.
+    pub async fn fetch_yield_prediction_data(
+        conn: &mut PgConnection,
+        project_id: Uuid,
+        predictors: &[MonitoringType],
+        as_of_day: Option<i32>,
+    ) -> Result<YieldPredictionData, AppError> {
+        let as_of_day = match as_of_day {
+            Some(day) => day,
+            None => {
+                let latest: Option<i32> = sqlx::query_scalar(
+                    r#"
+                    SELECT MAX(ms.days_after_treatment)
+                    FROM monitoring_sessions ms
+                    JOIN monitoring_data md ON md.session_id = ms.id
+                    JOIN monitoring_parameters mp ON md.parameter_id = mp.id
+                    WHERE ms.project_id = $1 AND mp.parameter_type = ANY($2)
+                    "#
+                )
+                .bind(project_id)
+                .bind(predictors)
+                .fetch_one(&mut *conn)
+                .await
+                .map_err(|e| AppError::Database(e.to_string()))?;
+                latest.ok_or_else(|| {
+                    AppError::Validation(
+                        "The project has no predictor data with days after treatment recorded".to_string(),
+                    )
+                })?
+            }
+        };
+
+        let training_projects: Vec<Uuid> = sqlx::query_scalar(
+            r#"
+            SELECT p.id FROM projects p
+            JOIN projects target ON target.id = $1
+            WHERE p.id <> target.id
+            AND p.status = 'completed'
+            AND LOWER(p.crop_type) = LOWER(target.crop_type)
+            "#
+        )
+        .bind(project_id)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        let mut project_ids = training_projects.clone();
+        project_ids.push(project_id);
+
+        #[derive(sqlx::FromRow)]
+        struct PredictorRow {
+            block_id: Uuid,
+            parameter_type: MonitoringType,
+            days: Option<i32>,
+            value: Option<f64>,
+        }
+
+        let predictor_rows: Vec<PredictorRow> = sqlx::query_as(
+            r#"
+            WITH early AS (
+                SELECT
+                    eb.id AS block_id,
+                    mp.parameter_type,
+                    ms.days_after_treatment,
+                    md.numeric_value::float8 AS value,
+                    DENSE_RANK() OVER (
+                        PARTITION BY eb.project_id, mp.parameter_type
+                        ORDER BY ms.days_after_treatment DESC
+                    ) AS recency
+                FROM monitoring_data md
+                JOIN experimental_units eu ON md.unit_id = eu.id
+                JOIN experimental_blocks eb ON eu.block_id = eb.id
+                JOIN monitoring_sessions ms ON md.session_id = ms.id
+                JOIN monitoring_parameters mp ON md.parameter_id = mp.id
+                WHERE eb.project_id = ANY($1)
+                AND mp.parameter_type = ANY($2)
+                AND ms.days_after_treatment <= $3
+                AND md.numeric_value IS NOT NULL
+                AND NOT COALESCE(md.is_outlier, false)
+                AND COALESCE(eu.is_active, true)
+                AND eu.excluded_reason IS NULL
+            )
+            SELECT block_id, parameter_type, MAX(days_after_treatment) AS days, AVG(value) AS value
+            FROM early
+            WHERE recency = 1
+            GROUP BY block_id, parameter_type
+            "#
+        )
+        .bind(&project_ids)
+        .bind(predictors)
+        .bind(as_of_day)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        #[derive(sqlx::FromRow)]
+        struct BlockRow {
+            id: Uuid,
+            project_id: Uuid,
+            block_code: String,
+            treatment: String,
+            is_control: bool,
+            yield_value: Option<f64>,
+        }
+
+        let blocks: Vec<BlockRow> = sqlx::query_as(
+            r#"
+            SELECT
+                eb.id,
+                eb.project_id,
+                eb.block_code,
+                COALESCE(f.code, eb.treatment_description, eb.block_code) AS treatment,
+                COALESCE(eb.is_control, false) AS is_control,
+                AVG(md.numeric_value)::float8 AS yield_value
+            FROM experimental_blocks eb
+            LEFT JOIN formulas f ON eb.formula_id = f.id
+            LEFT JOIN experimental_units eu ON eu.block_id = eb.id
+                AND COALESCE(eu.is_active, true) AND eu.excluded_reason IS NULL
+            LEFT JOIN monitoring_data md ON md.unit_id = eu.id
+                AND NOT COALESCE(md.is_outlier, false)
+                AND md.parameter_id IN (SELECT id FROM monitoring_parameters WHERE parameter_type = 'yield')
+            WHERE eb.project_id = ANY($1)
+            GROUP BY eb.id, eb.project_id, eb.block_code, f.code, eb.treatment_description, eb.is_control
+            ORDER BY eb.block_code
+            "#
+        )
+        .bind(&project_ids)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        let (mut training, mut target) = (Vec::new(), Vec::new());
+        for block in blocks {
+            let found: Vec<&PredictorRow> = predictor_rows.iter().filter(|r| r.block_id == block.id).collect();
+            let mut features: Vec<Option<f64>> = predictors
+                .iter()
+                .map(|p| found.iter().find(|r| r.parameter_type == *p).and_then(|r| r.value))
+                .collect();
+            let days: Vec<f64> = found.iter().filter_map(|r| r.days).map(f64::from).collect();
+            features.push((!days.is_empty()).then(|| days.iter().sum::<f64>() / days.len() as f64));
+
+            let plot = YieldPlot {
+                project_id: block.project_id,
+                block_code: block.block_code,
+                treatment: block.treatment,
+                is_control: block.is_control,
+                features,
+                yield_value: block.yield_value,
+            };
+            if block.project_id == project_id {
+                target.push(plot);
+            } else {
+                training.push(plot);
+            }
+        }
+
+        Ok(YieldPredictionData {
+            as_of_day,
+            predictors: predictors.to_vec(),
+            training_projects,
+            training,
+            target,
+        })
+    }
+}
+
+// ==============================================================================
+// SPATIAL ANALYSIS
+// ==============================================================================
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpatialAdjustment {
+    #[default]
+    RowColumn,        // linear and quadratic row and column trend covariates
+    NearestNeighbour, // Papadakis: mean residual of the neighbouring plots
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Neighbourhood {
+    #[default]
+    Rook,  // plots sharing an edge
+    Queen, // plots sharing an edge or a corner
+}
+
+impl Neighbourhood {
+    fn adjacent(self, a: (i32, i32), b: (i32, i32)) -> bool {
+        let (dr, dc) = ((a.0 - b.0).abs(), (a.1 - b.1).abs());
+        match self {
+            Neighbourhood::Rook => dr + dc == 1,
+            Neighbourhood::Queen => dr.max(dc) == 1,
+        }
+    }
+
+    fn neighbours(self, positions: &[(i32, i32)]) -> Vec<Vec<usize>> {
+        positions
+            .iter()
+            .map(|a| (0..positions.len()).filter(|j| self.adjacent(*a, positions[*j])).collect())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoransI {
+    pub statistic: f64,
+    pub expected: f64, // n/S0 · tr(MW)/(n-p); -1/(n-1) for raw values
+    pub z_score: f64,  // residual moments under normal errors
+    pub p_value: f64,  // two-sided, normal approximation
+    pub permutation_p_value: f64,
+    pub permutations: usize,
+    pub neighbour_pairs: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpatialTrendPoint {
+    pub position: i32,
+    pub plots: usize,
+    pub mean_value: f64,
+    pub mean_residual: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpatialTrend {
+    pub points: Vec<SpatialTrendPoint>, // one per row or column, for trend plots
+    pub slope: f64,                     // residual change per row or column
+    pub p_value: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpatialModelFit {
+    pub error_df: usize,
+    pub error_ms: f64,
+    pub treatment_f: f64,
+    pub treatment_p: f64,
+    pub covariates: Vec<RegressionCoefficient>,
+    pub morans_i: Option<MoransI>, // on the residuals; None without neighbouring plots
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpatialTreatmentMean {
+    pub treatment: String,
+    pub plots: usize,
+    pub unadjusted_mean: f64,
+    pub unadjusted_se: f64,
+    pub adjusted_mean: f64, // least-squares mean at the average covariate values
+    pub adjusted_se: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpatialAnalysis {
+    pub adjustment: SpatialAdjustment,
+    pub neighbourhood: Neighbourhood,
+    pub blocks: bool,
+    pub plots_used: usize,
+    pub plots_without_position: Vec<String>,
+    pub row_trend: SpatialTrend,
+    pub column_trend: SpatialTrend,
+    pub unadjusted: SpatialModelFit,
+    pub adjusted: SpatialModelFit,
+    pub relative_efficiency: f64, // unadjusted / adjusted error mean square
+    pub means: Vec<SpatialTreatmentMean>,
+    pub seed: u64, // of the Moran's I permutations
+}
+
+/// Least-squares fit of a plot-level linear model
+struct PlotModelFit {
+    beta: DVector<f64>,
+    xtx_inv: DMatrix<f64>,
+    residuals: Vec<f64>,
+    sse: f64,
+    df_error: usize,
+}
+
+impl PlotModelFit {
+    fn new(x: &DMatrix<f64>, y: &DVector<f64>) -> Option<Self> {
+        let (n, p) = x.shape();
+        if n <= p {
+            return None;
+        }
+        let xtx_inv = (x.transpose() * x).try_inverse()?;
+        let beta = &xtx_inv * x.transpose() * y;
+        let residuals = y - x * &beta;
+        Some(Self {
+            sse: residuals.dot(&residuals),
+            residuals: residuals.iter().copied().collect(),
+            beta,
+            xtx_inv,
+            df_error: n - p,
+        })
+    }
+
+    fn mse(&self) -> f64 {
+        self.sse / self.df_error as f64
+    }
+}
+
+impl StatisticalAnalysis {
+    /// Moran's I of least-squares residuals over plot positions with binary
+    /// weights. `design` is the model matrix the residuals come from (an
+    /// intercept column alone for raw values). The z-score uses the moments
+    /// of I for regression residuals under normal errors (Cliff & Ord),
+    /// with M = I - X(X'X)⁻¹X': E[I] = n/S0 · tr(MW)/(n-p). The permutation
+    /// p-value shuffles the residuals over the positions.
+    pub fn morans_i(
+        residuals: &[f64],
+        design: &DMatrix<f64>,
+        positions: &[(i32, i32)],
+        neighbourhood: Neighbourhood,
+        permutations: usize,
+        seed: u64,
+    ) -> Option<MoransI> {
+        let n = residuals.len();
+        let p = design.ncols();
+        let neighbours = neighbourhood.neighbours(positions);
+        let s0: f64 = neighbours.iter().map(|nb| nb.len() as f64).sum();
+        if n < 4 || n <= p || s0 == 0.0 {
+            return None;
+        }
+        let m2: f64 = residuals.iter().map(|v| v * v).sum();
+        if m2 <= 0.0 {
+            return None;
+        }
+        let statistic_of = |e: &[f64]| {
+            let cross: f64 = neighbours.iter().enumerate().map(|(i, nb)| nb.iter().map(|j| e[i] * e[*j]).sum::<f64>()).sum();
+            n as f64 / s0 * cross / m2
+        };
+        let statistic = statistic_of(residuals);
+
+        let weights = DMatrix::from_fn(n, n, |i, j| neighbours[i].contains(&j) as u8 as f64);
+        let hat = design * (design.transpose() * design).try_inverse()? * design.transpose();
+        let mw = (DMatrix::identity(n, n) - hat) * weights;
+        let (trace, trace_sq) = (mw.trace(), (&mw * &mw).trace());
+        let (scale, df) = (n as f64 / s0, (n - p) as f64);
+        let expected = scale * trace / df;
+        // W symmetric, so tr(MWMW') = tr((MW)²)
+        let variance = scale * scale * (2.0 * trace_sq + trace * trace) / (df * (df + 2.0)) - expected * expected;
+        let z_score = if variance > 0.0 { (statistic - expected) / variance.sqrt() } else { 0.0 };
+        let p_value = Normal::new(0.0, 1.0).map(|d| 2.0 * (1.0 - d.cdf(z_score.abs()))).unwrap_or(1.0);
+
+        let observed = (statistic - expected).abs();
+        let permuted = Self::resample_chunks(permutations, seed, |rng, count| {
+            let mut shuffled = residuals.to_vec();
+            (0..count)
+                .map(|_| {
+                    shuffled.shuffle(rng);
+                    statistic_of(&shuffled)
+                })
+                .collect()
+        });
+        let extreme = permuted.iter().filter(|i| (*i - expected).abs() >= observed - 1e-12).count();
+
+        Some(MoransI {
+            statistic,
+            expected,
+            z_score,
+            p_value,
+            permutation_p_value: (extreme + 1) as f64 / (permutations + 1) as f64,
+            permutations,
+            neighbour_pairs: (s0 / 2.0) as usize,
+        })
+    }
+
+    /// Plot values and residuals averaged per row or column, with the
+    /// linear regression of the residuals on position
+    fn spatial_trend(positions: &[i32], values: &[f64], residuals: &[f64]) -> SpatialTrend {
+        let mut levels: Vec<i32> = positions.to_vec();
+        levels.sort();
+        levels.dedup();
+        let points = levels
+            .iter()
+            .map(|level| {
+                let idx: Vec<usize> = (0..positions.len()).filter(|i| positions[*i] == *level).collect();
+                let m = idx.len() as f64;
+                SpatialTrendPoint {
+                    position: *level,
+                    plots: idx.len(),
+                    mean_value: idx.iter().map(|i| values[*i]).sum::<f64>() / m,
+                    mean_residual: idx.iter().map(|i| residuals[*i]).sum::<f64>() / m,
+                }
+            })
+            .collect();
+
+        let n = positions.len() as f64;
+        let mean_x = positions.iter().map(|p| *p as f64).sum::<f64>() / n;
+        let mean_y = residuals.iter().sum::<f64>() / n;
+        let sxx: f64 = positions.iter().map(|p| (*p as f64 - mean_x).powi(2)).sum();
+        let sxy: f64 = positions.iter().zip(residuals).map(|(p, r)| (*p as f64 - mean_x) * (r - mean_y)).sum();
+        let (slope, p_value) = if sxx > 0.0 && n > 2.0 {
+            let slope = sxy / sxx;
+            let sse: f64 = positions
+                .iter()
+                .zip(residuals)
+                .map(|(p, r)| (r - mean_y - slope * (*p as f64 - mean_x)).powi(2))
+                .sum();
+            let se = (sse / (n - 2.0) / sxx).sqrt();
+            let p = if se > 0.0 {
+                StudentsT::new(0.0, 1.0, n - 2.0).map(|d| 2.0 * (1.0 - d.cdf((slope / se).abs()))).unwrap_or(1.0)
+            } else if slope == 0.0 {
+                1.0 // flat residuals: no trend at all
+            } else {
+                0.0 // residuals exactly on a sloped line
+            };
+            (slope, p)
+        } else {
+            (0.0, 1.0)
+        };
+
+        SpatialTrend { points, slope, p_value }
+    }
+
+    /// Checks plot residuals for spatial autocorrelation and refits the
+    /// treatment model with spatial covariates. Both models contain the
+    /// treatments and, with `blocks`, the replications; the adjusted model
+    /// adds row/column polynomial trends or the Papadakis nearest-neighbour
+    /// covariate. Plots without a row and column position are left out.
+    pub fn spatial_analysis(
+        dataset: &ProjectDataset,
+        adjustment: SpatialAdjustment,
+        neighbourhood: Neighbourhood,
+        blocks: bool,
+        permutations: usize,
+        seed: u64,
+    ) -> Result<SpatialAnalysis, AppError> {
+        let (plots, unplaced): (Vec<&PlotValue>, Vec<&PlotValue>) = dataset
+            .plots
+            .iter()
+            .partition(|p| p.position_row.is_some() && p.position_column.is_some());
+        let positions: Vec<(i32, i32)> = plots
+            .iter()
+            .map(|p| (p.position_row.unwrap_or_default(), p.position_column.unwrap_or_default()))
+            .collect();
+        for (i, position) in positions.iter().enumerate() {
+            if let Some(j) = positions[..i].iter().position(|p| p == position) {
+                return Err(AppError::Validation(format!(
+                    "Plots {} and {} share row {}, column {}",
+                    plots[j].block_code, plots[i].block_code, position.0, position.1
+                )));
+            }
+        }
+        let y = DVector::from_iterator(plots.len(), plots.iter().map(|p| p.value));
+
+        let mut treatments: Vec<String> = Vec::new();
+        for plot in &plots {
+            if !treatments.contains(&plot.treatment) {
+                treatments.push(plot.treatment.clone());
+            }
+        }
+        let treatment_of: Vec<usize> =
+            plots.iter().map(|p| treatments.iter().position(|t| *t == p.treatment).unwrap_or(0)).collect();
+
+        let mut replications: Vec<i32> = Vec::new();
+        if blocks {
+            if plots.iter().any(|p| p.replication.is_none()) {
+                return Err(AppError::Validation(
+                    "Blocking needs a replication number on every positioned plot".to_string(),
+                ));
+            }
+            replications = plots.iter().filter_map(|p| p.replication).collect();
+            replications.sort();
+            replications.dedup();
+        }
+        let block_of: Vec<usize> = plots
+            .iter()
+            .map(|p| p.replication.and_then(|r| replications.iter().position(|b| *b == r)).unwrap_or(0))
+            .collect();
+
+        let n = plots.len();
+        let (t, b) = (treatments.len(), replications.len().max(1));
+        if t < 2 || n <= t + b {
+            return Err(AppError::Validation(
+                "Spatial analysis needs at least 2 treatments and more positioned plots than model terms".to_string(),
+            ));
+        }
+
+        // Intercept, treatment and block dummies, then covariates
+        let design = |covariates: &[Vec<f64>], with_treatments: bool| {
+            let treatment_columns = if with_treatments { t - 1 } else { 0 };
+            let p = 1 + treatment_columns + (b - 1) + covariates.len();
+            DMatrix::from_fn(n, p, |i, j| {
+                if j == 0 {
+                    1.0
+                } else if j <= treatment_columns {
+                    (treatment_of[i] == j) as u8 as f64
+                } else if j < 1 + treatment_columns + (b - 1) {
+                    (block_of[i] == j - treatment_columns) as u8 as f64
+                } else {
+                    covariates[j - 1 - treatment_columns - (b - 1)][i]
+                }
+            })
+        };
+        let singular = || AppError::Validation("The spatial model is singular for this layout".to_string());
+        let base = PlotModelFit::new(&design(&[], true), &y).ok_or_else(singular)?;
+
+        // Spatial covariates, keeping only those that add to the model rank
+        let (candidates, names): (Vec<Vec<f64>>, Vec<&str>) = match adjustment {
+            SpatialAdjustment::RowColumn => {
+                let centred = |values: Vec<f64>| {
+                    let mean = values.iter().sum::<f64>() / n as f64;
+                    values.into_iter().map(|v| v - mean).collect::<Vec<f64>>()
+                };
+                let row = centred(positions.iter().map(|p| p.0 as f64).collect());
+                let column = centred(positions.iter().map(|p| p.1 as f64).collect());
+                let row_sq = centred(row.iter().map(|v| v * v).collect());
+                let column_sq = centred(column.iter().map(|v| v * v).collect());
+                (vec![row, column, row_sq, column_sq], vec!["row", "column", "row²", "column²"])
+            }
+            SpatialAdjustment::NearestNeighbour => {
+                let neighbours = neighbourhood.neighbours(&positions);
+                let covariate = neighbours
+                    .iter()
+                    .map(|nb| {
+                        if nb.is_empty() {
+                            0.0
+                        } else {
+                            nb.iter().map(|j| base.residuals[*j]).sum::<f64>() / nb.len() as f64
+                        }
+                    })
+                    .collect();
+                (vec![covariate], vec!["neighbour_residual"])
+            }
+        };
+        let rank = |x: &DMatrix<f64>| {
+            let singular_values = x.clone().singular_values();
+            let largest = singular_values.iter().copied().fold(0.0, f64::max);
+            singular_values.iter().filter(|s| **s > largest * 1e-10).count()
+        };
+        let mut covariates: Vec<Vec<f64>> = Vec::new();
+        let mut covariate_names: Vec<String> = Vec::new();
+        for (candidate, name) in candidates.into_iter().zip(names) {
+            let mut trial = covariates.clone();
+            trial.push(candidate);
+            let x = design(&trial, true);
+            if rank(&x) == x.ncols() && x.ncols() < n {
+                covariates = trial;
+                covariate_names.push(name.to_string());
+            }
+        }
+        let adjusted = PlotModelFit::new(&design(&covariates, true), &y).ok_or_else(singular)?;
+
+        let values: Vec<f64> = plots.iter().map(|p| p.value).collect();
+        let summarize = |fit: &PlotModelFit, covariates: &[Vec<f64>]| -> Result<SpatialModelFit, AppError> {
+            let x = design(covariates, true);
+            let reduced = PlotModelFit::new(&design(covariates, false), &y).ok_or_else(singular)?;
+            let df_treatment = (t - 1) as f64;
+            let treatment_f = ((reduced.sse - fit.sse) / df_treatment) / fit.mse();
+            let first_covariate = 1 + (t - 1) + (b - 1);
+            let coefficients = covariate_names
+                .iter()
+                .take(covariates.len())
+                .enumerate()
+                .map(|(k, term)| {
+                    let j = first_covariate + k;
+                    let estimate = fit.beta[j];
+                    let std_error = (fit.mse() * fit.xtx_inv[(j, j)]).sqrt();
+                    let t_value = if std_error > 0.0 { estimate / std_error } else { 0.0 };
+                    RegressionCoefficient {
+                        term: term.clone(),
+                        estimate,
+                        std_error,
+                        t_value,
+                        p_value: StudentsT::new(0.0, 1.0, fit.df_error as f64)
+                            .map(|d| 2.0 * (1.0 - d.cdf(t_value.abs())))
+                            .unwrap_or(1.0),
+                    }
+                })
+                .collect();
+            Ok(SpatialModelFit {
+                error_df: fit.df_error,
+                error_ms: fit.mse(),
+                treatment_f,
+                treatment_p: Self::f_p_value(treatment_f, df_treatment, fit.df_error as f64),
+                covariates: coefficients,
+                morans_i: Self::morans_i(&fit.residuals, &x, &positions, neighbourhood, permutations, seed),
+            })
+        };
+        let unadjusted_fit = summarize(&base, &[])?;
+        let adjusted_fit = summarize(&adjusted, &covariates)?;
+
+        // Least-squares means: treatment effect, averaged blocks, covariate means
+        let covariate_means: Vec<f64> = covariates.iter().map(|c| c.iter().sum::<f64>() / n as f64).collect();
+        let means = treatments
+            .iter()
+            .enumerate()
+            .map(|(k, treatment)| {
+                let members: Vec<f64> = (0..n).filter(|i| treatment_of[*i] == k).map(|i| values[i]).collect();
+                let l = DVector::from_iterator(
+                    adjusted.beta.len(),
+                    std::iter::once(1.0)
+                        .chain((1..t).map(|j| (j == k) as u8 as f64))
+                        .chain(std::iter::repeat_n(1.0 / b as f64, b - 1))
+                        .chain(covariate_means.iter().copied()),
+                );
+                let unadjusted_mean = members.iter().sum::<f64>() / members.len() as f64;
+                SpatialTreatmentMean {
+                    treatment: treatment.clone(),
+                    plots: members.len(),
+                    unadjusted_mean,
+                    unadjusted_se: (base.mse() / members.len() as f64).sqrt(),
+                    adjusted_mean: l.dot(&adjusted.beta),
+                    adjusted_se: (adjusted.mse() * (l.transpose() * &adjusted.xtx_inv * &l)[(0, 0)]).sqrt(),
+                }
+            })
+            .collect();
+
+        let rows: Vec<i32> = positions.iter().map(|p| p.0).collect();
+        let columns: Vec<i32> = positions.iter().map(|p| p.1).collect();
+
+        Ok(SpatialAnalysis {
+            adjustment,
+            neighbourhood,
+            blocks,
+            plots_used: n,
+            plots_without_position: unplaced.iter().map(|p| p.block_code.clone()).collect(),
+            row_trend: Self::spatial_trend(&rows, &values, &base.residuals),
+            column_trend: Self::spatial_trend(&columns, &values, &base.residuals),
+            relative_efficiency: if adjusted.mse() > 0.0 { base.mse() / adjusted.mse() } else { 1.0 },
+            unadjusted: unadjusted_fit,
+            adjusted: adjusted_fit,
+            means,
+            seed,
+        })
+    }
+}
+
+// ==============================================================================
+// AI ANALYSIS SERVICE
+// ==============================================================================
+
+pub struct AIAnalysisService {
+    client: Client<OpenAIConfig>,
+    model: String,
+}
+
+impl AIAnalysisService {
+    pub fn new(settings: &Settings) -> Option<Self> {
+        if settings.openai.api_key.is_empty() {
+            return None;
+        }
+
+        let config = OpenAIConfig::new().with_api_key(&settings.openai.api_key);
+        let client = Client::with_config(config);
+
+        Some(Self {
+            client,
+            model: settings.openai.model.clone(),
+        })
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Interpret a monitoring summary read with `fetch_monitoring_summary`,
+    /// so callers can close their data snapshot before the model is called
+    pub async fn analyze_experiment_data(
+        &self,
+        pool: &PgPool,
+        project_id: Uuid,
+        data_summary: &MonitoringSummary,
+        analysis_type: &str,
+    ) -> Result<AIAnalysisResult, AppError> {
+        // Fetch project data
+        let project = ProjectService::get_by_id(pool, project_id).await?;
+
+        // Build prompt based on analysis type
+        let prompt = self.build_analysis_prompt(&project, data_summary, analysis_type);
+
+        // Call OpenAI API
+        let response = self.call_openai(&prompt).await?;
+
+        Ok(AIAnalysisResult {
+            analysis_type: analysis_type.to_string(),
+            insights: response.insights,
+            recommendations: response.recommendations,
+            statistical_interpretation: response.statistical_interpretation,
+            confidence_level: response.confidence_level,
+            limitations: response.limitations,
+            next_steps: response.next_steps,
+        })
+    }
+
+    pub async fn fetch_monitoring_summary(
+        &self,
+        conn: &mut PgConnection,
+        project_id: Uuid,
+    ) -> Result<MonitoringSummary, AppError> {
+        // Fetch parameter statistics
+        #[derive(sqlx::FromRow)]
+        struct StatsRow {
+            parameter_name: Option<String>,
+            parameter_code: Option<String>,
+            block_code: String,
+            is_control: bool,
+            data_count: Option<i64>,
+            avg_value: Option<f64>,
+            std_dev: Option<f64>,
+            min_value: Option<f64>,
+            max_value: Option<f64>,
+        }
+
+        let stats: Vec<StatsRow> = sqlx::query_as(
+            r#"
+            SELECT 
+                mp.name as parameter_name,
+                mp.code as parameter_code,
+                eb.block_code,
+                eb.is_control,
+                COUNT(md.id) as data_count,
+                AVG(md.numeric_value)::float8 as avg_value,
+                STDDEV(md.numeric_value)::float8 as std_dev,
+                MIN(md.numeric_value)::float8 as min_value,
+                MAX(md.numeric_value)::float8 as max_value
+            FROM monitoring_data md
+            JOIN experimental_units eu ON md.unit_id = eu.id
+            JOIN experimental_blocks eb ON eu.block_id = eb.id
+            JOIN monitoring_parameters mp ON md.parameter_id = mp.id
+            WHERE eb.project_id = $1
+            GROUP BY mp.name, mp.code, eb.block_code, eb.is_control
+            ORDER BY mp.code, eb.block_code
+            "#
+        )
+        .bind(project_id)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        let treatment_stats: Vec<TreatmentStats> = stats
+            .into_iter()
+            .map(|row| TreatmentStats {
+                parameter_name: row.parameter_name.unwrap_or_default(),
+                parameter_code: row.parameter_code.unwrap_or_default(),
+                block_code: row.block_code,
+                is_control: row.is_control,
+                n: row.data_count.unwrap_or(0) as usize,
+                mean: row.avg_value.unwrap_or(0.0),
+                std_dev: row.std_dev.unwrap_or(0.0),
+                min: row.min_value.unwrap_or(0.0),
+                max: row.max_value.unwrap_or(0.0),
+            })
+            .collect();
+
         Ok(MonitoringSummary { treatment_stats })
     }
 
-    fn build_analysis_prompt(
-        &self,
-        project: &Project,
-        summary: &MonitoringSummary,
-        analysis_type: &str,
-    ) -> String {
-        let stats_text = summary
-            .treatment_stats
+    fn build_analysis_prompt(
+        &self,
+        project: &Project,
+        summary: &MonitoringSummary,
+        analysis_type: &str,
+    ) -> String {
+        let stats_text = summary
+            .treatment_stats
+            .iter()
+            .map(|s| {
+                format!(
+                    "- {} ({}): Block {}{}, n={}, Mean={:.2}±{:.2}, Range=[{:.2}-{:.2}]",
+                    s.parameter_name,
+                    s.parameter_code,
+                    s.block_code,
+                    if s.is_control { " (CONTROL)" } else { "" },
+                    s.n,
+                    s.mean,
+                    s.std_dev,
+                    s.min,
+                    s.max
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        format!(
+            r#"You are an expert agricultural research scientist analyzing experiment data for Centra Biotech Indonesia R&D.
+
+PROJECT: {}
+TITLE: {}
+HYPOTHESIS: {}
+METHODOLOGY: {}
+CROP: {} ({})
+EXPERIMENT DESIGN: {:?}
+REPLICATIONS: {}
+
+DATA SUMMARY:
+{}
+
+ANALYSIS TYPE: {}
+
+Please provide:
+1. **Key Insights**: What do the data tell us? Are there significant patterns?
+2. **Statistical Interpretation**: Interpret the means, variability, and any comparisons
+3. **Treatment Effects**: Which treatments show promise compared to control?
+4. **Recommendations**: Practical recommendations for R&D team
+5. **Limitations**: Any data quality issues or limitations to consider
+6. **Next Steps**: Suggested next steps for the experiment
+
+Respond in JSON format with keys: insights, statistical_interpretation, recommendations, confidence_level (high/medium/low), limitations, next_steps"#,
+            project.code,
+            project.title,
+            project.hypothesis.as_deref().unwrap_or("Not specified"),
+            project.methodology.as_deref().unwrap_or("Not specified"),
+            project.crop_type.as_deref().unwrap_or("Not specified"),
+            project.crop_variety.as_deref().unwrap_or("Unknown variety"),
+            project.experiment_design,
+            project.replications.unwrap_or(3),
+            stats_text,
+            analysis_type
+        )
+    }
+
+    async fn call_openai(&self, prompt: &str) -> Result<AIResponse, AppError> {
+        let request = CreateChatCompletionRequestArgs::default()
+            .model(&self.model)
+            .messages([
+                ChatCompletionRequestMessage::System(
+                    ChatCompletionRequestSystemMessageArgs::default()
+                        .content("You are an expert agricultural data scientist specializing in biostimulant and fertilizer research. Provide analysis in clear, scientific language suitable for R&D reports.")
+                        .build()
+                        .map_err(|e| AppError::AIError(e.to_string()))?
+                ),
+                ChatCompletionRequestMessage::User(
+                    ChatCompletionRequestUserMessageArgs::default()
+                        .content(prompt)
+                        .build()
+                        .map_err(|e| AppError::AIError(e.to_string()))?
+                ),
+            ])
+            .temperature(0.3)
+            .max_tokens(2000_u32)
+            .build()
+            .map_err(|e| AppError::AIError(e.to_string()))?;
+
+        let response = self
+            .client
+            .chat()
+            .create(request)
+            .await
+            .map_err(|e| AppError::AIError(e.to_string()))?;
+
+        let content = response
+            .choices
+            .first()
+            .and_then(|c| c.message.content.clone())
+            .ok_or_else(|| AppError::AIError("No response from AI".to_string()))?;
+
+        // Parse JSON response
+        let parsed: AIResponse = serde_json::from_str(&content).unwrap_or_else(|_| AIResponse {
+            insights: content.clone(),
+            statistical_interpretation: String::new(),
+            recommendations: String::new(),
+            confidence_level: "medium".to_string(),
+            limitations: String::new(),
+            next_steps: String::new(),
+        });
+
+        Ok(parsed)
+    }
+
+    pub async fn generate_report_insights(
+        &self,
+        pool: &PgPool,
+        project_id: Uuid,
+    ) -> Result<String, AppError> {
+        let project = ProjectService::get_by_id(pool, project_id).await?;
+        let mut conn = pool.acquire().await.map_err(|e| AppError::Database(e.to_string()))?;
+        let summary = self.fetch_monitoring_summary(&mut conn, project_id).await?;
+
+        let prompt = format!(
+            r#"Generate a professional executive summary for the R&D experiment report:
+
+PROJECT: {} - {}
+STATUS: {:?}
+CROP: {} ({})
+
+DATA COLLECTED:
+{}
+
+Write a 2-3 paragraph executive summary suitable for management review, highlighting:
+1. Key findings
+2. Treatment efficacy
+3. Business implications
+4. Recommendations"#,
+            project.code,
+            project.title,
+            project.status,
+            project.crop_type.as_deref().unwrap_or("Unknown"),
+            project.crop_variety.as_deref().unwrap_or("Unknown"),
+            summary
+                .treatment_stats
+                .iter()
+                .take(10)
+                .map(|s| format!(
+                    "  {} ({}): Mean={:.2}",
+                    s.parameter_name, s.block_code, s.mean
+                ))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+
+        let response = self.call_openai(&prompt).await?;
+        Ok(response.insights)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonitoringSummary {
+    pub treatment_stats: Vec<TreatmentStats>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreatmentStats {
+    pub parameter_name: String,
+    pub parameter_code: String,
+    pub block_code: String,
+    pub is_control: bool,
+    pub n: usize,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AIResponse {
+    pub insights: String,
+    pub statistical_interpretation: String,
+    pub recommendations: String,
+    pub confidence_level: String,
+    pub limitations: String,
+    pub next_steps: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AIAnalysisResult {
+    pub analysis_type: String,
+    pub insights: String,
+    pub recommendations: String,
+    pub statistical_interpretation: String,
+    pub confidence_level: String,
+    pub limitations: String,
+    pub next_steps: String,
+}
+
+// ==============================================================================
+// COST-BENEFIT ANALYSIS
+// ==============================================================================
+
+pub struct CostBenefitAnalysis;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CostBenefitResult {
+    pub treatment_costs: Vec<TreatmentCost>,
+    pub yield_comparison: Vec<YieldComparison>,
+    pub roi_analysis: Vec<ROIAnalysis>,
+    pub break_even_analysis: BreakEvenAnalysis,
+    pub recommendation: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreatmentCost {
+    pub treatment_name: String,
+    pub formula_cost: Decimal,
+    pub application_cost: Decimal,
+    pub total_cost_per_ha: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct YieldComparison {
+    pub treatment_name: String,
+    pub yield_per_ha: f64,
+    pub yield_increase_vs_control: f64,
+    pub yield_increase_percent: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ROIAnalysis {
+    pub treatment_name: String,
+    pub additional_cost: Decimal,
+    pub additional_revenue: Decimal,
+    pub net_benefit: Decimal,
+    pub roi_percent: f64,
+    pub is_profitable: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoseResponseResult {
+    pub points: Vec<DosePoint>,
+    pub excluded_blocks: Vec<String>, // application rate not numeric or no data
+    pub cost_per_dose_unit: Option<f64>,
+    pub crop_price_per_kg: Decimal,
+    pub models: Vec<DoseResponseFit>,
+    pub best_model: Option<DoseResponseModel>, // highest adjusted R²
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DosePoint {
+    pub block_code: String,
+    pub dose: f64,
+    pub response: f64, // block mean
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BreakEvenAnalysis {
+    pub break_even_yield_increase: f64,
+    pub break_even_price: Decimal,
+    pub current_margin_of_safety: f64,
+}
+
+impl CostBenefitAnalysis {
+    pub async fn analyze(
+        conn: &mut PgConnection,
+        project_id: Uuid,
+        crop_price_per_kg: Decimal,
+    ) -> Result<CostBenefitResult, AppError> {
+        // Fetch formula costs by block
+        #[derive(sqlx::FromRow)]
+        struct FormulaCostRow {
+            block_code: String,
+            is_control: bool,
+            formula_name: Option<String>,
+            calculated_cost: Option<Decimal>,
+            application_rate: Option<String>,
+        }
+
+        let formula_costs: Vec<FormulaCostRow> = sqlx::query_as(
+            r#"
+            SELECT 
+                eb.block_code,
+                eb.is_control,
+                f.name as formula_name,
+                f.calculated_cost,
+                f.application_rate
+            FROM experimental_blocks eb
+            LEFT JOIN formulas f ON eb.formula_id = f.id
+            WHERE eb.project_id = $1
+            ORDER BY eb.block_code
+            "#
+        )
+        .bind(project_id)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        // Fetch yield data (assuming 'yield' parameter exists)
+        #[derive(sqlx::FromRow)]
+        struct YieldRow {
+            block_code: String,
+            is_control: bool,
+            avg_yield: Option<f64>,
+        }
+
+        let yields: Vec<YieldRow> = sqlx::query_as(
+            r#"
+            SELECT 
+                eb.block_code,
+                eb.is_control,
+                AVG(md.numeric_value)::float8 as avg_yield
+            FROM monitoring_data md
+            JOIN experimental_units eu ON md.unit_id = eu.id
+            JOIN experimental_blocks eb ON eu.block_id = eb.id
+            JOIN monitoring_parameters mp ON md.parameter_id = mp.id
+            WHERE eb.project_id = $1 
+            AND mp.parameter_type = 'yield'
+            GROUP BY eb.block_code, eb.is_control
+            "#
+        )
+        .bind(project_id)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        // Find control yield
+        let control_yield = yields
             .iter()
-            .map(|s| {
-                format!(
-                    "- {} ({}): Block {}{}, n={}, Mean={:.2}±{:.2}, Range=[{:.2}-{:.2}]",
-                    s.parameter_name,
-                    s.parameter_code,
-                    s.block_code,
-                    if s.is_control { " (CONTROL)" } else { "" },
-                    s.n,
-                    s.mean,
-                    s.std_dev,
-                    s.min,
-                    s.max
+            .find(|y| y.is_control)
+            .and_then(|y| y.avg_yield)
+            .unwrap_or(0.0);
+
+        // Calculate treatment costs
+        let treatment_costs: Vec<TreatmentCost> = formula_costs
+            .iter()
+            .map(|fc| {
+                Self::treatment_cost(
+                    fc.formula_name.clone().unwrap_or_else(|| fc.block_code.clone()),
+                    fc.calculated_cost.unwrap_or_default(),
                 )
             })
-            .collect::<Vec<_>>()
-            .join("\n");
+            .collect();
 
-        format!(
-            r#"You are an expert agricultural research scientist analyzing experiment data for Centra Biotech Indonesia R&D.
+        // Calculate yield comparisons
+        let yield_comparison: Vec<YieldComparison> = yields
+            .iter()
+            .map(|y| {
+                let yield_val = y.avg_yield.unwrap_or(0.0);
+                let increase = yield_val - control_yield;
+                let percent = if control_yield > 0.0 {
+                    (increase / control_yield) * 100.0
+                } else {
+                    0.0
+                };
+                YieldComparison {
+                    treatment_name: y.block_code.clone(),
+                    yield_per_ha: yield_val,
+                    yield_increase_vs_control: increase,
+                    yield_increase_percent: percent,
+                }
+            })
+            .collect();
 
-PROJECT: {}
-TITLE: {}
-HYPOTHESIS: {}
-METHODOLOGY: {}
-CROP: {} ({})
-EXPERIMENT DESIGN: {:?}
-REPLICATIONS: {}
+        // Calculate ROI
+        let control_cost = treatment_costs
+            .iter()
+            .find(|tc| formula_costs.iter().any(|fc| fc.block_code == tc.treatment_name && fc.is_control))
+            .map(|tc| tc.total_cost_per_ha)
+            .unwrap_or_default();
+
+        let roi_analysis: Vec<ROIAnalysis> = treatment_costs
+            .iter()
+            .zip(yield_comparison.iter())
+            .filter(|(_, yc)| yc.yield_increase_vs_control != 0.0)
+            .map(|(tc, yc)| Self::roi(tc, control_cost, yc.yield_increase_vs_control, crop_price_per_kg))
+            .collect();
+
+        // Simple break-even analysis
+        let avg_additional_cost: Decimal = if !roi_analysis.is_empty() {
+            roi_analysis.iter().map(|r| r.additional_cost).sum::<Decimal>() / Decimal::from(roi_analysis.len())
+        } else {
+            Decimal::ZERO
+        };
+
+        let break_even_yield = if crop_price_per_kg > Decimal::ZERO {
+            avg_additional_cost / crop_price_per_kg
+        } else {
+            Decimal::ZERO
+        };
+
+        let break_even_analysis = BreakEvenAnalysis {
+            break_even_yield_increase: break_even_yield.to_string().parse().unwrap_or(0.0),
+            break_even_price: avg_additional_cost,
+            current_margin_of_safety: 0.0, // Simplified
+        };
 
-DATA SUMMARY:
-{}
+        // Generate recommendation
+        let profitable_treatments: Vec<_> = roi_analysis
+            .iter()
+            .filter(|r| r.is_profitable)
+            .collect();
 
-ANALYSIS TYPE: {}
+        let recommendation = if profitable_treatments.is_empty() {
+            "No treatments showed positive ROI. Consider reviewing formulations or application rates.".to_string()
+        } else {
+            let best = profitable_treatments
+                .iter()
+                .max_by(|a, b| a.roi_percent.partial_cmp(&b.roi_percent).unwrap())
+                .unwrap();
+            format!(
+                "Recommended: {} with ROI of {:.1}% and net benefit of Rp {:.0}/ha",
+                best.treatment_name, best.roi_percent, best.net_benefit
+            )
+        };
 
-Please provide:
-1. **Key Insights**: What do the data tell us? Are there significant patterns?
-2. **Statistical Interpretation**: Interpret the means, variability, and any comparisons
-3. **Treatment Effects**: Which treatments show promise compared to control?
-4. **Recommendations**: Practical recommendations for R&D team
-5. **Limitations**: Any data quality issues or limitations to consider
-6. **Next Steps**: Suggested next steps for the experiment
+        Ok(CostBenefitResult {
+            treatment_costs,
+            yield_comparison,
+            roi_analysis,
+            break_even_analysis,
+            recommendation,
+        })
+    }
 
-Respond in JSON format with keys: insights, statistical_interpretation, recommendations, confidence_level (high/medium/low), limitations, next_steps"#,
-            project.code,
-            project.title,
-            project.hypothesis.as_deref().unwrap_or("Not specified"),
-            project.methodology.as_deref().unwrap_or("Not specified"),
-            project.crop_type.as_deref().unwrap_or("Not specified"),
-            project.crop_variety.as_deref().unwrap_or("Unknown variety"),
-            project.experiment_design,
-            project.replications.unwrap_or(3),
-            stats_text,
-            analysis_type
-        )
+    fn roi(
+        treatment_cost: &TreatmentCost,
+        control_cost: Decimal,
+        yield_increase: f64,
+        crop_price_per_kg: Decimal,
+    ) -> ROIAnalysis {
+        let additional_cost = treatment_cost.total_cost_per_ha - control_cost;
+        let additional_revenue = crop_price_per_kg * Decimal::try_from(yield_increase).unwrap_or_default();
+        let net_benefit = additional_revenue - additional_cost;
+        let roi = if additional_cost > Decimal::ZERO {
+            ((net_benefit / additional_cost) * Decimal::from(100)).to_string().parse::<f64>().unwrap_or(0.0)
+        } else {
+            0.0
+        };
+        ROIAnalysis {
+            treatment_name: treatment_cost.treatment_name.clone(),
+            additional_cost,
+            additional_revenue,
+            net_benefit,
+            roi_percent: roi,
+            is_profitable: net_benefit > Decimal::ZERO,
+        }
     }
 
-    async fn call_openai(&self, prompt: &str) -> Result<AIResponse, AppError> {
-        let request = CreateChatCompletionRequestArgs::default()
-            .model(&self.model)
-            .messages([
-                ChatCompletionRequestMessage::System(
-                    ChatCompletionRequestSystemMessageArgs::default()
-                        .content("You are an expert agricultural data scientist specializing in biostimulant and fertilizer research. Provide analysis in clear, scientific language suitable for R&D reports.")
-                        .build()
-                        .map_err(|e| AppError::AIError(e.to_string()))?
-                ),
-                ChatCompletionRequestMessage::User(
-                    ChatCompletionRequestUserMessageArgs::default()
-                        .content(prompt)
-                        .build()
-                        .map_err(|e| AppError::AIError(e.to_string()))?
-                ),
-            ])
-            .temperature(0.3)
-            .max_tokens(2000_u32)
-            .build()
-            .map_err(|e| AppError::AIError(e.to_string()))?;
+    fn treatment_cost(treatment_name: String, formula_cost: Decimal) -> TreatmentCost {
+        // Assuming application cost is 20% of formula cost
+        let application_cost = formula_cost * Decimal::from_str_exact("0.2").unwrap_or_default();
+        TreatmentCost {
+            treatment_name,
+            formula_cost,
+            application_cost,
+            total_cost_per_ha: formula_cost + application_cost,
+        }
+    }
 
-        let response = self
-            .client
-            .chat()
-            .create(request)
-            .await
-            .map_err(|e| AppError::AIError(e.to_string()))?;
+    /// Dose-response models over the numeric application rates of a
+    /// project's blocks. Control blocks without a formula count as dose 0.
+    /// The cost of one dose unit is the mean of each dosed block's total
+    /// cost per ha divided by its dose; with the crop price it gives the
+    /// economic optimum of every model.
+    pub async fn dose_response(
+        conn: &mut PgConnection,
+        project_id: Uuid,
+        parameter_id: Option<Uuid>,
+        session_id: Option<Uuid>,
+        crop_price_per_kg: Decimal,
+    ) -> Result<DoseResponseResult, AppError> {
+        #[derive(sqlx::FromRow)]
+        struct DoseRow {
+            block_code: String,
+            is_control: bool,
+            application_rate: Option<String>,
+            calculated_cost: Option<Decimal>,
+            response: Option<f64>,
+        }
 
-        let content = response
-            .choices
-            .first()
-            .and_then(|c| c.message.content.clone())
-            .ok_or_else(|| AppError::AIError("No response from AI".to_string()))?;
+        // Without a parameter the yield parameter is used, as in `analyze`
+        let rows: Vec<DoseRow> = sqlx::query_as(
+            r#"
+            SELECT
+                eb.block_code,
+                eb.is_control,
+                f.application_rate,
+                f.calculated_cost,
+                AVG(md.numeric_value)::float8 as response
+            FROM experimental_blocks eb
+            LEFT JOIN formulas f ON eb.formula_id = f.id
+            JOIN experimental_units eu ON eu.block_id = eb.id
+            JOIN monitoring_data md ON md.unit_id = eu.id
+            JOIN monitoring_parameters mp ON md.parameter_id = mp.id
+            WHERE eb.project_id = $1
+            AND md.numeric_value IS NOT NULL
+            AND (md.parameter_id = $2 OR ($2 IS NULL AND mp.parameter_type = 'yield'))
+            AND ($3::UUID IS NULL OR md.session_id = $3)
+            AND COALESCE(eu.is_active, true)
+            AND eu.excluded_reason IS NULL
+            AND NOT COALESCE(md.is_outlier, false)
+            GROUP BY eb.id, eb.block_code, eb.is_control, f.application_rate, f.calculated_cost
+            ORDER BY eb.block_code
+            "#
+        )
+        .bind(project_id)
+        .bind(parameter_id)
+        .bind(session_id)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
 
-        // Parse JSON response
-        let parsed: AIResponse = serde_json::from_str(&content).unwrap_or_else(|_| AIResponse {
-            insights: content.clone(),
-            statistical_interpretation: String::new(),
-            recommendations: String::new(),
-            confidence_level: "medium".to_string(),
-            limitations: String::new(),
-            next_steps: String::new(),
-        });
+        let mut points = Vec::new();
+        let mut excluded_blocks = Vec::new();
+        let mut unit_costs = Vec::new();
+        for row in rows {
+            let dose = match (&row.application_rate, row.is_control) {
+                (Some(rate), _) => StatisticalAnalysis::parse_dose(rate),
+                (None, true) => Some(0.0),
+                (None, false) => None,
+            };
+            let (Some(dose), Some(response)) = (dose, row.response) else {
+                excluded_blocks.push(row.block_code);
+                continue;
+            };
+            if let (true, Some(cost)) = (dose > 0.0, row.calculated_cost) {
+                let total = Self::treatment_cost(row.block_code.clone(), cost).total_cost_per_ha;
+                if let Ok(total) = total.to_string().parse::<f64>() {
+                    unit_costs.push(total / dose);
+                }
+            }
+            points.push(DosePoint {
+                block_code: row.block_code,
+                dose,
+                response,
+            });
+        }
 
-        Ok(parsed)
+        let cost_per_dose_unit =
+            (!unit_costs.is_empty()).then(|| unit_costs.iter().sum::<f64>() / unit_costs.len() as f64);
+        let price: f64 = crop_price_per_kg.to_string().parse().unwrap_or(0.0);
+
+        let xy: Vec<(f64, f64)> = points.iter().map(|p| (p.dose, p.response)).collect();
+        let range = xy.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| (lo.min(p.0), hi.max(p.0)));
+        let models: Vec<DoseResponseFit> = [
+            DoseResponseModel::Linear,
+            DoseResponseModel::Quadratic,
+            DoseResponseModel::LinearPlateau,
+        ]
+        .iter()
+        .filter_map(|model| StatisticalAnalysis::dose_response(*model, &xy))
+        .map(|mut fit| {
+            if let (Some(cost), true) = (cost_per_dose_unit, price > 0.0) {
+                StatisticalAnalysis::economic_optimum(&mut fit, cost / price, range);
+            }
+            fit
+        })
+        .collect();
+
+        let best_model = models
+            .iter()
+            .max_by(|a, b| a.adjusted_r_squared.partial_cmp(&b.adjusted_r_squared).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|fit| fit.model);
+
+        Ok(DoseResponseResult {
+            points,
+            excluded_blocks,
+            cost_per_dose_unit,
+            crop_price_per_kg,
+            models,
+            best_model,
+        })
     }
+}
 
-    pub async fn generate_report_insights(
-        &self,
-        pool: &PgPool,
-        project_id: Uuid,
-    ) -> Result<String, AppError> {
-        let project = ProjectService::get_by_id(pool, project_id).await?;
-        let summary = self.fetch_monitoring_summary(pool, project_id).await?;
+// ==============================================================================
+// DECISION SUPPORT (TOPSIS / AHP)
+// ==============================================================================
 
-        let prompt = format!(
-            r#"Generate a professional executive summary for the R&D experiment report:
+pub struct DecisionSupport;
 
-PROJECT: {} - {}
-STATUS: {:?}
-CROP: {} ({})
+/// Saaty's random consistency index for 1..=10 criteria
+const AHP_RANDOM_INDEX: [f64; 10] = [0.0, 0.0, 0.58, 0.90, 1.12, 1.24, 1.32, 1.41, 1.45, 1.49];
 
-DATA COLLECTED:
-{}
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecisionMethod {
+    #[default]
+    Topsis,
+    Ahp,
+}
 
-Write a 2-3 paragraph executive summary suitable for management review, highlighting:
-1. Key findings
-2. Treatment efficacy
-3. Business implications
-4. Recommendations"#,
-            project.code,
-            project.title,
-            project.status,
-            project.crop_type.as_deref().unwrap_or("Unknown"),
-            project.crop_variety.as_deref().unwrap_or("Unknown"),
-            summary
-                .treatment_stats
-                .iter()
-                .take(10)
-                .map(|s| format!(
-                    "  {} ({}): Mean={:.2}",
-                    s.parameter_name, s.block_code, s.mean
-                ))
-                .collect::<Vec<_>>()
-                .join("\n")
-        );
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecisionCriterion {
+    YieldGain,   // % over the control, mean across sites
+    Roi,         // % from the cost-benefit ROI, mean across sites
+    QcPassRate,  // % of completed lab tests passed
+    Consistency, // % of sites where the formula out-yielded the control
+    FormulaCost, // total cost per ha, mean across sites
+}
 
-        let response = self.call_openai(&prompt).await?;
-        Ok(response.insights)
+impl DecisionCriterion {
+    pub const ALL: [DecisionCriterion; 5] = [
+        DecisionCriterion::YieldGain,
+        DecisionCriterion::Roi,
+        DecisionCriterion::QcPassRate,
+        DecisionCriterion::Consistency,
+        DecisionCriterion::FormulaCost,
+    ];
+
+    /// Higher values are better for every criterion except cost
+    pub fn is_benefit(self) -> bool {
+        self != DecisionCriterion::FormulaCost
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DecisionCriterion::YieldGain => "yield gain",
+            DecisionCriterion::Roi => "ROI",
+            DecisionCriterion::QcPassRate => "QC pass rate",
+            DecisionCriterion::Consistency => "consistency across sites",
+            DecisionCriterion::FormulaCost => "formula cost",
+        }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MonitoringSummary {
-    pub treatment_stats: Vec<TreatmentStats>,
+pub struct DecisionAlternative {
+    pub formula_code: String,
+    pub formula_name: String,
+    pub sites: usize, // projects where the formula has yield data against a control
+    pub yield_gain_percent: Option<f64>,
+    pub roi_percent: Option<f64>,
+    pub qc_pass_rate: Option<f64>,
+    pub consistency_percent: Option<f64>,
+    pub cost_per_ha: Option<f64>,
+}
+
+impl DecisionAlternative {
+    pub fn value(&self, criterion: DecisionCriterion) -> Option<f64> {
+        match criterion {
+            DecisionCriterion::YieldGain => self.yield_gain_percent,
+            DecisionCriterion::Roi => self.roi_percent,
+            DecisionCriterion::QcPassRate => self.qc_pass_rate,
+            DecisionCriterion::Consistency => self.consistency_percent,
+            DecisionCriterion::FormulaCost => self.cost_per_ha,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TreatmentStats {
-    pub parameter_name: String,
-    pub parameter_code: String,
-    pub block_code: String,
-    pub is_control: bool,
-    pub n: usize,
-    pub mean: f64,
-    pub std_dev: f64,
-    pub min: f64,
-    pub max: f64,
+pub struct AhpConsistency {
+    pub lambda_max: f64,
+    pub consistency_index: f64,
+    pub consistency_ratio: f64,
+    pub is_acceptable: bool, // CR ≤ 0.10
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AIResponse {
-    pub insights: String,
-    pub statistical_interpretation: String,
-    pub recommendations: String,
-    pub confidence_level: String,
-    pub limitations: String,
-    pub next_steps: String,
+pub struct CriteriaWeights {
+    pub criteria: Vec<DecisionCriterion>,
+    pub weights: Vec<f64>, // normalized to sum to 1, aligned with criteria
+    pub ahp_consistency: Option<AhpConsistency>, // when derived from pairwise comparisons
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AIAnalysisResult {
-    pub analysis_type: String,
-    pub insights: String,
-    pub recommendations: String,
-    pub statistical_interpretation: String,
-    pub confidence_level: String,
-    pub limitations: String,
-    pub next_steps: String,
+pub struct RankedAlternative {
+    pub rank: usize,
+    pub formula_code: String,
+    pub formula_name: String,
+    pub score: f64,            // TOPSIS closeness or AHP priority, 0-1
+    pub values: Vec<f64>,      // criterion values used, aligned with criteria
+    pub imputed: Vec<DecisionCriterion>, // missing values replaced by the worst observed one
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SensitivityScenario {
+    pub weight: f64,
+    pub top_formula: String,
+    pub ranks: Vec<usize>, // aligned with the base ranking
+    pub rank_changes: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeightSensitivity {
+    pub criterion: DecisionCriterion,
+    pub base_weight: f64,
+    pub stable_weight_min: Option<f64>, // the top formula keeps first place between these weights;
+    pub stable_weight_max: Option<f64>, // None when it is not first at the base weight's grid point
+    pub scenarios: Vec<SensitivityScenario>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecisionAnalysis {
+    pub method: DecisionMethod,
+    pub weights: CriteriaWeights,
+    pub ranking: Vec<RankedAlternative>,
+    pub sensitivity: Vec<WeightSensitivity>,
+    pub recommendation: String,
 }
 
-// ==============================================================================
-// COST-BENEFIT ANALYSIS
-// ==============================================================================
+impl DecisionSupport {
+    /// Normalize user weights for the chosen criteria; equal weights when none are given
+    pub fn direct_weights(
+        criteria: &[DecisionCriterion],
+        weights: Option<&[f64]>,
+    ) -> Result<CriteriaWeights, AppError> {
+        let raw = match weights {
+            Some(w) => w.to_vec(),
+            None => vec![1.0; criteria.len()],
+        };
+        if raw.len() != criteria.len() || raw.iter().any(|w| !w.is_finite() || *w < 0.0) {
+            return Err(AppError::Validation(
+                "Weights must be non-negative and given for every criterion".to_string(),
+            ));
+        }
+        let total: f64 = raw.iter().sum();
+        if total <= 0.0 {
+            return Err(AppError::Validation("At least one weight must be positive".to_string()));
+        }
+
+        Ok(CriteriaWeights {
+            criteria: criteria.to_vec(),
+            weights: raw.iter().map(|w| w / total).collect(),
+            ahp_consistency: None,
+        })
+    }
+
+    /// AHP weights from a reciprocal pairwise comparison matrix (Saaty 1-9
+    /// scale): the principal eigenvector by power iteration, with the
+    /// consistency ratio CR = CI / RI
+    pub fn ahp_weights(
+        criteria: &[DecisionCriterion],
+        pairwise: &[Vec<f64>],
+    ) -> Result<CriteriaWeights, AppError> {
+        let n = criteria.len();
+        if pairwise.len() != n || pairwise.iter().any(|row| row.len() != n) {
+            return Err(AppError::Validation(
+                "The pairwise matrix must be square with one row per criterion".to_string(),
+            ));
+        }
+        for (i, row) in pairwise.iter().enumerate() {
+            for (j, &a) in row.iter().enumerate() {
+                if !a.is_finite() || a <= 0.0 || (a * pairwise[j][i] - 1.0).abs() > 1e-3 {
+                    return Err(AppError::Validation(
+                        "Pairwise comparisons must be positive and reciprocal (a_ji = 1 / a_ij)".to_string(),
+                    ));
+                }
+            }
+        }
+
+        let mut weights = vec![1.0 / n as f64; n];
+        for _ in 0..1000 {
+            let next: Vec<f64> = (0..n).map(|i| (0..n).map(|j| pairwise[i][j] * weights[j]).sum()).collect();
+            let total: f64 = next.iter().sum();
+            let next: Vec<f64> = next.iter().map(|v| v / total).collect();
+            let change: f64 = next.iter().zip(&weights).map(|(a, b)| (a - b).abs()).sum();
+            weights = next;
+            if change < 1e-12 {
+                break;
+            }
+        }
+
+        let lambda_max = (0..n)
+            .map(|i| (0..n).map(|j| pairwise[i][j] * weights[j]).sum::<f64>() / weights[i])
+            .sum::<f64>()
+            / n as f64;
+        let consistency_index = if n > 2 { (lambda_max - n as f64) / (n as f64 - 1.0) } else { 0.0 };
+        let random_index = AHP_RANDOM_INDEX.get(n - 1).copied().unwrap_or(1.49);
+        let consistency_ratio = if random_index > 0.0 { consistency_index / random_index } else { 0.0 };
+
+        Ok(CriteriaWeights {
+            criteria: criteria.to_vec(),
+            weights,
+            ahp_consistency: Some(AhpConsistency {
+                lambda_max,
+                consistency_index,
+                consistency_ratio,
+                is_acceptable: consistency_ratio <= 0.10,
+            }),
+        })
+    }
+
+    /// Scores of each alternative (rows) over the criteria (columns)
+    pub fn scores(method: DecisionMethod, matrix: &[Vec<f64>], weights: &[f64], benefit: &[bool]) -> Vec<f64> {
+        match method {
+            DecisionMethod::Topsis => Self::topsis(matrix, weights, benefit),
+            DecisionMethod::Ahp => Self::ahp_ratings(matrix, weights, benefit),
+        }
+    }
+
+    /// TOPSIS: vector-normalized, weighted distances to the ideal and
+    /// anti-ideal alternatives; the score is the relative closeness
+    fn topsis(matrix: &[Vec<f64>], weights: &[f64], benefit: &[bool]) -> Vec<f64> {
+        let k = weights.len();
+        let norms: Vec<f64> = (0..k).map(|j| matrix.iter().map(|row| row[j].powi(2)).sum::<f64>().sqrt()).collect();
+        let weighted: Vec<Vec<f64>> = matrix
+            .iter()
+            .map(|row| {
+                (0..k)
+                    .map(|j| if norms[j] > 0.0 { weights[j] * row[j] / norms[j] } else { 0.0 })
+                    .collect()
+            })
+            .collect();
+
+        let column = |j: usize| weighted.iter().map(move |row| row[j]);
+        let max: Vec<f64> = (0..k).map(|j| column(j).fold(f64::NEG_INFINITY, f64::max)).collect();
+        let min: Vec<f64> = (0..k).map(|j| column(j).fold(f64::INFINITY, f64::min)).collect();
+        let (ideal, anti_ideal): (Vec<f64>, Vec<f64>) =
+            (0..k).map(|j| if benefit[j] { (max[j], min[j]) } else { (min[j], max[j]) }).unzip();
+
+        weighted
+            .iter()
+            .map(|row| {
+                let to_ideal = row.iter().zip(&ideal).map(|(v, b)| (v - b).powi(2)).sum::<f64>().sqrt();
+                let to_anti = row.iter().zip(&anti_ideal).map(|(v, w)| (v - w).powi(2)).sum::<f64>().sqrt();
+                if to_ideal + to_anti > 0.0 {
+                    to_anti / (to_ideal + to_anti)
+                } else {
+                    0.5
+                }
+            })
+            .collect()
+    }
+
+    /// AHP in ratings mode: each alternative is rated 0-1 on every criterion
+    /// by min-max scaling (so negative gains and ROI are allowed) and the
+    /// ratings are combined with the criteria weights
+    fn ahp_ratings(matrix: &[Vec<f64>], weights: &[f64], benefit: &[bool]) -> Vec<f64> {
+        let k = weights.len();
+        let ranges: Vec<(f64, f64)> = (0..k)
+            .map(|j| {
+                matrix
+                    .iter()
+                    .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), row| (lo.min(row[j]), hi.max(row[j])))
+            })
+            .collect();
+
+        matrix
+            .iter()
+            .map(|row| {
+                (0..k)
+                    .map(|j| {
+                        let (lo, hi) = ranges[j];
+                        let rating = if hi > lo {
+                            if benefit[j] { (row[j] - lo) / (hi - lo) } else { (hi - row[j]) / (hi - lo) }
+                        } else {
+                            1.0
+                        };
+                        weights[j] * rating
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// 1-based ranks, best score first
+    fn ranks(scores: &[f64]) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..scores.len()).collect();
+        order.sort_by(|a, b| scores[*b].partial_cmp(&scores[*a]).unwrap_or(std::cmp::Ordering::Equal));
+        let mut ranks = vec![0; scores.len()];
+        for (position, index) in order.into_iter().enumerate() {
+            ranks[index] = position + 1;
+        }
+        ranks
+    }
+
+    /// Weights with one criterion set to `weight` and the others rescaled
+    /// in proportion so they still sum to 1
+    fn shifted_weights(base: &[f64], criterion: usize, weight: f64) -> Vec<f64> {
+        let rest = 1.0 - base[criterion];
+        base.iter()
+            .enumerate()
+            .map(|(j, w)| {
+                if j == criterion {
+                    weight
+                } else if rest > 0.0 {
+                    w * (1.0 - weight) / rest
+                } else {
+                    (1.0 - weight) / (base.len() - 1) as f64
+                }
+            })
+            .collect()
+    }
 
-pub struct CostBenefitAnalysis;
+    pub fn analyze(
+        alternatives: &[DecisionAlternative],
+        weights: CriteriaWeights,
+        method: DecisionMethod,
+    ) -> DecisionAnalysis {
+        let criteria = &weights.criteria;
+        let benefit: Vec<bool> = criteria.iter().map(|c| c.is_benefit()).collect();
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CostBenefitResult {
-    pub treatment_costs: Vec<TreatmentCost>,
-    pub yield_comparison: Vec<YieldComparison>,
-    pub roi_analysis: Vec<ROIAnalysis>,
-    pub break_even_analysis: BreakEvenAnalysis,
-    pub recommendation: String,
-}
+        // Missing values take the worst observed value of the criterion
+        let worst: Vec<f64> = criteria
+            .iter()
+            .map(|c| {
+                let observed = alternatives.iter().filter_map(|a| a.value(*c));
+                if c.is_benefit() {
+                    observed.fold(f64::INFINITY, f64::min)
+                } else {
+                    observed.fold(f64::NEG_INFINITY, f64::max)
+                }
+            })
+            .map(|v| if v.is_finite() { v } else { 0.0 })
+            .collect();
+        let matrix: Vec<Vec<f64>> = alternatives
+            .iter()
+            .map(|a| criteria.iter().zip(&worst).map(|(c, w)| a.value(*c).unwrap_or(*w)).collect())
+            .collect();
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TreatmentCost {
-    pub treatment_name: String,
-    pub formula_cost: Decimal,
-    pub application_cost: Decimal,
-    pub total_cost_per_ha: Decimal,
-}
+        let scores = Self::scores(method, &matrix, &weights.weights, &benefit);
+        let ranks = Self::ranks(&scores);
+        let top = ranks.iter().position(|r| *r == 1);
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct YieldComparison {
-    pub treatment_name: String,
-    pub yield_per_ha: f64,
-    pub yield_increase_vs_control: f64,
-    pub yield_increase_percent: f64,
-}
+        let mut ranking: Vec<RankedAlternative> = alternatives
+            .iter()
+            .enumerate()
+            .map(|(i, a)| RankedAlternative {
+                rank: ranks[i],
+                formula_code: a.formula_code.clone(),
+                formula_name: a.formula_name.clone(),
+                score: scores[i],
+                values: matrix[i].clone(),
+                imputed: criteria.iter().filter(|c| a.value(**c).is_none()).copied().collect(),
+            })
+            .collect();
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ROIAnalysis {
-    pub treatment_name: String,
-    pub additional_cost: Decimal,
-    pub additional_revenue: Decimal,
-    pub net_benefit: Decimal,
-    pub roi_percent: f64,
-    pub is_profitable: bool,
-}
+        // One-at-a-time sensitivity: ±25% and ±50% around each weight, plus
+        // the weight range over which the top formula stays first
+        let sensitivity: Vec<WeightSensitivity> = if criteria.len() < 2 || alternatives.len() < 2 {
+            Vec::new()
+        } else {
+            criteria
+                .iter()
+                .enumerate()
+                .map(|(j, criterion)| {
+                    let base_weight = weights.weights[j];
+                    let rerank = |w: f64| {
+                        let shifted = Self::shifted_weights(&weights.weights, j, w);
+                        Self::ranks(&Self::scores(method, &matrix, &shifted, &benefit))
+                    };
+
+                    let scenarios = [0.5, 0.75, 1.25, 1.5]
+                        .iter()
+                        .map(|factor| (base_weight * factor).min(1.0))
+                        .map(|w| {
+                            let new_ranks = rerank(w);
+                            let first = new_ranks.iter().position(|r| *r == 1).unwrap_or(0);
+                            SensitivityScenario {
+                                weight: w,
+                                top_formula: alternatives[first].formula_code.clone(),
+                                rank_changes: new_ranks.iter().zip(&ranks).filter(|(a, b)| a != b).count(),
+                                ranks: new_ranks,
+                            }
+                        })
+                        .collect();
+
+                    let grid: Vec<f64> = (0..=100).map(|step| step as f64 / 100.0).collect();
+                    let keeps_top: Vec<bool> = grid.iter().map(|w| top.map(|t| rerank(*w)[t] == 1).unwrap_or(false)).collect();
+                    let start = ((base_weight * 100.0).round() as usize).min(100);
+                    let (stable_weight_min, stable_weight_max) = if keeps_top[start] {
+                        let (mut lo, mut hi) = (start, start);
+                        while lo > 0 && keeps_top[lo - 1] {
+                            lo -= 1;
+                        }
+                        while hi < 100 && keeps_top[hi + 1] {
+                            hi += 1;
+                        }
+                        (Some(grid[lo]), Some(grid[hi]))
+                    } else {
+                        (None, None)
+                    };
+
+                    WeightSensitivity {
+                        criterion: *criterion,
+                        base_weight,
+                        stable_weight_min,
+                        stable_weight_max,
+                        scenarios,
+                    }
+                })
+                .collect()
+        };
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BreakEvenAnalysis {
-    pub break_even_yield_increase: f64,
-    pub break_even_price: Decimal,
-    pub current_margin_of_safety: f64,
-}
+        ranking.sort_by_key(|r| r.rank);
+
+        let recommendation = match ranking.first() {
+            None => "No formula has yield data against a control in the selected projects.".to_string(),
+            Some(best) => {
+                let fragile: Vec<String> = sensitivity
+                    .iter()
+                    .filter(|s| s.scenarios.iter().any(|sc| sc.top_formula != best.formula_code))
+                    .map(|s| s.criterion.label().to_string())
+                    .collect();
+                if fragile.is_empty() {
+                    format!(
+                        "Scale up {} ({}): ranked first with a score of {:.3}, and stays first with any weight changed by up to ±50%",
+                        best.formula_name, best.formula_code, best.score
+                    )
+                } else {
+                    format!(
+                        "{} ({}) ranks first with a score of {:.3}, but loses first place when the weight of {} changes by up to ±50%",
+                        best.formula_name,
+                        best.formula_code,
+                        best.score,
+                        fragile.join(", ")
+                    )
+                }
+            }
+        };
 
-impl CostBenefitAnalysis {
-    pub async fn analyze(
-        pool: &PgPool,
-        project_id: Uuid,
+        DecisionAnalysis {
+            method,
+            weights,
+            ranking,
+            sensitivity,
+            recommendation,
+        }
+    }
+
+    /// Criterion values of every formula tested in the given projects. Each
+    /// project is a site: yield gain and ROI are computed against that
+    /// project's control blocks and averaged across sites.
+    pub async fn fetch_alternatives(
+        conn: &mut PgConnection,
+        project_ids: &[Uuid],
         crop_price_per_kg: Decimal,
-    ) -> Result<CostBenefitResult, AppError> {
-        // Fetch formula costs by block
+    ) -> Result<Vec<DecisionAlternative>, AppError> {
         #[derive(sqlx::FromRow)]
-        struct FormulaCostRow {
-            block_code: String,
+        struct SiteRow {
+            project_id: Uuid,
             is_control: bool,
+            formula_code: Option<String>,
             formula_name: Option<String>,
             calculated_cost: Option<Decimal>,
-            application_rate: Option<String>,
+            avg_yield: Option<f64>,
         }
 
-        let formula_costs: Vec<FormulaCostRow> = sqlx::query_as(
+        let rows: Vec<SiteRow> = sqlx::query_as(
             r#"
-            SELECT 
-                eb.block_code,
-                eb.is_control,
-                f.name as formula_name,
+            SELECT
+                eb.project_id,
+                COALESCE(eb.is_control, false) AS is_control,
+                f.code AS formula_code,
+                f.name AS formula_name,
                 f.calculated_cost,
-                f.application_rate
+                AVG(md.numeric_value)::float8 AS avg_yield
             FROM experimental_blocks eb
             LEFT JOIN formulas f ON eb.formula_id = f.id
-            WHERE eb.project_id = $1
-            ORDER BY eb.block_code
+            LEFT JOIN experimental_units eu ON eu.block_id = eb.id
+                AND COALESCE(eu.is_active, true) AND eu.excluded_reason IS NULL
+            LEFT JOIN monitoring_data md ON md.unit_id = eu.id
+                AND NOT COALESCE(md.is_outlier, false)
+                AND md.parameter_id IN (SELECT id FROM monitoring_parameters WHERE parameter_type = 'yield')
+            WHERE eb.project_id = ANY($1)
+            GROUP BY eb.project_id, COALESCE(eb.is_control, false), f.code, f.name, f.calculated_cost
             "#
         )
-        .bind(project_id)
-        .fetch_all(pool)
+        .bind(project_ids)
+        .fetch_all(&mut *conn)
         .await
         .map_err(|e| AppError::Database(e.to_string()))?;
 
-        // Fetch yield data (assuming 'yield' parameter exists)
         #[derive(sqlx::FromRow)]
-        struct YieldRow {
-            block_code: String,
-            is_control: bool,
-            avg_yield: Option<f64>,
+        struct QcRow {
+            code: String,
+            passed: i64,
+            tested: i64,
         }
 
-        let yields: Vec<YieldRow> = sqlx::query_as(
+        let qc: Vec<QcRow> = sqlx::query_as(
             r#"
-            SELECT 
-                eb.block_code,
-                eb.is_control,
-                AVG(md.numeric_value)::float8 as avg_yield
-            FROM monitoring_data md
-            JOIN experimental_units eu ON md.unit_id = eu.id
-            JOIN experimental_blocks eb ON eu.block_id = eb.id
-            JOIN monitoring_parameters mp ON md.parameter_id = mp.id
-            WHERE eb.project_id = $1 
-            AND mp.parameter_type = 'yield'
-            GROUP BY eb.block_code, eb.is_control
+            SELECT f.code, COUNT(*) FILTER (WHERE lt.is_passed) AS passed, COUNT(lt.is_passed) AS tested
+            FROM lab_tests lt
+            JOIN formulas f ON lt.formula_id = f.id
+            WHERE f.project_id = ANY($1)
+            GROUP BY f.code
             "#
         )
-        .bind(project_id)
-        .fetch_all(pool)
+        .bind(project_ids)
+        .fetch_all(&mut *conn)
         .await
         .map_err(|e| AppError::Database(e.to_string()))?;
 
-        // Find control yield
-        let control_yield = yields
-            .iter()
-            .find(|y| y.is_control)
-            .and_then(|y| y.avg_yield)
-            .unwrap_or(0.0);
+        #[derive(Default)]
+        struct FormulaSites {
+            name: String,
+            gains: Vec<f64>,
+            rois: Vec<f64>,
+            costs: Vec<f64>,
+        }
 
-        // Calculate treatment costs
-        let treatment_costs: Vec<TreatmentCost> = formula_costs
-            .iter()
-            .map(|fc| {
-                let formula_cost = fc.calculated_cost.unwrap_or_default();
-                // Assuming application cost is 20% of formula cost
-                let application_cost = formula_cost * Decimal::from_str_exact("0.2").unwrap_or_default();
-                TreatmentCost {
-                    treatment_name: fc.formula_name.clone().unwrap_or_else(|| fc.block_code.clone()),
-                    formula_cost,
-                    application_cost,
-                    total_cost_per_ha: formula_cost + application_cost,
-                }
-            })
-            .collect();
+        let mut by_formula: Vec<(String, FormulaSites)> = Vec::new();
+        for project_id in project_ids {
+            let site: Vec<&SiteRow> = rows.iter().filter(|r| r.project_id == *project_id).collect();
+            let controls: Vec<&SiteRow> = site.iter().copied().filter(|r| r.is_control).collect();
+            let control_yields: Vec<f64> = controls.iter().filter_map(|r| r.avg_yield).collect();
+            if control_yields.is_empty() {
+                continue;
+            }
+            let control_yield = control_yields.iter().sum::<f64>() / control_yields.len() as f64;
+            let control_cost = controls
+                .iter()
+                .find_map(|r| r.calculated_cost)
+                .map(|cost| CostBenefitAnalysis::treatment_cost(String::new(), cost).total_cost_per_ha)
+                .unwrap_or_default();
 
-        // Calculate yield comparisons
-        let yield_comparison: Vec<YieldComparison> = yields
-            .iter()
-            .map(|y| {
-                let yield_val = y.avg_yield.unwrap_or(0.0);
-                let increase = yield_val - control_yield;
-                let percent = if control_yield > 0.0 {
-                    (increase / control_yield) * 100.0
-                } else {
-                    0.0
+            for row in site.iter().filter(|r| !r.is_control) {
+                let (Some(code), Some(avg_yield)) = (&row.formula_code, row.avg_yield) else {
+                    continue;
                 };
-                YieldComparison {
-                    treatment_name: y.block_code.clone(),
-                    yield_per_ha: yield_val,
-                    yield_increase_vs_control: increase,
-                    yield_increase_percent: percent,
-                }
-            })
-            .collect();
-
-        // Calculate ROI
-        let control_cost = treatment_costs
-            .iter()
-            .find(|tc| formula_costs.iter().any(|fc| fc.block_code == tc.treatment_name && fc.is_control))
-            .map(|tc| tc.total_cost_per_ha)
-            .unwrap_or_default();
-
-        let roi_analysis: Vec<ROIAnalysis> = treatment_costs
-            .iter()
-            .zip(yield_comparison.iter())
-            .filter(|(_, yc)| yc.yield_increase_vs_control != 0.0)
-            .map(|(tc, yc)| {
-                let additional_cost = tc.total_cost_per_ha - control_cost;
-                let additional_revenue = crop_price_per_kg * Decimal::try_from(yc.yield_increase_vs_control).unwrap_or_default();
-                let net_benefit = additional_revenue - additional_cost;
-                let roi = if additional_cost > Decimal::ZERO {
-                    ((net_benefit / additional_cost) * Decimal::from(100)).to_string().parse::<f64>().unwrap_or(0.0)
-                } else {
-                    0.0
+                let name = row.formula_name.clone().unwrap_or_else(|| code.clone());
+                let cost = CostBenefitAnalysis::treatment_cost(name.clone(), row.calculated_cost.unwrap_or_default());
+                let increase = avg_yield - control_yield;
+                let roi = CostBenefitAnalysis::roi(&cost, control_cost, increase, crop_price_per_kg);
+
+                let index = match by_formula.iter().position(|(c, _)| c == code) {
+                    Some(index) => index,
+                    None => {
+                        by_formula.push((code.clone(), FormulaSites { name, ..Default::default() }));
+                        by_formula.len() - 1
+                    }
                 };
-                ROIAnalysis {
-                    treatment_name: tc.treatment_name.clone(),
-                    additional_cost,
-                    additional_revenue,
-                    net_benefit,
-                    roi_percent: roi,
-                    is_profitable: net_benefit > Decimal::ZERO,
+                let entry = &mut by_formula[index].1;
+                if control_yield > 0.0 {
+                    entry.gains.push(increase / control_yield * 100.0);
                 }
-            })
-            .collect();
-
-        // Simple break-even analysis
-        let avg_additional_cost: Decimal = if !roi_analysis.is_empty() {
-            roi_analysis.iter().map(|r| r.additional_cost).sum::<Decimal>() / Decimal::from(roi_analysis.len())
-        } else {
-            Decimal::ZERO
-        };
-
-        let break_even_yield = if crop_price_per_kg > Decimal::ZERO {
-            avg_additional_cost / crop_price_per_kg
-        } else {
-            Decimal::ZERO
-        };
-
-        let break_even_analysis = BreakEvenAnalysis {
-            break_even_yield_increase: break_even_yield.to_string().parse().unwrap_or(0.0),
-            break_even_price: avg_additional_cost,
-            current_margin_of_safety: 0.0, // Simplified
-        };
-
-        // Generate recommendation
-        let profitable_treatments: Vec<_> = roi_analysis
-            .iter()
-            .filter(|r| r.is_profitable)
-            .collect();
+                entry.rois.push(roi.roi_percent);
+                entry.costs.push(cost.total_cost_per_ha.to_string().parse::<f64>().unwrap_or(0.0));
+            }
+        }
 
-        let recommendation = if profitable_treatments.is_empty() {
-            "No treatments showed positive ROI. Consider reviewing formulations or application rates.".to_string()
-        } else {
-            let best = profitable_treatments
-                .iter()
-                .max_by(|a, b| a.roi_percent.partial_cmp(&b.roi_percent).unwrap())
-                .unwrap();
-            format!(
-                "Recommended: {} with ROI of {:.1}% and net benefit of Rp {:.0}/ha",
-                best.treatment_name, best.roi_percent, best.net_benefit
-            )
+        let mean = |values: &[f64]| {
+            (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
         };
 
-        Ok(CostBenefitResult {
-            treatment_costs,
-            yield_comparison,
-            roi_analysis,
-            break_even_analysis,
-            recommendation,
-        })
+        Ok(by_formula
+            .into_iter()
+            .map(|(code, sites)| {
+                let qc_pass_rate = qc
+                    .iter()
+                    .find(|q| q.code == code && q.tested > 0)
+                    .map(|q| q.passed as f64 / q.tested as f64 * 100.0);
+                let consistency_percent = (!sites.gains.is_empty()).then(|| {
+                    sites.gains.iter().filter(|g| **g > 0.0).count() as f64 / sites.gains.len() as f64 * 100.0
+                });
+                DecisionAlternative {
+                    formula_code: code,
+                    formula_name: sites.name,
+                    sites: sites.rois.len(),
+                    yield_gain_percent: mean(&sites.gains),
+                    roi_percent: mean(&sites.rois),
+                    qc_pass_rate,
+                    consistency_percent,
+                    cost_per_ha: mean(&sites.costs),
+                }
+            })
+            .collect())
     }
 }
 
@@ -1769,3 +8923,241 @@ impl HistoryExportService {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tensile strength by cotton weight percent (Montgomery, Design and
+    // Analysis of Experiments, ch. 3): 5 replicates, MSE 8.06 on 20 df.
+    const TENSILE_MEANS: [f64; 5] = [9.8, 15.4, 17.6, 21.6, 10.8];
+
+    #[test]
+    fn studentized_range_cdf_matches_ptukey() {
+        // Upper 5% points from R's qtukey(0.95, k, df)
+        for (q, k, df) in [(3.151064, 2.0, 10.0), (3.772929, 3.0, 12.0), (4.231857, 5.0, 20.0), (3.314493, 3.0, 1e6)] {
+            let p = StatisticalAnalysis::studentized_range_cdf(q, k, df);
+            assert!((p - 0.95).abs() < 1e-5, "ptukey({}, {}, {}) = {}", q, k, df, p);
+        }
+    }
+
+    #[test]
+    fn studentized_range_of_two_means_is_scaled_t() {
+        // P(range < q) for k = 2 is P(|T| < q / sqrt(2))
+        for (q, df) in [(1.0, 3.0), (2.0, 5.0), (3.5, 30.0)] {
+            let t = StudentsT::new(0.0, 1.0, df).unwrap();
+            let expected = 2.0 * t.cdf(q / std::f64::consts::SQRT_2) - 1.0;
+            let p = StatisticalAnalysis::studentized_range_cdf(q, 2.0, df);
+            assert!((p - expected).abs() < 1e-7, "q = {}, df = {}: {} vs {}", q, df, p, expected);
+        }
+    }
+
+    #[test]
+    fn studentized_range_quantile_inverts_cdf() {
+        let q = StatisticalAnalysis::studentized_range_quantile(0.95, 3.0, 12.0);
+        assert!((q - 3.772929).abs() < 1e-4, "qtukey(0.95, 3, 12) = {}", q);
+    }
+
+    #[test]
+    fn tukey_letters_match_textbook() {
+        // HSD = 5.37: 21.6-17.6 and 17.6-15.4 and 15.4-10.8 and 10.8-9.8 are
+        // not significant, every wider gap is
+        let result = StatisticalAnalysis::mean_separation(PostHocTest::Tukey, &TENSILE_MEANS, &[5; 5], 8.06, 20.0, 0.05);
+        assert_eq!(result.letters, ["d", "bc", "ab", "a", "cd"]);
+    }
+
+    #[test]
+    fn lsd_letters_match_textbook() {
+        // LSD = 3.75: only 17.6-15.4 and 10.8-9.8 are not significant
+        let result = StatisticalAnalysis::mean_separation(PostHocTest::Lsd, &TENSILE_MEANS, &[5; 5], 8.06, 20.0, 0.05);
+        assert_eq!(result.letters, ["c", "b", "b", "a", "c"]);
+    }
+
+    #[test]
+    fn duncan_letters_match_textbook() {
+        // Shortest significant ranges 3.75, 3.94, 4.04, 4.13 for 2-5 means
+        let result = StatisticalAnalysis::mean_separation(PostHocTest::Duncan, &TENSILE_MEANS, &[5; 5], 8.06, 20.0, 0.05);
+        assert_eq!(result.letters, ["c", "b", "b", "a", "c"]);
+    }
+
+    // Vascular graft yield by extrusion pressure in 6 resin batches
+    // (Montgomery, ch. 4): treatment SS 178.17, error SS 109.89 on 15 df.
+    const GRAFT_YIELD: [[f64; 6]; 4] = [
+        [90.3, 89.2, 98.2, 93.9, 87.4, 97.9],
+        [92.5, 89.5, 90.6, 94.7, 87.0, 95.8],
+        [85.5, 90.8, 89.6, 86.2, 88.0, 93.4],
+        [82.5, 89.5, 85.6, 87.4, 78.9, 90.7],
+    ];
+
+    /// Plots as (value, treatment, block), skipping `missing`
+    fn graft_plots(missing: Option<(usize, usize)>) -> (Vec<f64>, Vec<usize>, Vec<usize>) {
+        let mut plots = (Vec::new(), Vec::new(), Vec::new());
+        for (t, row) in GRAFT_YIELD.iter().enumerate() {
+            for (b, &v) in row.iter().enumerate() {
+                if missing != Some((t, b)) {
+                    plots.0.push(v);
+                    plots.1.push(t);
+                    plots.2.push(b);
+                }
+            }
+        }
+        plots
+    }
+
+    fn type_ii_treatment_f(values: &[f64], treatments: &[usize], blocks: &[usize]) -> f64 {
+        let factor = |name: &str, labels: &[usize]| GlmFactor {
+            name: name.to_string(),
+            levels: labels.iter().map(|l| l.to_string()).collect(),
+        };
+        let glm = StatisticalAnalysis::glm(&GlmModel {
+            response: values.to_vec(),
+            factors: vec![factor("Block", blocks), factor("Treatment", treatments)],
+            covariates: Vec::new(),
+            terms: vec![vec!["Block".to_string()], vec!["Treatment".to_string()]],
+        });
+        glm.terms.iter().find(|term| term.name == "Treatment").and_then(|term| term.type_ii.f).unwrap()
+    }
+
+    #[test]
+    fn blocked_permutation_f_matches_rcbd() {
+        let (values, treatments, blocks) = graft_plots(None);
+        let result = StatisticalAnalysis::permutation_test(&values, &treatments, Some(&blocks), 999, 1);
+        assert!((result.f_statistic - 8.107077).abs() < 1e-5, "F = {}", result.f_statistic);
+        assert!((result.parametric_p_value - 0.0019).abs() < 1e-4, "p = {}", result.parametric_p_value);
+        assert!(result.blocked && result.p_value < 0.01);
+    }
+
+    #[test]
+    fn blocked_permutation_scores_each_labelling_with_adjusted_f() {
+        // With a missing plot the adjusted F differs from the unadjusted
+        // treatment SS; permuted labellings must be scored the same way
+        let (values, treatments, blocks) = graft_plots(Some((1, 2)));
+        let scorer = AdjustedTreatmentF::new(&values, &treatments, &blocks);
+        let mut permuted = treatments.clone();
+        permuted.swap(0, 6); // (t0, b0) <-> (t1, b0)
+        permuted.swap(12, 18); // (t2, b1) <-> (t3, b1)
+        assert_eq!((blocks[0], blocks[6], blocks[12], blocks[18]), (0, 0, 1, 1));
+        for labels in [&treatments, &permuted] {
+            let expected = type_ii_treatment_f(&values, labels, &blocks);
+            let f = scorer.f(labels);
+            assert!((f - expected).abs() < 1e-9 * expected, "{} vs {}", f, expected);
+        }
+        assert_eq!((scorer.df_treatment, scorer.df_error), (3.0, 14.0));
+    }
+
+    #[test]
+    fn resample_seed_reproduces_known_draws() {
+        // A change here means stored seeds no longer reproduce their runs
+        let draws = StatisticalAnalysis::resample_chunks(RESAMPLE_CHUNK + 2, 42, |rng, count| {
+            (0..count).map(|_| rng.gen_range(0..1000) as f64).collect()
+        });
+        assert_eq!(draws.len(), RESAMPLE_CHUNK + 2);
+        assert_eq!(draws[..3], [848.0, 45.0, 280.0]);
+        assert_eq!(draws[RESAMPLE_CHUNK..], [593.0, 3.0]);
+    }
+
+    #[test]
+    fn ancova_matches_textbook_fiber_strength() {
+        // Breaking strength by machine with fiber diameter as covariate
+        // (Montgomery, ch. 15): slope 0.954, adjusted treatment F 2.61 on
+        // 2 and 11 df, MSE 2.54, adjusted means 40.38, 41.42, 38.80
+        let strength = [36.0, 41.0, 39.0, 42.0, 49.0, 40.0, 48.0, 39.0, 45.0, 44.0, 35.0, 37.0, 42.0, 34.0, 32.0];
+        let diameter = [20.0, 25.0, 24.0, 25.0, 32.0, 22.0, 28.0, 22.0, 30.0, 28.0, 21.0, 23.0, 26.0, 21.0, 15.0];
+        let result = StatisticalAnalysis::ancova(&AncovaData {
+            response: strength.to_vec(),
+            treatments: (1..=3).flat_map(|m| std::iter::repeat_n(m.to_string(), 5)).collect(),
+            blocks: None,
+            covariates: vec![GlmCovariate {
+                name: "Diameter".to_string(),
+                values: diameter.to_vec(),
+            }],
+        });
+
+        assert!((result.slopes[0].slope - 0.953988).abs() < 1e-5);
+        assert_eq!((result.treatment.df, result.anova.error.df), (2, 11));
+        assert!((result.treatment.ss - 13.283851).abs() < 1e-5);
+        assert!((result.treatment.f.unwrap() - 2.610643).abs() < 1e-5);
+        assert!((result.anova.error.ms - 2.544172).abs() < 1e-5);
+        for (mean, expected) in result.adjusted_means.iter().zip([40.382413, 41.419223, 38.798364]) {
+            assert!((mean - expected).abs() < 1e-5, "{} vs {}", mean, expected);
+        }
+        assert!(result.slope_homogeneity.passed);
+    }
+
+    #[test]
+    fn severity_blocks_replications_on_a_min_based_scale() {
+        // 1-5 scores: 1 is healthy (0%), 3 is 50%, 5 is 100%
+        let scale = SeverityScale {
+            min_class: 1.0,
+            max_class: 5.0,
+            healthy_class: 1.0,
+            class_midpoints: Vec::new(),
+        };
+        let scores = [("A", [3.0, 2.0, 4.0]), ("B", [5.0, 4.0, 5.0]), ("C", [1.0, 2.0, 2.0])];
+        let treatments: Vec<SeverityTreatmentData> = scores
+            .iter()
+            .map(|(name, plots)| SeverityTreatmentData {
+                treatment: name.to_string(),
+                plots: plots
+                    .iter()
+                    .enumerate()
+                    .map(|(r, score)| SeverityPlotData {
+                        plot: format!("{}{}", name, r + 1),
+                        replication: Some(r as i32 + 1),
+                        sessions: vec![SeveritySession {
+                            days_after_treatment: 14.0,
+                            scores: vec![*score],
+                        }],
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let result = StatisticalAnalysis::severity_analysis(&scale, &treatments, true, PostHocTest::Tukey).unwrap();
+        let dsi: Vec<f64> = result.treatments[0].plots.iter().map(|p| p.points[0].severity_index).collect();
+        assert_eq!(dsi, vec![50.0, 25.0, 75.0]);
+
+        let expected = StatisticalAnalysis::rcbd_anova(&[
+            vec![Some(50.0), Some(25.0), Some(75.0)],
+            vec![Some(100.0), Some(75.0), Some(100.0)],
+            vec![Some(0.0), Some(25.0), Some(25.0)],
+        ]);
+        let comparison = result.comparisons.iter().find(|c| c.index == "final_severity_index").unwrap();
+        match &comparison.anova {
+            SeverityAnova::Rcbd { result } => {
+                assert_eq!((result.block.df, result.error.df), (2, 4));
+                assert!((result.treatment.f.unwrap() - expected.treatment.f.unwrap()).abs() < 1e-9);
+            }
+            other => panic!("expected an RCBD comparison, got {:?}", other),
+        }
+
+        let mut duplicated = treatments.clone();
+        duplicated[0].plots[1].replication = Some(1);
+        assert!(StatisticalAnalysis::severity_analysis(&scale, &duplicated, true, PostHocTest::Tukey).is_err());
+    }
+
+    #[test]
+    fn morans_i_uses_residual_moments() {
+        // Four plots in a row, rook neighbours (S0 = 6)
+        let positions = [(1, 0), (1, 1), (1, 2), (1, 3)];
+        let morans_i = |residuals: &[f64], design: &DMatrix<f64>| {
+            StatisticalAnalysis::morans_i(residuals, design, &positions, Neighbourhood::Rook, 99, 7).unwrap()
+        };
+
+        // Intercept only: the raw-value moments, E[I] = -1/(n-1) and
+        // Var = 4/27, so I = 1/3 gives z = sqrt(3)
+        let raw = morans_i(&[-1.5, -0.5, 0.5, 1.5], &DMatrix::from_element(4, 1, 1.0));
+        assert!((raw.statistic - 1.0 / 3.0).abs() < 1e-12);
+        assert!((raw.expected + 1.0 / 3.0).abs() < 1e-12);
+        assert!((raw.z_score - 3f64.sqrt()).abs() < 1e-9);
+
+        // Residuals of y = 1, 3, 2, 4 on a column trend: tr(MW) = -2 and
+        // tr((MW)²) = 2.5, so E[I] = -2/3 and Var = 1/18; I = -1 gives z = -sqrt(2)
+        let trend = DMatrix::from_fn(4, 2, |i, j| if j == 0 { 1.0 } else { i as f64 });
+        let residuals = morans_i(&[-0.3, 0.9, -0.9, 0.3], &trend);
+        assert!((residuals.statistic + 1.0).abs() < 1e-12);
+        assert!((residuals.expected + 2.0 / 3.0).abs() < 1e-12);
+        assert!((residuals.z_score + 2f64.sqrt()).abs() < 1e-9);
+        assert_eq!(residuals.neighbour_pairs, 3);
+    }
+}
@@ -0,0 +1,413 @@
+use serde::{Deserialize, Serialize};
+use statrs::distribution::{ChiSquared, ContinuousCDF, FisherSnedecor, Normal};
+
+// ==============================================================================
+// ANOVA ASSUMPTION DIAGNOSTICS
+// ==============================================================================
+
+pub struct AssumptionDiagnostics;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnovaDiagnostics {
+    pub normality: AssumptionTest,     // Shapiro-Wilk on the residuals
+    pub levene: AssumptionTest,        // Brown-Forsythe (median-centred) Levene test
+    pub bartlett: AssumptionTest,
+    pub assumptions_met: bool,
+    pub residuals: Vec<ResidualPoint>,
+    pub flagged_count: usize,
+    pub suggested_transformation: Option<Transformation>,
+    pub recommendation: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssumptionTest {
+    pub name: String,
+    pub statistic: f64,
+    pub df1: Option<f64>,
+    pub df2: Option<f64>,
+    pub p_value: f64,
+    pub passed: bool, // p >= 0.05
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResidualPoint {
+    pub group: usize,
+    pub index: usize, // position within the group
+    pub observed: f64,
+    pub fitted: f64,
+    pub residual: f64,
+    pub standardized: f64,
+    pub is_flagged: bool, // |standardized| > 3
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transformation {
+    Log,         // log(x), or log(x + 1) when zeros occur
+    Sqrt,        // sqrt(x), or sqrt(x + 0.5) for small counts
+    ArcsineSqrt, // arcsin(sqrt(p)) for percentages
+}
+
+const ALPHA: f64 = 0.05;
+const FLAG_LIMIT: f64 = 3.0;
+
+impl AssumptionDiagnostics {
+    /// Diagnostics for a one-way layout: normality of the residuals,
+    /// homogeneity of group variances, standardized residuals and, when an
+    /// assumption fails, a variance-stabilizing transformation.
+    ///
+    /// Set `is_percentage` for data recorded as 0-100 % (incidence, germination),
+    /// which gets the arcsine square-root transformation.
+    pub fn one_way(groups: &[Vec<f64>], is_percentage: bool) -> AnovaDiagnostics {
+        let means: Vec<f64> = groups.iter().map(|g| Self::mean(g)).collect();
+        let n_total: usize = groups.iter().map(|g| g.len()).sum();
+        let k = groups.iter().filter(|g| !g.is_empty()).count();
+
+        let ss_error: f64 = groups
+            .iter()
+            .zip(means.iter())
+            .map(|(g, m)| g.iter().map(|y| (y - m).powi(2)).sum::<f64>())
+            .sum();
+        let df_error = n_total.saturating_sub(k) as f64;
+        let ms_error = if df_error > 0.0 { ss_error / df_error } else { 0.0 };
+
+        // Internally studentized residuals: e / sqrt(MSE * (1 - 1/n_i))
+        let residuals: Vec<ResidualPoint> = groups
+            .iter()
+            .enumerate()
+            .flat_map(|(g, values)| {
+                let fitted = means[g];
+                let leverage = 1.0 / values.len().max(1) as f64;
+                values.iter().enumerate().map(move |(index, &observed)| {
+                    let residual = observed - fitted;
+                    let scale = (ms_error * (1.0 - leverage)).sqrt();
+                    let standardized = if scale > 0.0 { residual / scale } else { 0.0 };
+                    ResidualPoint {
+                        group: g,
+                        index,
+                        observed,
+                        fitted,
+                        residual,
+                        standardized,
+                        is_flagged: standardized.abs() > FLAG_LIMIT,
+                    }
+                })
+            })
+            .collect();
+
+        let raw: Vec<f64> = residuals.iter().map(|r| r.residual).collect();
+        let normality = Self::shapiro_wilk(&raw);
+        let levene = Self::levene(groups);
+        let bartlett = Self::bartlett(groups);
+        let assumptions_met = normality.passed && levene.passed;
+        let flagged_count = residuals.iter().filter(|r| r.is_flagged).count();
+
+        let (suggested_transformation, recommendation) = if assumptions_met {
+            (None, "Residuals look normal and group variances are homogeneous; the ANOVA can be used as is.".to_string())
+        } else {
+            Self::suggest_transformation(groups, is_percentage)
+        };
+
+        AnovaDiagnostics {
+            normality,
+            levene,
+            bartlett,
+            assumptions_met,
+            residuals,
+            flagged_count,
+            suggested_transformation,
+            recommendation,
+        }
+    }
+
+    /// Shapiro-Wilk W test (Royston, 1995, algorithm AS R94) for 3 to 5000 values.
+    pub fn shapiro_wilk(values: &[f64]) -> AssumptionTest {
+        let n = values.len();
+        let untestable = |statistic: f64| AssumptionTest {
+            name: "Shapiro-Wilk".to_string(),
+            statistic,
+            df1: None,
+            df2: None,
+            p_value: 1.0,
+            passed: true,
+        };
+        if !(3..=5000).contains(&n) {
+            return untestable(1.0);
+        }
+
+        let mut x = values.to_vec();
+        x.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        if x[n - 1] - x[0] < 1e-19 {
+            return untestable(1.0);
+        }
+
+        let normal = match Normal::new(0.0, 1.0) {
+            Ok(d) => d,
+            Err(_) => return untestable(1.0),
+        };
+        let poly = |c: &[f64], x: f64| c.iter().rev().fold(0.0, |acc, &ci| acc * x + ci);
+
+        // Coefficients for the upper half of the order statistics
+        let half = n / 2;
+        let an = n as f64;
+        let mut a = vec![0.0; half];
+        if n == 3 {
+            a[0] = std::f64::consts::FRAC_1_SQRT_2;
+        } else {
+            let m: Vec<f64> = (1..=half)
+                .map(|i| normal.inverse_cdf((i as f64 - 0.375) / (an + 0.25)))
+                .collect();
+            let summ2 = 2.0 * m.iter().map(|v| v * v).sum::<f64>();
+            let ssumm2 = summ2.sqrt();
+            let rsn = 1.0 / an.sqrt();
+            let c1 = [0.0, 0.221157, -0.147981, -2.07119, 4.434685, -2.706056];
+            let c2 = [0.0, 0.042981, -0.293762, -1.752461, 5.682633, -3.582633];
+            let a1 = poly(&c1, rsn) - m[0] / ssumm2;
+
+            let (first, fac) = if n > 5 {
+                let a2 = -m[1] / ssumm2 + poly(&c2, rsn);
+                a[1] = a2;
+                let fac = ((summ2 - 2.0 * m[0] * m[0] - 2.0 * m[1] * m[1])
+                    / (1.0 - 2.0 * a1 * a1 - 2.0 * a2 * a2))
+                    .sqrt();
+                (2, fac)
+            } else {
+                (1, ((summ2 - 2.0 * m[0] * m[0]) / (1.0 - 2.0 * a1 * a1)).sqrt())
+            };
+            a[0] = a1;
+            for i in first..half {
+                a[i] = -m[i] / fac;
+            }
+        }
+
+        let mean = Self::mean(&x);
+        let ssq: f64 = x.iter().map(|v| (v - mean).powi(2)).sum();
+        let numerator: f64 = (0..half).map(|i| a[i] * (x[n - 1 - i] - x[i])).sum();
+        let w = (numerator * numerator / ssq).min(1.0);
+
+        let p_value = if n == 3 {
+            let pi6 = 6.0 / std::f64::consts::PI;
+            let stqr = std::f64::consts::PI / 3.0;
+            (pi6 * (w.sqrt().asin() - stqr)).max(0.0)
+        } else {
+            let y = (1.0 - w).ln();
+            let (z, mu, sigma) = if n <= 11 {
+                let gamma = poly(&[-2.273, 0.459], an);
+                if y >= gamma {
+                    (f64::INFINITY, 0.0, 1.0)
+                } else {
+                    let mu = poly(&[0.544, -0.39978, 0.025054, -6.714e-4], an);
+                    let sigma = poly(&[1.3822, -0.77857, 0.062767, -0.0020322], an).exp();
+                    (-(gamma - y).ln(), mu, sigma)
+                }
+            } else {
+                let ln_n = an.ln();
+                let mu = poly(&[-1.5861, -0.31082, -0.083751, 0.0038915], ln_n);
+                let sigma = poly(&[-0.4803, -0.082676, 0.0030302], ln_n).exp();
+                (y, mu, sigma)
+            };
+            if z.is_infinite() {
+                0.0
+            } else {
+                1.0 - normal.cdf((z - mu) / sigma)
+            }
+        };
+
+        AssumptionTest {
+            name: "Shapiro-Wilk".to_string(),
+            statistic: w,
+            df1: None,
+            df2: None,
+            p_value,
+            passed: p_value >= ALPHA,
+        }
+    }
+
+    /// Levene's test with the group medians as centre (Brown-Forsythe), which
+    /// stays reliable for skewed data.
+    pub fn levene(groups: &[Vec<f64>]) -> AssumptionTest {
+        let deviations: Vec<Vec<f64>> = groups
+            .iter()
+            .filter(|g| !g.is_empty())
+            .map(|g| {
+                let centre = Self::median(g);
+                g.iter().map(|y| (y - centre).abs()).collect()
+            })
+            .collect();
+
+        let k = deviations.len();
+        let n: usize = deviations.iter().map(|g| g.len()).sum();
+        let grand = deviations.iter().flatten().sum::<f64>() / n.max(1) as f64;
+        let between: f64 = deviations
+            .iter()
+            .map(|g| g.len() as f64 * (Self::mean(g) - grand).powi(2))
+            .sum();
+        let within: f64 = deviations
+            .iter()
+            .map(|g| {
+                let m = Self::mean(g);
+                g.iter().map(|z| (z - m).powi(2)).sum::<f64>()
+            })
+            .sum();
+
+        let df1 = k.saturating_sub(1) as f64;
+        let df2 = n.saturating_sub(k) as f64;
+        let statistic = if df1 > 0.0 && df2 > 0.0 && within > 0.0 {
+            (between / df1) / (within / df2)
+        } else {
+            0.0
+        };
+        let p_value = if df1 > 0.0 && df2 > 0.0 {
+            FisherSnedecor::new(df1, df2)
+                .map(|d| 1.0 - d.cdf(statistic))
+                .unwrap_or(1.0)
+        } else {
+            1.0
+        };
+
+        AssumptionTest {
+            name: "Levene (Brown-Forsythe)".to_string(),
+            statistic,
+            df1: Some(df1),
+            df2: Some(df2),
+            p_value,
+            passed: p_value >= ALPHA,
+        }
+    }
+
+    /// Bartlett's test for equal variances; sensitive to non-normality.
+    pub fn bartlett(groups: &[Vec<f64>]) -> AssumptionTest {
+        let usable: Vec<(f64, f64)> = groups
+            .iter()
+            .filter(|g| g.len() >= 2)
+            .map(|g| {
+                let m = Self::mean(g);
+                let df = (g.len() - 1) as f64;
+                (df, g.iter().map(|y| (y - m).powi(2)).sum::<f64>() / df)
+            })
+            .collect();
+
+        let k = usable.len() as f64;
+        let df_total: f64 = usable.iter().map(|(df, _)| df).sum();
+        let pooled = if df_total > 0.0 {
+            usable.iter().map(|(df, s2)| df * s2).sum::<f64>() / df_total
+        } else {
+            0.0
+        };
+
+        let testable = k >= 2.0 && pooled > 0.0 && usable.iter().all(|(_, s2)| *s2 > 0.0);
+        let statistic = if testable {
+            let numerator = df_total * pooled.ln() - usable.iter().map(|(df, s2)| df * s2.ln()).sum::<f64>();
+            let correction = 1.0
+                + (usable.iter().map(|(df, _)| 1.0 / df).sum::<f64>() - 1.0 / df_total) / (3.0 * (k - 1.0));
+            numerator / correction
+        } else {
+            0.0
+        };
+        let p_value = if testable {
+            ChiSquared::new(k - 1.0)
+                .map(|d| 1.0 - d.cdf(statistic))
+                .unwrap_or(1.0)
+        } else {
+            1.0
+        };
+
+        AssumptionTest {
+            name: "Bartlett".to_string(),
+            statistic,
+            df1: Some((k - 1.0).max(0.0)),
+            df2: None,
+            p_value,
+            passed: p_value >= ALPHA,
+        }
+    }
+
+    /// Picks a transformation from the data scale and the slope of
+    /// log(SD) on log(mean) across groups (Taylor's power law): slope near
+    /// 0.5 suggests the square root, near 1 the logarithm.
+    fn suggest_transformation(groups: &[Vec<f64>], is_percentage: bool) -> (Option<Transformation>, String) {
+        let values: Vec<f64> = groups.iter().flatten().copied().collect();
+        let has_zero = values.contains(&0.0);
+
+        if is_percentage {
+            return (
+                Some(Transformation::ArcsineSqrt),
+                "Assumptions not met. Percentage data: analyse arcsin(sqrt(p/100)) and report back-transformed means.".to_string(),
+            );
+        }
+        if values.iter().any(|v| *v < 0.0) {
+            return (
+                None,
+                "Assumptions not met and the data contain negative values; consider a nonparametric test.".to_string(),
+            );
+        }
+
+        let points: Vec<(f64, f64)> = groups
+            .iter()
+            .filter(|g| g.len() >= 2)
+            .filter_map(|g| {
+                let m = Self::mean(g);
+                let sd = (g.iter().map(|y| (y - m).powi(2)).sum::<f64>() / (g.len() - 1) as f64).sqrt();
+                (m > 0.0 && sd > 0.0).then(|| (m.ln(), sd.ln()))
+            })
+            .collect();
+        let slope = if points.len() >= 3 {
+            let mx = points.iter().map(|p| p.0).sum::<f64>() / points.len() as f64;
+            let my = points.iter().map(|p| p.1).sum::<f64>() / points.len() as f64;
+            let sxx: f64 = points.iter().map(|p| (p.0 - mx).powi(2)).sum();
+            let sxy: f64 = points.iter().map(|p| (p.0 - mx) * (p.1 - my)).sum();
+            if sxx > 0.0 { Some(sxy / sxx) } else { None }
+        } else {
+            None
+        };
+
+        match slope {
+            Some(b) if b >= 0.75 => (
+                Some(Transformation::Log),
+                format!(
+                    "Assumptions not met. SD grows in proportion to the mean (slope {:.2}); analyse {}.",
+                    b,
+                    if has_zero { "log(x + 1)" } else { "log(x)" }
+                ),
+            ),
+            Some(b) if b >= 0.25 => (
+                Some(Transformation::Sqrt),
+                format!(
+                    "Assumptions not met. Variance grows with the mean as for counts (slope {:.2}); analyse {}.",
+                    b,
+                    if has_zero { "sqrt(x + 0.5)" } else { "sqrt(x)" }
+                ),
+            ),
+            Some(b) => (
+                None,
+                format!(
+                    "Assumptions not met but variance is unrelated to the mean (slope {:.2}); check flagged residuals or use a nonparametric test.",
+                    b
+                ),
+            ),
+            None => (
+                None,
+                "Assumptions not met; too few replicated groups to choose a transformation. Check flagged residuals or use a nonparametric test.".to_string(),
+            ),
+        }
+    }
+
+    fn mean(values: &[f64]) -> f64 {
+        if values.is_empty() {
+            0.0
+        } else {
+            values.iter().sum::<f64>() / values.len() as f64
+        }
+    }
+
+    fn median(values: &[f64]) -> f64 {
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let n = sorted.len();
+        match n {
+            0 => 0.0,
+            _ if n % 2 == 1 => sorted[n / 2],
+            _ => 0.5 * (sorted[n / 2 - 1] + sorted[n / 2]),
+        }
+    }
+}
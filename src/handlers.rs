@@ -393,6 +393,38 @@ pub async fn lock_project(
     )))
 }
 
+pub async fn generate_project_layout(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    body: web::Json<GenerateLayoutRequest>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user = req.extensions().get::<AuthenticatedUser>().cloned()
+        .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+    Authorization::can_create_project(&user)?;
+
+    let layout = LayoutService::generate(pool.get_ref(), path.into_inner(), body.into_inner(), user.user_id()?).await?;
+
+    Ok(HttpResponse::Created().json(ApiResponse::success_with_message(
+        layout,
+        "Field layout generated successfully",
+    )))
+}
+
+pub async fn get_project_layout(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let _user = req.extensions().get::<AuthenticatedUser>().cloned()
+        .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+    let layout = LayoutService::get(pool.get_ref(), path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(layout)))
+}
+
 // ==============================================================================
 // FORMULA HANDLERS
 // ==============================================================================
@@ -574,6 +606,20 @@ pub async fn verify_monitoring_data(
     Ok(HttpResponse::Ok().json(ApiResponse::success(data)))
 }
 
+pub async fn rescan_session_outliers(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let _user = req.extensions().get::<AuthenticatedUser>().cloned()
+        .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+    let result = MonitoringService::scan_outliers(pool.get_ref(), path.into_inner()).await?;
+    let message = format!("{} of {} values flagged as outliers", result.flagged.len(), result.scanned);
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success_with_message(result, &message)))
+}
+
 pub async fn complete_monitoring_session(
     pool: web::Data<PgPool>,
     path: web::Path<Uuid>,
@@ -591,6 +637,60 @@ pub async fn complete_monitoring_session(
     )))
 }
 
+// ==============================================================================
+// ANALYSIS RESULTS
+// ==============================================================================
+
+pub async fn list_analysis_results(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    query: web::Query<AnalysisResultQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let _user = req.extensions().get::<AuthenticatedUser>().cloned()
+        .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+    let results = AnalysisResultService::list(pool.get_ref(), path.into_inner(), &query).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(results)))
+}
+
+pub async fn get_analysis_result(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let _user = req.extensions().get::<AuthenticatedUser>().cloned()
+        .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+    let result = AnalysisResultService::get(pool.get_ref(), path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(result)))
+}
+
+pub async fn invalidate_analysis_result(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    body: web::Json<InvalidateAnalysisRequest>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user = req.extensions().get::<AuthenticatedUser>().cloned()
+        .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+    let result = AnalysisResultService::invalidate(
+        pool.get_ref(),
+        path.into_inner(),
+        body.into_inner(),
+        user.user_id()?,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success_with_message(
+        result,
+        "Analysis result invalidated",
+    )))
+}
+
 // ==============================================================================
 // AUDIT LOGS
 // ==============================================================================
@@ -769,24 +869,19 @@ pub async fn batch_submit_monitoring_data(
         .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
     
     let batch_session_id = body.session_id;
-    let mut results = Vec::new();
-    
-    for item in &body.data {
-        let req = SubmitMonitoringDataRequest {
+    let reqs: Vec<SubmitMonitoringDataRequest> = body
+        .data
+        .iter()
+        .map(|item| SubmitMonitoringDataRequest {
             session_id: batch_session_id,
             unit_id: item.unit_id,
             parameter_id: item.parameter_id,
             numeric_value: item.numeric_value,
             text_value: item.text_value.clone(),
             observation_notes: item.notes.clone(),
-        };
-        let data = MonitoringService::submit_data(
-            pool.get_ref(),
-            req,
-            user.user_id()?,
-        ).await?;
-        results.push(data);
-    }
+        })
+        .collect();
+    let results = MonitoringService::submit_batch(pool.get_ref(), reqs, user.user_id()?).await?;
 
     let message = format!("{} data points submitted", body.data.len());
     Ok(HttpResponse::Created().json(ApiResponse::success_with_message(
@@ -1,6 +1,7 @@
 mod analysis;
 mod auth;
 mod config;
+mod diagnostics;
 mod errors;
 mod handlers;
 mod models;
@@ -147,8 +148,11 @@ async fn main() -> std::io::Result<()> {
                                     .route("/{id}/status", web::put().to(handlers::update_project_status))
                                     .route("/{id}/lock", web::post().to(handlers::lock_project))
                                     .route("/{id}/team", web::post().to(handlers::add_team_member))
+                                    .route("/{id}/layout", web::post().to(handlers::generate_project_layout))
+                                    .route("/{id}/layout", web::get().to(handlers::get_project_layout))
                                     .route("/{id}/qr-codes", web::post().to(qrcode::generate_project_qr_codes_handler))
                                     .route("/{id}/qr-print", web::get().to(qrcode::generate_qr_print_sheet))
+                                    .route("/{id}/analysis-results", web::get().to(handlers::list_analysis_results))
                             )
                             // Formula routes
                             .service(
@@ -172,6 +176,7 @@ async fn main() -> std::io::Result<()> {
                                 web::scope("/monitoring")
                                     .route("/sessions", web::post().to(handlers::create_monitoring_session))
                                     .route("/sessions/{id}/complete", web::post().to(handlers::complete_monitoring_session))
+                                    .route("/sessions/{id}/outlier-scan", web::post().to(handlers::rescan_session_outliers))
                                     .route("/data", web::post().to(handlers::submit_monitoring_data))
                                     .route("/data/batch", web::post().to(handlers::batch_submit_monitoring_data))
                                     .route("/data/{id}/verify", web::post().to(handlers::verify_monitoring_data))
@@ -186,8 +191,34 @@ async fn main() -> std::io::Result<()> {
                             // Analysis routes
                             .route("/analysis/descriptive", web::post().to(analysis_handler::descriptive_stats))
                             .route("/analysis/anova", web::post().to(analysis_handler::anova_analysis))
+                            .route("/analysis/project/descriptive", web::post().to(analysis_handler::project_descriptive_stats))
+                            .route("/analysis/project/anova", web::post().to(analysis_handler::project_anova))
+                            .route("/analysis/project/spatial", web::post().to(analysis_handler::project_spatial))
+                            .route("/analysis/rcbd", web::post().to(analysis_handler::rcbd_anova))
+                            .route("/analysis/split-plot", web::post().to(analysis_handler::split_plot_anova))
+                            .route("/analysis/glm", web::post().to(analysis_handler::glm_analysis))
+                            .route("/analysis/ancova", web::post().to(analysis_handler::ancova_analysis))
+                            .route("/analysis/dunnett", web::post().to(analysis_handler::dunnett_analysis))
+                            .route("/analysis/nonparametric", web::post().to(analysis_handler::nonparametric_analysis))
+                            .route("/analysis/repeated-measures", web::post().to(analysis_handler::repeated_measures_anova))
+                            .route("/analysis/growth-curves", web::post().to(analysis_handler::growth_curves))
+                            .route("/analysis/severity", web::post().to(analysis_handler::severity_analysis))
+                            .route("/analysis/yield-prediction", web::post().to(analysis_handler::yield_prediction))
+                            .route("/analysis/multivariate", web::post().to(analysis_handler::multivariate_analysis))
+                            .route("/analysis/multi-environment", web::post().to(analysis_handler::multi_environment_analysis))
+                            .route("/analysis/bootstrap", web::post().to(analysis_handler::bootstrap_analysis))
+                            .route("/analysis/permutation", web::post().to(analysis_handler::permutation_test))
                             .route("/analysis/ai", web::post().to(analysis_handler::ai_analysis))
                             .route("/analysis/cost-benefit", web::post().to(analysis_handler::cost_benefit))
+                            .route("/analysis/decision", web::post().to(analysis_handler::decision_support))
+                            .route("/analysis/dose-response", web::post().to(analysis_handler::dose_response))
+                            .route("/analysis/power-plan", web::post().to(analysis_handler::power_plan))
+                            // Stored analysis results
+                            .service(
+                                web::scope("/analysis-results")
+                                    .route("/{id}", web::get().to(handlers::get_analysis_result))
+                                    .route("/{id}/invalidate", web::post().to(handlers::invalidate_analysis_result))
+                            )
                             // Report routes
                             .route("/reports/generate", web::post().to(report_handler::generate_report))
                             // AI Chat routes (RAG Research Assistant)
@@ -219,12 +250,52 @@ async fn main() -> std::io::Result<()> {
 
 mod analysis_handler {
     use super::*;
-    use crate::analysis::{AIAnalysisService, CostBenefitAnalysis, StatisticalAnalysis};
+    use crate::analysis::{
+        AIAnalysisService, BootstrapStatistic, CorrelationMethod, CostBenefitAnalysis, DataLevel,
+        DecisionCriterion, DecisionMethod, DecisionSupport, DunnettAlternative, GlmModel, GrowthModel,
+        Neighbourhood, PostHocTest, SpatialAdjustment, StatisticalAnalysis,
+    };
     use crate::auth::AuthenticatedUser;
+    use crate::diagnostics::AssumptionDiagnostics;
     use crate::errors::AppError;
-    use crate::models::ApiResponse;
+    use crate::models::{AnalysisResult, ApiResponse, ExperimentDesign, MonitoringType, NewAnalysisRun};
+    use crate::services::AnalysisResultService;
     use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
-    use sqlx::PgPool;
+    use sqlx::{PgPool, Postgres, Transaction};
+
+    /// Persist a project-bound run, hashing the monitoring data inside the
+    /// snapshot the analysis read it from
+    async fn save_run(
+        pool: &PgPool,
+        user: &AuthenticatedUser,
+        snapshot: Transaction<'static, Postgres>,
+        mut run: NewAnalysisRun,
+    ) -> Result<AnalysisResult, AppError> {
+        AnalysisResultService::seal_snapshot(snapshot, &mut run).await?;
+        AnalysisResultService::record(pool, run, user.user_id()?).await
+    }
+
+    fn saved_message(stored: &AnalysisResult) -> String {
+        format!("Saved as analysis result {} (version {})", stored.id, stored.version)
+    }
+
+    /// Respond with a run on request-body values, storing it first when the
+    /// caller names a project
+    async fn respond_with_request_run<I: serde::Serialize, R: serde::Serialize>(
+        pool: &PgPool,
+        user: &AuthenticatedUser,
+        project_id: Option<uuid::Uuid>,
+        analysis_type: &str,
+        input: &I,
+        result: R,
+    ) -> Result<HttpResponse, AppError> {
+        let Some(project_id) = project_id else {
+            return Ok(HttpResponse::Ok().json(ApiResponse::success(result)));
+        };
+        let run = NewAnalysisRun::from_request(project_id, analysis_type, input, &result);
+        let stored = AnalysisResultService::record(pool, run, user.user_id()?).await?;
+        Ok(HttpResponse::Ok().json(ApiResponse::success_with_message(result, &saved_message(&stored))))
+    }
 
     #[derive(Debug, serde::Deserialize)]
     pub struct DescriptiveStatsRequest {
@@ -246,22 +317,226 @@ mod analysis_handler {
         Ok(HttpResponse::Ok().json(ApiResponse::success(stats)))
     }
 
-    #[derive(Debug, serde::Deserialize)]
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    pub struct ProjectDataRequest {
+        pub project_id: uuid::Uuid,
+        pub parameter_id: uuid::Uuid,
+        pub session_id: uuid::Uuid,
+        /// Analysis design; defaults to the project's experiment design
+        pub design: Option<ExperimentDesign>,
+        #[serde(default)]
+        pub post_hoc: PostHocTest,
+        pub alpha: Option<f64>,
+        /// Keep values flagged as outliers
+        #[serde(default)]
+        pub include_outliers: bool,
+    }
+
+    pub async fn project_descriptive_stats(
+        pool: web::Data<PgPool>,
+        body: web::Json<ProjectDataRequest>,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, AppError> {
+        let user = req
+            .extensions()
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+        let mut snapshot = AnalysisResultService::begin_snapshot(pool.get_ref()).await?;
+        let dataset = StatisticalAnalysis::fetch_project_dataset(
+            &mut snapshot,
+            body.project_id,
+            body.parameter_id,
+            body.session_id,
+            body.include_outliers,
+        )
+        .await?;
+        if dataset.used.is_empty() {
+            return Err(AppError::Validation("No usable values for this parameter and session".to_string()));
+        }
+
+        let values: Vec<f64> = dataset.used.iter().map(|r| r.value).collect();
+        let by_treatment: Vec<serde_json::Value> = dataset
+            .treatments()
+            .into_iter()
+            .map(|treatment| {
+                let values: Vec<f64> =
+                    dataset.used.iter().filter(|r| r.treatment == treatment).map(|r| r.value).collect();
+                serde_json::json!({
+                    "treatment": treatment,
+                    "stats": StatisticalAnalysis::descriptive(&values),
+                })
+            })
+            .collect();
+
+        let result = serde_json::json!({
+            "overall": StatisticalAnalysis::descriptive(&values),
+            "by_treatment": by_treatment,
+            "dataset": dataset
+        });
+
+        let mut run = NewAnalysisRun::new(body.project_id, "descriptive", &*body, &result);
+        run.parameter_ids = vec![body.parameter_id];
+        run.session_ids = vec![body.session_id];
+        let stored = save_run(pool.get_ref(), &user, snapshot, run).await?;
+
+        Ok(HttpResponse::Ok().json(ApiResponse::success_with_message(result, &saved_message(&stored))))
+    }
+
+    pub async fn project_anova(
+        pool: web::Data<PgPool>,
+        body: web::Json<ProjectDataRequest>,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, AppError> {
+        let user = req
+            .extensions()
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+        let alpha = body.alpha.unwrap_or(0.05);
+        if !(alpha > 0.0 && alpha < 1.0) {
+            return Err(AppError::Validation(
+                "alpha must be between 0 and 1".to_string(),
+            ));
+        }
+
+        let mut snapshot = AnalysisResultService::begin_snapshot(pool.get_ref()).await?;
+        let dataset = StatisticalAnalysis::fetch_project_dataset(
+            &mut snapshot,
+            body.project_id,
+            body.parameter_id,
+            body.session_id,
+            body.include_outliers,
+        )
+        .await?;
+        let design = body
+            .design
+            .clone()
+            .or_else(|| dataset.project_design.clone())
+            .unwrap_or(ExperimentDesign::Ral);
+        let analysis = StatisticalAnalysis::project_anova(&dataset, design, body.post_hoc, alpha)?;
+
+        let result = serde_json::json!({
+            "treatments": dataset.treatments(),
+            "analysis": analysis,
+            "dataset": dataset
+        });
+
+        let mut run = NewAnalysisRun::new(body.project_id, "anova", &*body, &result);
+        run.parameter_ids = vec![body.parameter_id];
+        run.session_ids = vec![body.session_id];
+        let stored = save_run(pool.get_ref(), &user, snapshot, run).await?;
+
+        Ok(HttpResponse::Ok().json(ApiResponse::success_with_message(result, &saved_message(&stored))))
+    }
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    pub struct SpatialRequest {
+        pub project_id: uuid::Uuid,
+        pub parameter_id: uuid::Uuid,
+        pub session_id: uuid::Uuid,
+        #[serde(default)]
+        pub adjustment: SpatialAdjustment,
+        #[serde(default)]
+        pub neighbourhood: Neighbourhood,
+        /// Fit replications as blocks; defaults to true for RAK projects
+        pub blocks: Option<bool>,
+        #[serde(default)]
+        pub include_outliers: bool,
+        pub permutations: Option<usize>,
+        pub seed: Option<u64>,
+    }
+
+    pub async fn project_spatial(
+        pool: web::Data<PgPool>,
+        mut body: web::Json<SpatialRequest>,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, AppError> {
+        let user = req
+            .extensions()
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+        let permutations = body.permutations.unwrap_or(999);
+        if !(99..=MAX_RESAMPLES).contains(&permutations) {
+            return Err(AppError::Validation(format!(
+                "permutations must be between 99 and {}",
+                MAX_RESAMPLES
+            )));
+        }
+
+        let mut snapshot = AnalysisResultService::begin_snapshot(pool.get_ref()).await?;
+        let dataset = StatisticalAnalysis::fetch_project_dataset(
+            &mut snapshot,
+            body.project_id,
+            body.parameter_id,
+            body.session_id,
+            body.include_outliers,
+        )
+        .await?;
+        let blocks = body
+            .blocks
+            .unwrap_or(dataset.project_design == Some(ExperimentDesign::Rak));
+
+        let seed = *body.seed.get_or_insert_with(rand::random);
+        let (adjustment, neighbourhood) = (body.adjustment, body.neighbourhood);
+        let result = web::block(move || {
+            StatisticalAnalysis::spatial_analysis(&dataset, adjustment, neighbourhood, blocks, permutations, seed)
+        })
+        .await
+        .map_err(|e| AppError::InternalError(e.to_string()))??;
+
+        let mut run = NewAnalysisRun::new(body.project_id, "spatial", &*body, &result);
+        run.parameter_ids = vec![body.parameter_id];
+        run.session_ids = vec![body.session_id];
+        let stored = save_run(pool.get_ref(), &user, snapshot, run).await?;
+
+        Ok(HttpResponse::Ok().json(ApiResponse::success_with_message(result, &saved_message(&stored))))
+    }
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
     pub struct AnovaRequest {
         pub groups: Vec<Vec<f64>>,
+        /// Mean separation test: `lsd` (default), `duncan`, `tukey` or `scott_knott`
+        #[serde(default)]
+        pub post_hoc: PostHocTest,
+        pub alpha: Option<f64>,
+        /// Attach normality/homogeneity tests and residuals to the ANOVA result
+        #[serde(default)]
+        pub diagnostics: bool,
+        /// Values are percentages (0-100), for the transformation suggestion
+        #[serde(default)]
+        pub is_percentage: bool,
+        /// Store the run under this project
+        pub project_id: Option<uuid::Uuid>,
     }
 
     pub async fn anova_analysis(
+        pool: web::Data<PgPool>,
         body: web::Json<AnovaRequest>,
         req: HttpRequest,
     ) -> Result<HttpResponse, AppError> {
-        let _user = req
+        let user = req
             .extensions()
             .get::<AuthenticatedUser>()
             .cloned()
             .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
 
-        let result = StatisticalAnalysis::one_way_anova(&body.groups);
+        let alpha = body.alpha.unwrap_or(0.05);
+        if !(alpha > 0.0 && alpha < 1.0) {
+            return Err(AppError::Validation(
+                "alpha must be between 0 and 1".to_string(),
+            ));
+        }
+
+        let mut result = StatisticalAnalysis::one_way_anova(&body.groups);
+        if body.diagnostics {
+            result.diagnostics = Some(AssumptionDiagnostics::one_way(&body.groups, body.is_percentage));
+        }
+        let is_significant = result.source_between.p.map(|p| p < alpha).unwrap_or(false);
 
         // Calculate LSD if significant
         let lsd_comparisons = if result.is_significant_05 {
@@ -274,13 +549,879 @@ mod analysis_handler {
             None
         };
 
-        Ok(HttpResponse::Ok().json(serde_json::json!({
+        // Mean separation with letters, only after a significant F-test
+        let mean_separation = if is_significant {
+            Some(StatisticalAnalysis::mean_separation(
+                body.post_hoc,
+                &result.group_means,
+                &result.group_sizes,
+                result.source_within.ms,
+                result.source_within.df as f64,
+                alpha,
+            ))
+        } else {
+            None
+        };
+
+        let mut response = serde_json::json!({
             "anova": result,
-            "lsd_comparisons": lsd_comparisons
-        })))
+            "lsd_comparisons": lsd_comparisons,
+            "mean_separation": mean_separation
+        });
+
+        if let Some(project_id) = body.project_id {
+            let run = NewAnalysisRun::from_request(project_id, "anova", &*body, &response);
+            let stored = AnalysisResultService::record(pool.get_ref(), run, user.user_id()?).await?;
+            response["analysis_result_id"] = serde_json::json!(stored.id);
+        }
+
+        Ok(HttpResponse::Ok().json(response))
     }
 
-    #[derive(Debug, serde::Deserialize)]
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    pub struct RcbdAnovaRequest {
+        /// Rows are treatments, columns are blocks (replications); `null` marks a missing plot
+        pub data: Vec<Vec<Option<f64>>>,
+        /// Store the run under this project
+        pub project_id: Option<uuid::Uuid>,
+    }
+
+    pub async fn rcbd_anova(
+        pool: web::Data<PgPool>,
+        body: web::Json<RcbdAnovaRequest>,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, AppError> {
+        let user = req
+            .extensions()
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+        let blocks = body.data.iter().map(|row| row.len()).max().unwrap_or(0);
+        if body.data.len() < 2 || blocks < 2 {
+            return Err(AppError::Validation(
+                "RCBD analysis needs at least 2 treatments and 2 blocks".to_string(),
+            ));
+        }
+        if body.data.iter().any(|row| row.len() != blocks) {
+            return Err(AppError::Validation(
+                "Every treatment needs one entry per block; use null for a missing plot".to_string(),
+            ));
+        }
+        if body.data.iter().any(|row| row.iter().all(|v| v.is_none())) {
+            return Err(AppError::Validation(
+                "Every treatment needs at least one observed plot".to_string(),
+            ));
+        }
+        if (0..blocks).any(|j| body.data.iter().all(|row| row[j].is_none())) {
+            return Err(AppError::Validation(
+                "Every block needs at least one observed plot".to_string(),
+            ));
+        }
+
+        let result = StatisticalAnalysis::rcbd_anova(&body.data);
+
+        respond_with_request_run(pool.get_ref(), &user, body.project_id, "rcbd_anova", &*body, result).await
+    }
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    pub struct SplitPlotAnovaRequest {
+        /// Indexed as `[main_plot][sub_plot][replication]`; the layout must be balanced
+        pub data: Vec<Vec<Vec<f64>>>,
+        /// Store the run under this project
+        pub project_id: Option<uuid::Uuid>,
+    }
+
+    pub async fn split_plot_anova(
+        pool: web::Data<PgPool>,
+        body: web::Json<SplitPlotAnovaRequest>,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, AppError> {
+        let user = req
+            .extensions()
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+        let sub_levels = body.data.first().map(|m| m.len()).unwrap_or(0);
+        let reps = body
+            .data
+            .first()
+            .and_then(|m| m.first())
+            .map(|s| s.len())
+            .unwrap_or(0);
+        if body.data.len() < 2 || sub_levels < 2 || reps < 2 {
+            return Err(AppError::Validation(
+                "Split-plot analysis needs at least 2 main-plot levels, 2 sub-plot levels and 2 replications".to_string(),
+            ));
+        }
+        let balanced = body
+            .data
+            .iter()
+            .all(|m| m.len() == sub_levels && m.iter().all(|s| s.len() == reps));
+        if !balanced {
+            return Err(AppError::Validation(
+                "Every main-plot × sub-plot combination must have the same number of replications".to_string(),
+            ));
+        }
+
+        let result = StatisticalAnalysis::split_plot_anova(&body.data);
+
+        respond_with_request_run(pool.get_ref(), &user, body.project_id, "split_plot_anova", &*body, result).await
+    }
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    pub struct GlmRequest {
+        #[serde(flatten)]
+        pub model: GlmModel,
+        /// Store the run under this project
+        pub project_id: Option<uuid::Uuid>,
+    }
+
+    // The default model fits every factor subset and the least-squares means
+    // average over the full grid of factor levels, so both are bounded.
+    const GLM_MAX_FACTORS: usize = 6;
+    const GLM_MAX_LEVEL_CELLS: usize = 10_000;
+
+    pub async fn glm_analysis(
+        pool: web::Data<PgPool>,
+        body: web::Json<GlmRequest>,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, AppError> {
+        let user = req
+            .extensions()
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+        let model = &body.model;
+        let n = model.response.len();
+        if n < 3 {
+            return Err(AppError::Validation(
+                "GLM analysis needs at least 3 observations".to_string(),
+            ));
+        }
+        if model.factors.is_empty() && model.covariates.is_empty() {
+            return Err(AppError::Validation(
+                "At least one factor or covariate is required".to_string(),
+            ));
+        }
+        if model.factors.iter().any(|f| f.levels.len() != n)
+            || model.covariates.iter().any(|c| c.values.len() != n)
+        {
+            return Err(AppError::Validation(
+                "Every factor and covariate needs one value per observation".to_string(),
+            ));
+        }
+        if model.factors.len() > GLM_MAX_FACTORS {
+            return Err(AppError::Validation(format!(
+                "GLM analysis supports at most {} factors",
+                GLM_MAX_FACTORS
+            )));
+        }
+        let cells = model.factors.iter().try_fold(1usize, |cells, f| {
+            let levels = f.levels.iter().collect::<std::collections::HashSet<_>>().len();
+            cells.checked_mul(levels).filter(|c| *c <= GLM_MAX_LEVEL_CELLS)
+        });
+        if cells.is_none() {
+            return Err(AppError::Validation(format!(
+                "The factor levels form more than {} combinations",
+                GLM_MAX_LEVEL_CELLS
+            )));
+        }
+
+        let names: Vec<&str> = model
+            .factors
+            .iter()
+            .map(|f| f.name.as_str())
+            .chain(model.covariates.iter().map(|c| c.name.as_str()))
+            .collect();
+        if names.iter().enumerate().any(|(i, name)| names[..i].contains(name)) {
+            return Err(AppError::Validation(
+                "Factor and covariate names must be unique".to_string(),
+            ));
+        }
+        if let Some(unknown) = model.terms.iter().flatten().find(|t| !names.contains(&t.as_str())) {
+            return Err(AppError::Validation(format!(
+                "Model term refers to unknown variable '{}'",
+                unknown
+            )));
+        }
+
+        let result = StatisticalAnalysis::glm(model);
+
+        respond_with_request_run(pool.get_ref(), &user, body.project_id, "glm", &*body, result).await
+    }
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    pub struct DunnettRequest {
+        pub project_id: uuid::Uuid,
+        pub parameter_id: uuid::Uuid,
+        pub session_id: uuid::Uuid,
+        /// `two_sided` (default), `greater` or `less` than the control
+        #[serde(default)]
+        pub alternative: DunnettAlternative,
+        pub alpha: Option<f64>,
+        /// Keep values flagged as outliers
+        #[serde(default)]
+        pub include_outliers: bool,
+    }
+
+    pub async fn dunnett_analysis(
+        pool: web::Data<PgPool>,
+        body: web::Json<DunnettRequest>,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, AppError> {
+        let user = req
+            .extensions()
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+        let alpha = body.alpha.unwrap_or(0.05);
+        if !(alpha > 0.0 && alpha < 1.0) {
+            return Err(AppError::Validation(
+                "alpha must be between 0 and 1".to_string(),
+            ));
+        }
+
+        let mut snapshot = AnalysisResultService::begin_snapshot(pool.get_ref()).await?;
+        let plot_means = StatisticalAnalysis::fetch_treatment_plot_means(
+            &mut snapshot,
+            body.project_id,
+            body.parameter_id,
+            body.session_id,
+            body.include_outliers,
+        )
+        .await?;
+
+        // Plots of every control treatment are pooled into one control group
+        let (controls, treatments): (Vec<_>, Vec<_>) = plot_means.into_iter().partition(|t| t.is_control);
+        if controls.is_empty() {
+            return Err(AppError::Validation(
+                "No control plot with data for this parameter and session".to_string(),
+            ));
+        }
+        if treatments.is_empty() {
+            return Err(AppError::Validation(
+                "No treatment plot with data for this parameter and session".to_string(),
+            ));
+        }
+
+        let control_values: Vec<f64> = controls.iter().flat_map(|t| t.values.iter().copied()).collect();
+        let treatment_values: Vec<Vec<f64>> = treatments.iter().map(|t| t.values.clone()).collect();
+        let n_total = control_values.len() + treatment_values.iter().map(|t| t.len()).sum::<usize>();
+        if n_total <= treatment_values.len() + 1 {
+            return Err(AppError::Validation(
+                "Dunnett's test needs replicate plots to estimate the error variance".to_string(),
+            ));
+        }
+
+        let result = StatisticalAnalysis::dunnett_test(&control_values, &treatment_values, body.alternative, alpha);
+
+        let response = serde_json::json!({
+            "control_blocks": controls.iter().flat_map(|t| &t.block_codes).collect::<Vec<_>>(),
+            "treatments": treatments.iter().map(|t| &t.treatment).collect::<Vec<_>>(),
+            "dunnett": result
+        });
+
+        let mut run = NewAnalysisRun::new(body.project_id, "dunnett", &*body, &response);
+        run.parameter_ids = vec![body.parameter_id];
+        run.session_ids = vec![body.session_id];
+        let stored = save_run(pool.get_ref(), &user, snapshot, run).await?;
+
+        Ok(HttpResponse::Ok().json(ApiResponse::success_with_message(response, &saved_message(&stored))))
+    }
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    #[serde(tag = "test", rename_all = "snake_case")]
+    pub enum NonparametricTest {
+        KruskalWallis { groups: Vec<Vec<f64>> },
+        MannWhitney { group1: Vec<f64>, group2: Vec<f64> },
+        Wilcoxon { group1: Vec<f64>, group2: Vec<f64> },
+        /// Rows are treatments, columns are blocks; every cell must be present
+        Friedman { data: Vec<Vec<f64>> },
+    }
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    pub struct NonparametricRequest {
+        #[serde(flatten)]
+        pub test: NonparametricTest,
+        /// Store the run under this project
+        pub project_id: Option<uuid::Uuid>,
+    }
+
+    pub async fn nonparametric_analysis(
+        pool: web::Data<PgPool>,
+        body: web::Json<NonparametricRequest>,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, AppError> {
+        let user = req
+            .extensions()
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+        let result = match &body.test {
+            NonparametricTest::KruskalWallis { groups } => {
+                if groups.iter().filter(|g| !g.is_empty()).count() < 2 {
+                    return Err(AppError::Validation(
+                        "Kruskal-Wallis needs at least 2 non-empty groups".to_string(),
+                    ));
+                }
+                serde_json::to_value(StatisticalAnalysis::kruskal_wallis(groups))
+            }
+            NonparametricTest::MannWhitney { group1, group2 } => {
+                if group1.is_empty() || group2.is_empty() {
+                    return Err(AppError::Validation(
+                        "Mann-Whitney U needs two non-empty groups".to_string(),
+                    ));
+                }
+                serde_json::to_value(StatisticalAnalysis::mann_whitney(group1, group2))
+            }
+            NonparametricTest::Wilcoxon { group1, group2 } => {
+                if group1.len() != group2.len() || group1.is_empty() {
+                    return Err(AppError::Validation(
+                        "Wilcoxon signed-rank needs two paired groups of equal length".to_string(),
+                    ));
+                }
+                serde_json::to_value(StatisticalAnalysis::wilcoxon_signed_rank(group1, group2))
+            }
+            NonparametricTest::Friedman { data } => {
+                let blocks = data.first().map(|row| row.len()).unwrap_or(0);
+                if data.len() < 2 || blocks < 2 || data.iter().any(|row| row.len() != blocks) {
+                    return Err(AppError::Validation(
+                        "Friedman test needs at least 2 treatments observed in the same 2 or more blocks".to_string(),
+                    ));
+                }
+                serde_json::to_value(StatisticalAnalysis::friedman(data))
+            }
+        }
+        .map_err(|e| AppError::InternalError(e.to_string()))?;
+
+        respond_with_request_run(pool.get_ref(), &user, body.project_id, "nonparametric", &*body, result).await
+    }
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    pub struct RepeatedMeasuresRequest {
+        pub project_id: uuid::Uuid,
+        pub parameter_id: uuid::Uuid,
+    }
+
+    pub async fn repeated_measures_anova(
+        pool: web::Data<PgPool>,
+        body: web::Json<RepeatedMeasuresRequest>,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, AppError> {
+        let user = req
+            .extensions()
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+        let mut snapshot = AnalysisResultService::begin_snapshot(pool.get_ref()).await?;
+        let mut series =
+            StatisticalAnalysis::fetch_repeated_measures(&mut snapshot, body.project_id, body.parameter_id).await?;
+
+        // Treatments whose units all miss a session drop out of the analysis
+        let kept: Vec<usize> = (0..series.groups.len()).filter(|g| !series.data[*g].is_empty()).collect();
+        series.groups = kept.iter().map(|g| series.groups[*g].clone()).collect();
+        series.data = kept.iter().map(|g| series.data[*g].clone()).collect();
+
+        let units: usize = series.data.iter().map(|g| g.len()).sum();
+        if series.times.len() < 2 {
+            return Err(AppError::Validation(
+                "Repeated-measures analysis needs data from at least 2 monitoring sessions".to_string(),
+            ));
+        }
+        if series.groups.is_empty() || units <= series.groups.len() {
+            return Err(AppError::Validation(
+                "Repeated-measures analysis needs more units with complete session data than treatments".to_string(),
+            ));
+        }
+
+        let result = StatisticalAnalysis::repeated_measures_anova(&series.data);
+
+        let response = serde_json::json!({
+            "sessions": series.times,
+            "treatments": series.groups,
+            "excluded_units": series.excluded_units,
+            "anova": result
+        });
+
+        let mut run = NewAnalysisRun::new(body.project_id, "repeated_measures", &*body, &response);
+        run.parameter_ids = vec![body.parameter_id];
+        let stored = save_run(pool.get_ref(), &user, snapshot, run).await?;
+
+        Ok(HttpResponse::Ok().json(ApiResponse::success_with_message(response, &saved_message(&stored))))
+    }
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    pub struct AncovaRequest {
+        pub project_id: uuid::Uuid,
+        pub parameter_id: uuid::Uuid,
+        pub session_id: uuid::Uuid,
+        pub baseline_session_id: uuid::Uuid,
+        /// Baseline parameters used as covariates; defaults to the response parameter
+        #[serde(default)]
+        pub covariate_parameter_ids: Vec<uuid::Uuid>,
+        #[serde(default)]
+        pub blocks: bool,
+    }
+
+    pub async fn ancova_analysis(
+        pool: web::Data<PgPool>,
+        body: web::Json<AncovaRequest>,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, AppError> {
+        let user = req
+            .extensions()
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+        let covariates = if body.covariate_parameter_ids.is_empty() {
+            vec![body.parameter_id]
+        } else {
+            body.covariate_parameter_ids.clone()
+        };
+        let mut snapshot = AnalysisResultService::begin_snapshot(pool.get_ref()).await?;
+        let mut data = StatisticalAnalysis::fetch_ancova_data(
+            &mut snapshot,
+            body.project_id,
+            body.parameter_id,
+            body.session_id,
+            body.baseline_session_id,
+            &covariates,
+        )
+        .await?;
+
+        if body.blocks && data.blocks.is_none() {
+            return Err(AppError::Validation(
+                "Blocked ANCOVA needs a replication number on every plot".to_string(),
+            ));
+        }
+        if !body.blocks {
+            data.blocks = None;
+        }
+
+        let mut treatments = data.treatments.clone();
+        treatments.sort();
+        treatments.dedup();
+        if treatments.len() < 2 || data.response.len() <= treatments.len() + covariates.len() {
+            return Err(AppError::Validation(
+                "ANCOVA needs at least 2 treatments and more units with both baseline and response values than model terms".to_string(),
+            ));
+        }
+
+        let result = StatisticalAnalysis::ancova(&data);
+
+        let mut run = NewAnalysisRun::new(body.project_id, "ancova", &*body, &result);
+        run.parameter_ids = std::iter::once(body.parameter_id).chain(covariates).collect();
+        run.session_ids = vec![body.session_id, body.baseline_session_id];
+        let stored = save_run(pool.get_ref(), &user, snapshot, run).await?;
+
+        Ok(HttpResponse::Ok().json(ApiResponse::success_with_message(result, &saved_message(&stored))))
+    }
+
+    const MAX_RESAMPLES: usize = 200_000;
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    pub struct BootstrapRequest {
+        #[serde(default)]
+        pub statistic: BootstrapStatistic,
+        pub sample: Vec<f64>,
+        /// Second sample for differences and ratios
+        #[serde(default)]
+        pub reference: Vec<f64>,
+        pub resamples: Option<usize>,
+        pub confidence: Option<f64>,
+        /// Stored seed to reproduce an earlier run; a new one is drawn when absent
+        pub seed: Option<u64>,
+        /// Store the run under this project
+        pub project_id: Option<uuid::Uuid>,
+    }
+
+    pub async fn bootstrap_analysis(
+        pool: web::Data<PgPool>,
+        body: web::Json<BootstrapRequest>,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, AppError> {
+        let user = req
+            .extensions()
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+        let resamples = body.resamples.unwrap_or(10_000);
+        let confidence = body.confidence.unwrap_or(0.95);
+        if !(confidence > 0.0 && confidence < 1.0) {
+            return Err(AppError::Validation("confidence must be between 0 and 1".to_string()));
+        }
+        if !(100..=MAX_RESAMPLES).contains(&resamples) {
+            return Err(AppError::Validation(format!(
+                "resamples must be between 100 and {}",
+                MAX_RESAMPLES
+            )));
+        }
+        if body.sample.len() < 2 || (body.statistic != BootstrapStatistic::Mean && body.reference.len() < 2) {
+            return Err(AppError::Validation(
+                "Each sample needs at least 2 observations".to_string(),
+            ));
+        }
+
+        // The stored input carries the seed actually used
+        let mut body = body.into_inner();
+        let seed = *body.seed.get_or_insert_with(rand::random);
+        let (statistic, sample, reference) = (body.statistic, body.sample.clone(), body.reference.clone());
+        let result = web::block(move || {
+            StatisticalAnalysis::bootstrap(statistic, &sample, &reference, resamples, confidence, seed)
+        })
+        .await
+        .map_err(|e| AppError::InternalError(e.to_string()))?;
+
+        respond_with_request_run(pool.get_ref(), &user, body.project_id, "bootstrap", &body, result).await
+    }
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    pub struct PermutationRequest {
+        /// One-way layout: one list of plot values per treatment
+        pub groups: Option<Vec<Vec<f64>>>,
+        /// Blocked layout: rows are treatments, columns are blocks; `null` marks a missing plot
+        pub blocks: Option<Vec<Vec<Option<f64>>>>,
+        pub permutations: Option<usize>,
+        pub seed: Option<u64>,
+        /// Store the run under this project
+        pub project_id: Option<uuid::Uuid>,
+    }
+
+    pub async fn permutation_test(
+        pool: web::Data<PgPool>,
+        body: web::Json<PermutationRequest>,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, AppError> {
+        let user = req
+            .extensions()
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+        let permutations = body.permutations.unwrap_or(10_000);
+        if !(100..=MAX_RESAMPLES).contains(&permutations) {
+            return Err(AppError::Validation(format!(
+                "permutations must be between 100 and {}",
+                MAX_RESAMPLES
+            )));
+        }
+
+        let mut values = Vec::new();
+        let mut treatments = Vec::new();
+        let mut blocks = Vec::new();
+        match (&body.groups, &body.blocks) {
+            (Some(groups), None) => {
+                for (t, group) in groups.iter().enumerate() {
+                    values.extend(group.iter().copied());
+                    treatments.extend(std::iter::repeat_n(t, group.len()));
+                }
+            }
+            (None, Some(rows)) => {
+                for (t, row) in rows.iter().enumerate() {
+                    for (b, value) in row.iter().enumerate() {
+                        if let Some(v) = value {
+                            values.push(*v);
+                            treatments.push(t);
+                            blocks.push(b);
+                        }
+                    }
+                }
+            }
+            _ => {
+                return Err(AppError::Validation(
+                    "Provide either groups or blocks".to_string(),
+                ))
+            }
+        }
+        let mut observed = treatments.clone();
+        observed.sort();
+        observed.dedup();
+        if observed.len() < 2 || values.len() <= observed.len() {
+            return Err(AppError::Validation(
+                "At least 2 treatments and some replication are needed".to_string(),
+            ));
+        }
+
+        // The stored input carries the seed actually used
+        let mut body = body.into_inner();
+        let seed = *body.seed.get_or_insert_with(rand::random);
+        let blocked = body.blocks.is_some();
+        let result = web::block(move || {
+            StatisticalAnalysis::permutation_test(
+                &values,
+                &treatments,
+                blocked.then_some(blocks.as_slice()),
+                permutations,
+                seed,
+            )
+        })
+        .await
+        .map_err(|e| AppError::InternalError(e.to_string()))?;
+
+        respond_with_request_run(pool.get_ref(), &user, body.project_id, "permutation", &body, result).await
+    }
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    pub struct MultiEnvironmentRequest {
+        pub project_ids: Vec<uuid::Uuid>,
+        pub parameter_code: String,
+        /// Session to use in every project; defaults to each project's latest
+        pub days_after_treatment: Option<i32>,
+        #[serde(default)]
+        pub lower_is_better: bool,
+    }
+
+    pub async fn multi_environment_analysis(
+        pool: web::Data<PgPool>,
+        body: web::Json<MultiEnvironmentRequest>,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, AppError> {
+        let user = req
+            .extensions()
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+        let mut snapshot = AnalysisResultService::begin_snapshot(pool.get_ref()).await?;
+        let data = StatisticalAnalysis::fetch_multi_environment_data(
+            &mut snapshot,
+            &body.project_ids,
+            &body.parameter_code,
+            body.days_after_treatment,
+        )
+        .await?;
+
+        let result = StatisticalAnalysis::multi_environment_analysis(&data, body.lower_is_better);
+        if result.environments.len() < 2 || result.treatments.len() < 2 {
+            return Err(AppError::Validation(
+                "Combined analysis needs at least 2 environments sharing at least 2 treatments".to_string(),
+            ));
+        }
+        if result.error.df <= 0 {
+            return Err(AppError::Validation(
+                "Combined analysis needs replicated plots in each environment".to_string(),
+            ));
+        }
+
+        // One run under the first project; the others are recorded as related
+        let mut run = NewAnalysisRun::new(body.project_ids[0], "multi_environment", &*body, &result);
+        run.related_project_ids = body.project_ids[1..].to_vec();
+        let stored = save_run(pool.get_ref(), &user, snapshot, run).await?;
+
+        Ok(HttpResponse::Ok().json(ApiResponse::success_with_message(result, &saved_message(&stored))))
+    }
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    pub struct SeverityRequest {
+        pub project_id: uuid::Uuid,
+        pub parameter_id: uuid::Uuid,
+        #[serde(default)]
+        pub post_hoc: PostHocTest,
+    }
+
+    pub async fn severity_analysis(
+        pool: web::Data<PgPool>,
+        body: web::Json<SeverityRequest>,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, AppError> {
+        let user = req
+            .extensions()
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+        let mut snapshot = AnalysisResultService::begin_snapshot(pool.get_ref()).await?;
+        let dataset =
+            StatisticalAnalysis::fetch_severity_scores(&mut snapshot, body.project_id, body.parameter_id).await?;
+        if dataset.treatments.is_empty() {
+            return Err(AppError::Validation(
+                "No scores with days after treatment recorded for this parameter".to_string(),
+            ));
+        }
+
+        let result = StatisticalAnalysis::severity_analysis(
+            &dataset.scale,
+            &dataset.treatments,
+            dataset.design == Some(ExperimentDesign::Rak),
+            body.post_hoc,
+        )?;
+
+        let mut run = NewAnalysisRun::new(body.project_id, "severity", &*body, &result);
+        run.parameter_ids = vec![body.parameter_id];
+        let stored = save_run(pool.get_ref(), &user, snapshot, run).await?;
+
+        Ok(HttpResponse::Ok().json(ApiResponse::success_with_message(result, &saved_message(&stored))))
+    }
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    pub struct MultivariateRequest {
+        pub project_id: uuid::Uuid,
+        pub session_id: uuid::Uuid,
+        #[serde(default)]
+        pub level: DataLevel,
+        #[serde(default)]
+        pub method: CorrelationMethod,
+    }
+
+    pub async fn multivariate_analysis(
+        pool: web::Data<PgPool>,
+        body: web::Json<MultivariateRequest>,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, AppError> {
+        let user = req
+            .extensions()
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+        let mut snapshot = AnalysisResultService::begin_snapshot(pool.get_ref()).await?;
+        let data =
+            StatisticalAnalysis::fetch_multivariate_data(&mut snapshot, body.project_id, body.session_id, body.level)
+                .await?;
+
+        if data.variables.len() < 2 || data.values.len() < 3 {
+            return Err(AppError::Validation(
+                "Correlation and PCA need at least 2 numeric parameters and 3 complete observations".to_string(),
+            ));
+        }
+
+        let correlation = StatisticalAnalysis::correlation_matrix(&data, body.method);
+        let pca = StatisticalAnalysis::pca(&data);
+
+        let result = serde_json::json!({
+            "level": body.level,
+            "observations": data.labels,
+            "correlation": correlation,
+            "pca": pca
+        });
+
+        let mut run = NewAnalysisRun::new(body.project_id, "multivariate", &*body, &result);
+        run.session_ids = vec![body.session_id];
+        let stored = save_run(pool.get_ref(), &user, snapshot, run).await?;
+
+        Ok(HttpResponse::Ok().json(ApiResponse::success_with_message(result, &saved_message(&stored))))
+    }
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    pub struct GrowthCurveRequest {
+        pub project_id: uuid::Uuid,
+        pub parameter_id: uuid::Uuid,
+        #[serde(default)]
+        pub model: GrowthModel,
+        #[serde(default)]
+        pub post_hoc: PostHocTest,
+    }
+
+    pub async fn growth_curves(
+        pool: web::Data<PgPool>,
+        body: web::Json<GrowthCurveRequest>,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, AppError> {
+        let user = req
+            .extensions()
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+        let mut snapshot = AnalysisResultService::begin_snapshot(pool.get_ref()).await?;
+        let series =
+            StatisticalAnalysis::fetch_growth_series(&mut snapshot, body.project_id, body.parameter_id).await?;
+
+        let sessions = series
+            .iter()
+            .flat_map(|t| t.plots.iter().flat_map(|plot| plot.points.iter().map(|p| p.0 as i64)))
+            .collect::<std::collections::BTreeSet<_>>()
+            .len();
+        if sessions < 4 {
+            return Err(AppError::Validation(
+                "Growth curve fitting needs data from at least 4 sessions with days after treatment".to_string(),
+            ));
+        }
+
+        let result = StatisticalAnalysis::growth_curve_analysis(body.model, &series, body.post_hoc);
+
+        let mut run = NewAnalysisRun::new(body.project_id, "growth_curve", &*body, &result);
+        run.parameter_ids = vec![body.parameter_id];
+        let stored = save_run(pool.get_ref(), &user, snapshot, run).await?;
+
+        Ok(HttpResponse::Ok().json(ApiResponse::success_with_message(result, &saved_message(&stored))))
+    }
+
+    fn default_yield_predictors() -> Vec<MonitoringType> {
+        vec![MonitoringType::Height, MonitoringType::Chlorophyll, MonitoringType::LeafArea]
+    }
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    pub struct YieldPredictionRequest {
+        pub project_id: uuid::Uuid,
+        #[serde(default = "default_yield_predictors")]
+        pub predictors: Vec<MonitoringType>,
+        /// Use sessions up to this day after treatment; defaults to the latest one
+        pub as_of_day: Option<i32>,
+        pub confidence_level: Option<f64>,
+    }
+
+    pub async fn yield_prediction(
+        pool: web::Data<PgPool>,
+        body: web::Json<YieldPredictionRequest>,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, AppError> {
+        let user = req
+            .extensions()
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+        let confidence_level = body.confidence_level.unwrap_or(0.95);
+        if !(confidence_level > 0.0 && confidence_level < 1.0) {
+            return Err(AppError::Validation(
+                "confidence_level must be between 0 and 1".to_string(),
+            ));
+        }
+        if body.predictors.is_empty()
+            || body.predictors.contains(&MonitoringType::Yield)
+            || (1..body.predictors.len()).any(|i| body.predictors[..i].contains(&body.predictors[i]))
+        {
+            return Err(AppError::Validation(
+                "Predictors must be distinct, non-yield monitoring types".to_string(),
+            ));
+        }
+
+        let mut snapshot = AnalysisResultService::begin_snapshot(pool.get_ref()).await?;
+        let data = StatisticalAnalysis::fetch_yield_prediction_data(
+            &mut snapshot,
+            body.project_id,
+            &body.predictors,
+            body.as_of_day,
+        )
+        .await?;
+        if data.training_projects.is_empty() {
+            return Err(AppError::Validation(
+                "No completed projects of the same crop to train on".to_string(),
+            ));
+        }
+
+        let result = StatisticalAnalysis::yield_prediction(&data, confidence_level).ok_or_else(|| {
+            AppError::Validation(
+                "Too few training plots with both predictor and yield data to fit the model".to_string(),
+            )
+        })?;
+
+        let run = NewAnalysisRun::new(body.project_id, "yield_prediction", &*body, &result);
+        let stored = save_run(pool.get_ref(), &user, snapshot, run).await?;
+
+        Ok(HttpResponse::Ok().json(ApiResponse::success_with_message(result, &saved_message(&stored))))
+    }
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
     pub struct AIAnalysisRequest {
         pub project_id: uuid::Uuid,
         pub analysis_type: String,
@@ -292,7 +1433,7 @@ mod analysis_handler {
         body: web::Json<AIAnalysisRequest>,
         req: HttpRequest,
     ) -> Result<HttpResponse, AppError> {
-        let _user = req
+        let user = req
             .extensions()
             .get::<AuthenticatedUser>()
             .cloned()
@@ -301,14 +1442,27 @@ mod analysis_handler {
         let ai_service = AIAnalysisService::new(&settings)
             .ok_or_else(|| AppError::AIError("OpenAI API not configured".to_string()))?;
 
+        // Hash the summary's data and close the snapshot before the slow model call
+        let mut snapshot = AnalysisResultService::begin_snapshot(pool.get_ref()).await?;
+        let summary = ai_service.fetch_monitoring_summary(&mut snapshot, body.project_id).await?;
+        let mut run = NewAnalysisRun::new(body.project_id, "ai", &*body, &serde_json::Value::Null);
+        AnalysisResultService::seal_snapshot(snapshot, &mut run).await?;
+
         let result = ai_service
-            .analyze_experiment_data(pool.get_ref(), body.project_id, &body.analysis_type)
+            .analyze_experiment_data(pool.get_ref(), body.project_id, &summary, &body.analysis_type)
             .await?;
 
-        Ok(HttpResponse::Ok().json(ApiResponse::success(result)))
+        run.results = serde_json::to_value(&result).unwrap_or(serde_json::Value::Null);
+        run.analysis_name = Some(body.analysis_type.clone());
+        run.ai_model_used = Some(ai_service.model().to_string());
+        run.ai_insights = Some(result.insights.clone());
+        run.ai_recommendations = Some(result.recommendations.clone());
+        let stored = AnalysisResultService::record(pool.get_ref(), run, user.user_id()?).await?;
+
+        Ok(HttpResponse::Ok().json(ApiResponse::success_with_message(result, &saved_message(&stored))))
     }
 
-    #[derive(Debug, serde::Deserialize)]
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
     pub struct CostBenefitRequest {
         pub project_id: uuid::Uuid,
         pub crop_price_per_kg: rust_decimal::Decimal,
@@ -319,20 +1473,242 @@ mod analysis_handler {
         body: web::Json<CostBenefitRequest>,
         req: HttpRequest,
     ) -> Result<HttpResponse, AppError> {
-        let _user = req
+        let user = req
             .extensions()
             .get::<AuthenticatedUser>()
             .cloned()
             .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
 
+        let mut snapshot = AnalysisResultService::begin_snapshot(pool.get_ref()).await?;
         let result = CostBenefitAnalysis::analyze(
-            pool.get_ref(),
+            &mut snapshot,
             body.project_id,
             body.crop_price_per_kg,
         )
         .await?;
 
-        Ok(HttpResponse::Ok().json(ApiResponse::success(result)))
+        let run = NewAnalysisRun::new(body.project_id, "cost_benefit", &*body, &result);
+        let stored = save_run(pool.get_ref(), &user, snapshot, run).await?;
+
+        Ok(HttpResponse::Ok().json(ApiResponse::success_with_message(result, &saved_message(&stored))))
+    }
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    pub struct DecisionRequest {
+        /// Projects to pool; each one is a site
+        pub project_ids: Vec<uuid::Uuid>,
+        pub crop_price_per_kg: rust_decimal::Decimal,
+        #[serde(default)]
+        pub method: DecisionMethod,
+        /// Criteria to rank on; defaults to all of them
+        #[serde(default)]
+        pub criteria: Vec<DecisionCriterion>,
+        /// Direct weights aligned with the criteria; equal when omitted
+        pub weights: Option<Vec<f64>>,
+        /// AHP pairwise comparison matrix aligned with the criteria; overrides `weights`
+        pub pairwise: Option<Vec<Vec<f64>>>,
+    }
+
+    pub async fn decision_support(
+        pool: web::Data<PgPool>,
+        body: web::Json<DecisionRequest>,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, AppError> {
+        let user = req
+            .extensions()
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+        if body.project_ids.is_empty() {
+            return Err(AppError::Validation("At least one project is required".to_string()));
+        }
+        let criteria = if body.criteria.is_empty() {
+            DecisionCriterion::ALL.to_vec()
+        } else {
+            body.criteria.clone()
+        };
+        if (1..criteria.len()).any(|i| criteria[..i].contains(&criteria[i])) {
+            return Err(AppError::Validation("Each criterion may only be listed once".to_string()));
+        }
+
+        let weights = match &body.pairwise {
+            Some(pairwise) => DecisionSupport::ahp_weights(&criteria, pairwise)?,
+            None => DecisionSupport::direct_weights(&criteria, body.weights.as_deref())?,
+        };
+
+        let mut snapshot = AnalysisResultService::begin_snapshot(pool.get_ref()).await?;
+        let alternatives =
+            DecisionSupport::fetch_alternatives(&mut snapshot, &body.project_ids, body.crop_price_per_kg).await?;
+        if alternatives.len() < 2 {
+            return Err(AppError::Validation(
+                "Ranking needs at least 2 formulas with yield data against a control".to_string(),
+            ));
+        }
+
+        let result = DecisionSupport::analyze(&alternatives, weights, body.method);
+
+        // One run under the first project; the others are recorded as related
+        let mut run = NewAnalysisRun::new(body.project_ids[0], "decision", &*body, &result);
+        run.related_project_ids = body.project_ids[1..].to_vec();
+        let stored = save_run(pool.get_ref(), &user, snapshot, run).await?;
+
+        Ok(HttpResponse::Ok().json(ApiResponse::success_with_message(
+            serde_json::json!({
+                "alternatives": alternatives,
+                "analysis": result
+            }),
+            &saved_message(&stored),
+        )))
+    }
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    pub struct PowerPlanRequest {
+        pub crop_type: Option<String>,
+        pub parameter_code: Option<String>,
+        pub parameter_type: Option<MonitoringType>,
+        pub design: ExperimentDesign,
+        pub treatments_count: usize,
+        #[serde(default)]
+        pub factor_levels: Vec<usize>,
+        pub detectable_difference_percent: f64,
+        pub power: Option<f64>,
+        pub alpha: Option<f64>,
+        pub cv_percent: Option<f64>, // overrides the historical estimate
+        /// Store the plan under this project
+        pub project_id: Option<uuid::Uuid>,
+    }
+
+    pub async fn power_plan(
+        pool: web::Data<PgPool>,
+        body: web::Json<PowerPlanRequest>,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, AppError> {
+        let user = req
+            .extensions()
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+        let alpha = body.alpha.unwrap_or(0.05);
+        let target_power = body.power.unwrap_or(0.8);
+        if !matches!(body.design, ExperimentDesign::Ral | ExperimentDesign::Rak | ExperimentDesign::Factorial) {
+            return Err(AppError::Validation(
+                "Replication planning supports RAL, RAK and factorial designs".to_string(),
+            ));
+        }
+        if body.treatments_count < 2 {
+            return Err(AppError::Validation("At least 2 treatments are required".to_string()));
+        }
+        if body.design == ExperimentDesign::Factorial
+            && (body.factor_levels.len() < 2
+                || body.factor_levels.iter().any(|l| *l < 2)
+                || body.factor_levels.iter().product::<usize>() != body.treatments_count)
+        {
+            return Err(AppError::Validation(
+                "Factorial designs need factor_levels (each at least 2) whose product equals treatments_count"
+                    .to_string(),
+            ));
+        }
+        if body.detectable_difference_percent <= 0.0
+            || !(alpha > 0.0 && alpha < 1.0)
+            || !(target_power > 0.0 && target_power < 1.0)
+        {
+            return Err(AppError::Validation(
+                "detectable_difference_percent must be positive; alpha and power must lie between 0 and 1".to_string(),
+            ));
+        }
+
+        let historical = match &body.crop_type {
+            Some(crop) if body.parameter_code.is_some() || body.parameter_type.is_some() => {
+                StatisticalAnalysis::historical_cv(
+                    pool.get_ref(),
+                    crop,
+                    body.parameter_code.as_deref(),
+                    body.parameter_type.clone(),
+                    body.project_id,
+                )
+                .await?
+            }
+            _ => Vec::new(),
+        };
+
+        // Median of plot-level estimates, falling back to unit-level ones
+        let historical_cv = ["plot", "unit"].iter().find_map(|level| {
+            let mut cvs: Vec<f64> = historical.iter().filter(|h| h.level == *level).map(|h| h.cv_percent).collect();
+            if cvs.is_empty() {
+                return None;
+            }
+            cvs.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            let mid = cvs.len() / 2;
+            Some(if cvs.len().is_multiple_of(2) { (cvs[mid - 1] + cvs[mid]) / 2.0 } else { cvs[mid] })
+        });
+        let cv_percent = body.cv_percent.or(historical_cv).filter(|cv| *cv > 0.0).ok_or_else(|| {
+            AppError::Validation(
+                "No past projects of this crop and parameter have usable data; provide cv_percent".to_string(),
+            )
+        })?;
+
+        let plan = StatisticalAnalysis::replication_plan(
+            body.design.clone(),
+            body.treatments_count,
+            &body.factor_levels,
+            cv_percent,
+            body.detectable_difference_percent,
+            alpha,
+            target_power,
+        );
+
+        let response = serde_json::json!({
+            "historical_cv": historical,
+            "plan": plan
+        });
+
+        respond_with_request_run(pool.get_ref(), &user, body.project_id, "power_plan", &*body, response).await
+    }
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize)]
+    pub struct DoseResponseRequest {
+        pub project_id: uuid::Uuid,
+        pub parameter_id: Option<uuid::Uuid>,
+        /// Session to read; every session is averaged when absent
+        pub session_id: Option<uuid::Uuid>,
+        pub crop_price_per_kg: rust_decimal::Decimal,
+    }
+
+    pub async fn dose_response(
+        pool: web::Data<PgPool>,
+        body: web::Json<DoseResponseRequest>,
+        req: HttpRequest,
+    ) -> Result<HttpResponse, AppError> {
+        let user = req
+            .extensions()
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Authentication("Not authenticated".to_string()))?;
+
+        let mut snapshot = AnalysisResultService::begin_snapshot(pool.get_ref()).await?;
+        let result = CostBenefitAnalysis::dose_response(
+            &mut snapshot,
+            body.project_id,
+            body.parameter_id,
+            body.session_id,
+            body.crop_price_per_kg,
+        )
+        .await?;
+
+        if result.models.is_empty() {
+            return Err(AppError::Validation(
+                "Dose-response fitting needs at least 3 distinct numeric application rates with data".to_string(),
+            ));
+        }
+
+        let mut run = NewAnalysisRun::new(body.project_id, "dose_response", &*body, &result);
+        run.parameter_ids = body.parameter_id.into_iter().collect();
+        run.session_ids = body.session_id.into_iter().collect();
+        let stored = save_run(pool.get_ref(), &user, snapshot, run).await?;
+
+        Ok(HttpResponse::Ok().json(ApiResponse::success_with_message(result, &saved_message(&stored))))
     }
 }
 
@@ -345,6 +1721,7 @@ mod report_handler {
     use crate::auth::AuthenticatedUser;
     use crate::errors::AppError;
     use crate::models::ApiResponse;
+    use crate::analysis::PostHocTest;
     use crate::reports::{ReportGenerator, ReportSection, ReportType};
     use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
     use sqlx::PgPool;
@@ -355,6 +1732,9 @@ mod report_handler {
         pub report_type: String,
         pub sections: Option<Vec<String>>,
         pub include_ai_insights: Option<bool>,
+        /// Mean separation test for the statistical report (default LSD)
+        #[serde(default)]
+        pub post_hoc: PostHocTest,
     }
 
     pub async fn generate_report(
@@ -434,6 +1814,7 @@ mod report_handler {
                 report_type,
                 &sections,
                 ai_insights,
+                body.post_hoc,
             )
             .await?;
 
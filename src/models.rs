@@ -322,6 +322,8 @@ pub struct ExperimentalBlock {
     pub plant_count: Option<i32>,
     pub qr_code_data: Option<String>,
     pub qr_code_generated_at: Option<DateTime<Utc>>,
+    pub replication: Option<i32>,
+    pub factor_levels: Option<serde_json::Value>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -344,6 +346,26 @@ pub struct ExperimentalUnit {
     pub created_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct FieldLayout {
+    pub project_id: Uuid,
+    pub experiment_design: ExperimentDesign,
+    pub seed: i64,
+    pub replications: i32,
+    pub units_per_plot: i32,
+    pub treatments: serde_json::Value,
+    pub generated_by: Uuid,
+    pub generated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayoutTreatment {
+    pub formula_id: Option<Uuid>,
+    pub description: String,
+    pub is_control: bool,
+    pub factor_levels: serde_json::Value,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct MonitoringParameter {
     pub id: Uuid,
@@ -363,6 +385,8 @@ pub struct MonitoringParameter {
     pub is_required: bool,
     pub is_active: bool,
     pub created_at: DateTime<Utc>,
+    #[sqlx(default)]
+    pub severity_scale: Option<serde_json::Value>, // scoring scale of pest/disease parameters
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -395,6 +419,7 @@ pub struct MonitoringData {
     pub boolean_value: Option<bool>,
     pub is_outlier: bool,
     pub outlier_reason: Option<String>,
+    pub outlier_source: Option<String>, // scan, manual
     pub is_verified: bool,
     pub verified_by: Option<Uuid>,
     pub verified_at: Option<DateTime<Utc>>,
@@ -424,6 +449,16 @@ pub struct AnalysisResult {
     pub generated_by: Uuid,
     pub is_valid: bool,
     pub invalidated_reason: Option<String>,
+    pub version: i32,
+    pub engine_version: Option<String>,
+    pub data_hash: Option<String>,
+    pub related_project_ids: Vec<Uuid>,
+    pub parameter_ids: Vec<Uuid>,
+    pub session_ids: Vec<Uuid>,
+    pub is_stale: bool,
+    pub stale_since: Option<DateTime<Utc>>,
+    pub invalidated_at: Option<DateTime<Utc>>,
+    pub invalidated_by: Option<Uuid>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -615,6 +650,31 @@ pub struct BatchMonitoringDataRequest {
     pub data: Vec<CreateMonitoringDataRequest>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerateLayoutRequest {
+    #[serde(default)]
+    pub formula_ids: Vec<Uuid>,
+    pub include_control: Option<bool>, // defaults to true for RAL/RAK
+    #[serde(default)]
+    pub factors: Vec<LayoutFactor>, // factorial designs only
+    pub replications: Option<i32>, // defaults to the project's replications
+    pub units_per_plot: Option<i32>,
+    pub seed: Option<i64>, // random when omitted
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayoutFactor {
+    pub name: String,
+    pub levels: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeneratedLayout {
+    pub layout: FieldLayout,
+    pub blocks: Vec<ExperimentalBlock>,
+    pub units_count: i64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnalysisRequest {
     pub project_id: Uuid,
@@ -749,6 +809,22 @@ pub struct SubmitMonitoringDataRequest {
     pub observation_notes: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutlierScanResult {
+    pub session_id: Uuid,
+    pub scanned: usize,
+    pub flagged: Vec<FlaggedValue>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlaggedValue {
+    pub data_id: Uuid,
+    pub unit_id: Uuid,
+    pub parameter_id: Uuid,
+    pub value: f64,
+    pub reason: String,
+}
+
 // ==============================================================================
 // ADDITIONAL DTOs FOR HANDLERS
 // ==============================================================================
@@ -768,3 +844,72 @@ pub struct QCDecisionRequest {
 pub struct CreateFormulaVersionRequest {
     pub version: String,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewAnalysisRun {
+    pub project_id: Uuid,
+    pub analysis_type: String,
+    pub analysis_name: Option<String>,
+    pub input_parameters: serde_json::Value,
+    pub results: serde_json::Value,
+    pub related_project_ids: Vec<Uuid>, // Further projects read by cross-project runs
+    pub parameter_ids: Vec<Uuid>,       // Empty when the run read every parameter
+    pub session_ids: Vec<Uuid>,         // Empty when the run read every session
+    pub data_hash: Option<String>,      // Set from the read snapshot; None for request-body input, never stale
+    pub ai_model_used: Option<String>,
+    pub ai_prompt: Option<String>,
+    pub ai_insights: Option<String>,
+    pub ai_recommendations: Option<String>,
+}
+
+impl NewAnalysisRun {
+    pub fn new<I: Serialize, R: Serialize>(
+        project_id: Uuid,
+        analysis_type: &str,
+        input: &I,
+        results: &R,
+    ) -> Self {
+        Self {
+            project_id,
+            analysis_type: analysis_type.to_string(),
+            analysis_name: None,
+            input_parameters: serde_json::to_value(input).unwrap_or(serde_json::Value::Null),
+            results: serde_json::to_value(results).unwrap_or(serde_json::Value::Null),
+            related_project_ids: Vec::new(),
+            parameter_ids: Vec::new(),
+            session_ids: Vec::new(),
+            data_hash: None,
+            ai_model_used: None,
+            ai_prompt: None,
+            ai_insights: None,
+            ai_recommendations: None,
+        }
+    }
+
+    /// A run on values posted in the request rather than read from the project
+    pub fn from_request<I: Serialize, R: Serialize>(
+        project_id: Uuid,
+        analysis_type: &str,
+        input: &I,
+        results: &R,
+    ) -> Self {
+        Self::new(project_id, analysis_type, input, results)
+    }
+
+    /// Every project whose data the run read
+    pub fn project_ids(&self) -> Vec<Uuid> {
+        std::iter::once(self.project_id).chain(self.related_project_ids.iter().copied()).collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisResultQuery {
+    pub analysis_type: Option<String>,
+    pub include_invalid: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct InvalidateAnalysisRequest {
+    #[validate(length(min = 1, message = "A reason is required"))]
+    pub reason: String,
+}
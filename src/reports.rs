@@ -1,7 +1,10 @@
 use crate::config::Settings;
 use crate::errors::AppError;
 use crate::models::*;
-use crate::analysis::{StatisticalAnalysis, DescriptiveStats, AnovaResult};
+use crate::analysis::{
+    StatisticalAnalysis, DescriptiveStats, AnovaResult, AnovaSource, CorrelationMethod, GrowthModel,
+    GrowthPlotData, GrowthTreatmentData, MultivariateData, PlotSeverity, PostHocTest, SeverityAnova, SeverityPlotData, SeverityScale, SeverityTreatmentData,
+};
 use chrono::{NaiveDate, Utc};
 use printpdf::*;
 use serde::{Serialize, Deserialize};
@@ -33,6 +36,7 @@ impl ReportGenerator {
         report_type: ReportType,
         sections: &[ReportSection],
         ai_insights: Option<String>,
+        post_hoc: PostHocTest,
     ) -> Result<GeneratedReport, AppError> {
         // Fetch project data
         let project = self.fetch_project_data(pool, project_id).await?;
@@ -46,7 +50,7 @@ impl ReportGenerator {
                 self.generate_full_report(&project, sections, ai_insights.as_deref()).await?
             }
             ReportType::StatisticalAnalysis => {
-                self.generate_statistical_report(&project).await?
+                self.generate_statistical_report(&project, post_hoc).await?
             }
             ReportType::QCReport => {
                 self.generate_qc_report(&project).await?
@@ -144,7 +148,8 @@ impl ReportGenerator {
                 id, project_id, block_code, block_name, formula_id,
                 treatment_description, is_control, position_row, position_column,
                 area_size, area_unit, plant_count, qr_code_data,
-                qr_code_generated_at, created_at, updated_at
+                qr_code_generated_at, replication, factor_levels,
+                created_at, updated_at
             FROM experimental_blocks WHERE project_id = $1
             ORDER BY block_code
             "#
@@ -179,7 +184,7 @@ impl ReportGenerator {
                 parameter_type,
                 data_type, unit, custom_unit,
                 min_value, max_value, decimal_places, outlier_threshold_percent,
-                sort_order, is_required, is_active, created_at
+                sort_order, is_required, is_active, created_at, severity_scale
             FROM monitoring_parameters WHERE project_id = $1 AND is_active = true
             ORDER BY sort_order
             "#
@@ -229,6 +234,9 @@ impl ReportGenerator {
             JOIN monitoring_parameters mp ON md.parameter_id = mp.id
             JOIN monitoring_sessions ms ON md.session_id = ms.id
             WHERE eb.project_id = $1
+            AND eu.excluded_reason IS NULL
+            AND COALESCE(eu.is_active, true)
+            AND NOT COALESCE(md.is_outlier, false)
             GROUP BY mp.id, mp.name, mp.code, eb.id, eb.block_code, eb.is_control, 
                      ms.session_code, ms.days_after_treatment
             ORDER BY mp.code, ms.days_after_treatment, eb.block_code
@@ -258,6 +266,29 @@ impl ReportGenerator {
             })
             .collect();
 
+        // Unit scores of pest and disease parameters for severity indices
+        let unit_scores: Vec<(Uuid, Uuid, i32, f64)> = sqlx::query_as(
+            r#"
+            SELECT md.parameter_id, eu.block_id, ms.days_after_treatment, md.numeric_value::float8
+            FROM monitoring_data md
+            JOIN experimental_units eu ON md.unit_id = eu.id
+            JOIN experimental_blocks eb ON eu.block_id = eb.id
+            JOIN monitoring_parameters mp ON md.parameter_id = mp.id
+            JOIN monitoring_sessions ms ON md.session_id = ms.id
+            WHERE eb.project_id = $1
+            AND mp.parameter_type IN ('pest_level', 'disease_level')
+            AND md.numeric_value IS NOT NULL
+            AND ms.days_after_treatment IS NOT NULL
+            AND eu.excluded_reason IS NULL
+            AND COALESCE(eu.is_active, true)
+            AND NOT COALESCE(md.is_outlier, false)
+            "#
+        )
+        .bind(project_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
         Ok(ProjectReportData {
             project,
             formulas,
@@ -265,6 +296,15 @@ impl ReportGenerator {
             sessions,
             parameters,
             data_summary: data_summaries,
+            severity_scores: unit_scores
+                .into_iter()
+                .map(|(parameter_id, block_id, days_after_treatment, score)| UnitScore {
+                    parameter_id,
+                    block_id,
+                    days_after_treatment,
+                    score,
+                })
+                .collect(),
         })
     }
 
@@ -710,6 +750,7 @@ Based on the data collected and analyzed, the following conclusions can be drawn
     async fn generate_statistical_report(
         &self,
         data: &ProjectReportData,
+        post_hoc: PostHocTest,
     ) -> Result<(ReportContent, Option<String>), AppError> {
         let mut sections = Vec::new();
 
@@ -776,6 +817,15 @@ Based on the data collected and analyzed, the following conclusions can be drawn
             });
         }
 
+        if data.project.experiment_design == Some(ExperimentDesign::SplitPlot) {
+            sections.extend(Self::split_plot_sections(data));
+        } else {
+            sections.extend(Self::mean_separation_sections(data, post_hoc));
+        }
+        sections.extend(Self::growth_curve_sections(data, post_hoc));
+        sections.extend(Self::severity_sections(data, post_hoc));
+        sections.extend(Self::multivariate_sections(data));
+
         let content = ReportContent {
             title: format!("Statistical Report - {}", data.project.code),
             generated_at: Utc::now(),
@@ -786,6 +836,944 @@ Based on the data collected and analyzed, the following conclusions can be drawn
         Ok((content, None))
     }
 
+    /// Split-plot ANOVA per parameter and session, using each plot's
+    /// `factor_levels` ("main_plot" / "sub_plot") and `replication`.
+    fn split_plot_sections(data: &ProjectReportData) -> Vec<ReportContentSection> {
+        let level = |block: &ExperimentalBlock, key: &str| -> Option<String> {
+            block
+                .factor_levels
+                .as_ref()
+                .and_then(|f| f.get(key))
+                .and_then(|v| v.as_str().map(str::to_string).or_else(|| Some(v.to_string())))
+        };
+
+        let plots: Vec<(Uuid, String, String, i32)> = data
+            .blocks
+            .iter()
+            .filter_map(|b| Some((b.id, level(b, "main_plot")?, level(b, "sub_plot")?, b.replication?)))
+            .collect();
+
+        if plots.is_empty() {
+            return vec![ReportContentSection {
+                title: "Split-Plot ANOVA".to_string(),
+                content: "Split-plot analysis skipped: no plot has main-plot/sub-plot levels and a replication number assigned.".to_string(),
+                tables: vec![],
+                charts: vec![],
+            }];
+        }
+
+        let mut main_levels: Vec<String> = plots.iter().map(|p| p.1.clone()).collect();
+        let mut sub_levels: Vec<String> = plots.iter().map(|p| p.2.clone()).collect();
+        let mut reps: Vec<i32> = plots.iter().map(|p| p.3).collect();
+        main_levels.sort();
+        main_levels.dedup();
+        sub_levels.sort();
+        sub_levels.dedup();
+        reps.sort();
+        reps.dedup();
+
+        let mut sections = Vec::new();
+        for param in &data.parameters {
+            for session in &data.sessions {
+                let mut cells = vec![vec![vec![None; reps.len()]; sub_levels.len()]; main_levels.len()];
+                for d in data.data_summary.iter().filter(|d| {
+                    d.parameter_id == param.id && d.session_code == session.session_code && d.n > 0
+                }) {
+                    if let Some((_, m, s, r)) = plots.iter().find(|p| p.0 == d.block_id) {
+                        let a = main_levels.iter().position(|l| l == m).unwrap_or(0);
+                        let b = sub_levels.iter().position(|l| l == s).unwrap_or(0);
+                        let k = reps.iter().position(|x| x == r).unwrap_or(0);
+                        cells[a][b][k] = Some(d.mean);
+                    }
+                }
+
+                if cells.iter().flatten().flatten().all(|c| c.is_none()) {
+                    continue;
+                }
+
+                let title = format!("Split-Plot ANOVA: {} ({})", param.name, session.session_code);
+                let complete: Option<Vec<Vec<Vec<f64>>>> = cells
+                    .iter()
+                    .map(|m| m.iter().map(|s| s.iter().copied().collect()).collect())
+                    .collect();
+                let Some(values) = complete.filter(|_| main_levels.len() >= 2 && sub_levels.len() >= 2 && reps.len() >= 2) else {
+                    sections.push(ReportContentSection {
+                        title,
+                        content: "Skipped: the main-plot × sub-plot × replication layout is incomplete for this session.".to_string(),
+                        tables: vec![],
+                        charts: vec![],
+                    });
+                    continue;
+                };
+
+                let result = StatisticalAnalysis::split_plot_anova(&values);
+                let anova_table = TableData {
+                    title: format!("ANOVA: {}", param.name),
+                    headers: ["Source", "SS", "df", "MS", "F", "P-value"]
+                        .iter()
+                        .map(|h| h.to_string())
+                        .collect(),
+                    rows: vec![
+                        Self::anova_row("Replication", &result.replication),
+                        Self::anova_row("Main plot (A)", &result.main_plot),
+                        Self::anova_row("Error (a)", &result.error_a),
+                        Self::anova_row("Sub plot (B)", &result.sub_plot),
+                        Self::anova_row("A × B", &result.interaction),
+                        Self::anova_row("Error (b)", &result.error_b),
+                        Self::anova_row("Total", &result.total),
+                    ],
+                };
+
+                let mut means_headers = vec!["Main plot".to_string()];
+                means_headers.extend(sub_levels.iter().cloned());
+                means_headers.push("Mean".to_string());
+                let mut means_rows: Vec<Vec<String>> = main_levels
+                    .iter()
+                    .enumerate()
+                    .map(|(a, name)| {
+                        let mut row = vec![name.clone()];
+                        row.extend(result.cell_means[a].iter().map(|m| format!("{:.3}", m)));
+                        row.push(format!("{:.3}", result.main_plot_means[a]));
+                        row
+                    })
+                    .collect();
+                let mut footer = vec!["Mean".to_string()];
+                footer.extend(result.sub_plot_means.iter().map(|m| format!("{:.3}", m)));
+                footer.push(format!("{:.3}", result.grand_mean));
+                means_rows.push(footer);
+
+                let se_table = TableData {
+                    title: "Standard Errors of Difference".to_string(),
+                    headers: ["Comparison", "SED", "t (0.05)", "LSD (0.05)"]
+                        .iter()
+                        .map(|h| h.to_string())
+                        .collect(),
+                    rows: result
+                        .comparisons
+                        .iter()
+                        .map(|c| {
+                            vec![
+                                c.description.clone(),
+                                format!("{:.4}", c.std_error),
+                                format!("{:.3}", c.t_value),
+                                format!("{:.4}", c.lsd_05),
+                            ]
+                        })
+                        .collect(),
+                };
+
+                sections.push(ReportContentSection {
+                    title,
+                    content: format!(
+                        "**CV (a) = {:.2}%** | **CV (b) = {:.2}%**",
+                        result.cv_a_percent, result.cv_b_percent
+                    ),
+                    tables: vec![
+                        anova_table,
+                        TableData {
+                            title: "Treatment Means".to_string(),
+                            headers: means_headers,
+                            rows: means_rows,
+                        },
+                        se_table,
+                    ],
+                    charts: vec![],
+                });
+            }
+        }
+
+        sections
+    }
+
+    /// Treatment ANOVA per parameter and session on plot means, followed by
+    /// the chosen mean separation test. Plots of the same formula (or the same
+    /// treatment description) are replicates; RAK projects with replication
+    /// numbers are analysed as RCBD, everything else as a one-way layout.
+    /// Blocks sharing a formula (or, failing that, a treatment description)
+    /// are replicates of the same treatment.
+    fn treatment_key(block: &ExperimentalBlock) -> String {
+        block
+            .formula_id
+            .map(|id| id.to_string())
+            .or_else(|| block.treatment_description.clone())
+            .unwrap_or_else(|| block.block_code.clone())
+    }
+
+    /// Distinct treatments in block order as (key, display label).
+    fn treatment_labels(data: &ProjectReportData) -> Vec<(String, String)> {
+        let mut treatments: Vec<(String, String)> = Vec::new();
+        for block in &data.blocks {
+            let key = Self::treatment_key(block);
+            if !treatments.iter().any(|(k, _)| *k == key) {
+                let name = block.treatment_description.clone().unwrap_or_else(|| block.block_code.clone());
+                treatments.push((key, format!("{}{}", name, if block.is_control { " (C)" } else { "" })));
+            }
+        }
+        treatments
+    }
+
+    fn mean_separation_sections(data: &ProjectReportData, test: PostHocTest) -> Vec<ReportContentSection> {
+        let treatment_key = Self::treatment_key;
+        let treatments = Self::treatment_labels(data);
+
+        let mut reps: Vec<i32> = data.blocks.iter().filter_map(|b| b.replication).collect();
+        reps.sort();
+        reps.dedup();
+        let as_rcbd = data.project.experiment_design == Some(ExperimentDesign::Rak)
+            && reps.len() >= 2
+            && data.blocks.iter().all(|b| b.replication.is_some());
+
+        let mut sections = Vec::new();
+        for param in &data.parameters {
+            for session in &data.sessions {
+                let plots: Vec<(usize, Option<i32>, f64)> = data
+                    .data_summary
+                    .iter()
+                    .filter(|d| d.parameter_id == param.id && d.session_code == session.session_code && d.n > 0)
+                    .filter_map(|d| {
+                        let block = data.blocks.iter().find(|b| b.id == d.block_id)?;
+                        let t = treatments.iter().position(|(k, _)| *k == treatment_key(block))?;
+                        Some((t, block.replication, d.mean))
+                    })
+                    .collect();
+
+                if plots.is_empty() {
+                    continue;
+                }
+
+                let title = format!("Mean Separation ({}): {} ({})", test.label(), param.name, session.session_code);
+                let observed: Vec<usize> = (0..treatments.len()).filter(|t| plots.iter().any(|p| p.0 == *t)).collect();
+                let replicated = observed.len() >= 2
+                    && observed.iter().all(|t| plots.iter().filter(|p| p.0 == *t).count() >= 2);
+                if !replicated {
+                    sections.push(ReportContentSection {
+                        title,
+                        content: "Skipped: at least two treatments with two or more replicate plots are needed.".to_string(),
+                        tables: vec![],
+                        charts: vec![],
+                    });
+                    continue;
+                }
+
+                // Ratings (scores) go to rank-based tests instead of the ANOVA
+                if param.data_type == "rating" {
+                    let labels: Vec<String> = observed.iter().map(|t| treatments[*t].1.clone()).collect();
+                    let groups: Vec<Vec<f64>> = observed
+                        .iter()
+                        .map(|t| plots.iter().filter(|p| p.0 == *t).map(|p| p.2).collect())
+                        .collect();
+                    let blocks = as_rcbd.then(|| {
+                        let mut cells = vec![vec![None; reps.len()]; observed.len()];
+                        for &(t, r, y) in &plots {
+                            let i = observed.iter().position(|o| *o == t).unwrap_or(0);
+                            if let Some(j) = r.and_then(|r| reps.iter().position(|x| *x == r)) {
+                                cells[i][j] = Some(y);
+                            }
+                        }
+                        cells
+                    });
+                    sections.push(Self::rank_test_section(
+                        &format!("{} ({})", param.name, session.session_code),
+                        &labels,
+                        &groups,
+                        blocks,
+                    ));
+                    continue;
+                }
+
+                let (anova_rows, means, sizes, error, p_treatment, cv, estimated, effect) = if as_rcbd {
+                    let mut cells = vec![vec![None; reps.len()]; observed.len()];
+                    for &(t, r, y) in &plots {
+                        let i = observed.iter().position(|o| *o == t).unwrap_or(0);
+                        if let Some(j) = r.and_then(|r| reps.iter().position(|x| *x == r)) {
+                            cells[i][j] = Some(y);
+                        }
+                    }
+                    let result = StatisticalAnalysis::rcbd_anova(&cells);
+                    (
+                        vec![
+                            Self::anova_row("Treatment", &result.treatment),
+                            Self::anova_row("Block", &result.block),
+                            Self::anova_row("Error", &result.error),
+                            Self::anova_row("Total", &result.total),
+                        ],
+                        result.treatment_means,
+                        result.treatment_sizes,
+                        result.error,
+                        result.treatment.p.unwrap_or(1.0),
+                        result.cv_percent,
+                        result.estimated_plots,
+                        result.effect_size,
+                    )
+                } else {
+                    let groups: Vec<Vec<f64>> = observed
+                        .iter()
+                        .map(|t| plots.iter().filter(|p| p.0 == *t).map(|p| p.2).collect())
+                        .collect();
+                    let result = StatisticalAnalysis::one_way_anova(&groups);
+                    let cv = if result.grand_mean != 0.0 {
+                        result.source_within.ms.sqrt() / result.grand_mean * 100.0
+                    } else {
+                        0.0
+                    };
+                    (
+                        vec![
+                            Self::anova_row("Treatment", &result.source_between),
+                            Self::anova_row("Error", &result.source_within),
+                            Self::anova_row("Total", &result.source_total),
+                        ],
+                        result.group_means,
+                        result.group_sizes,
+                        result.source_within,
+                        result.source_between.p.unwrap_or(1.0),
+                        cv,
+                        Vec::new(),
+                        result.effect_size,
+                    )
+                };
+
+                let significant = p_treatment < 0.05;
+                let intervals = StatisticalAnalysis::mean_intervals(&means, &sizes, error.ms, error.df as f64);
+                let letters = if significant {
+                    StatisticalAnalysis::mean_separation(test, &means, &sizes, error.ms, error.df as f64, 0.05).letters
+                } else {
+                    vec![String::new(); means.len()]
+                };
+
+                let mut ranked: Vec<usize> = (0..means.len()).collect();
+                ranked.sort_by(|&a, &b| means[b].partial_cmp(&means[a]).unwrap_or(std::cmp::Ordering::Equal));
+                let means_table = TableData {
+                    title: format!("Treatment Means ({})", test.label()),
+                    headers: ["Treatment", "n", "Mean", "95% CI", "Notation"]
+                        .iter()
+                        .map(|h| h.to_string())
+                        .collect(),
+                    rows: ranked
+                        .iter()
+                        .map(|&i| {
+                            vec![
+                                treatments[observed[i]].1.clone(),
+                                sizes[i].to_string(),
+                                if estimated.iter().any(|e| e.treatment == i) {
+                                    format!("{:.3}*", means[i])
+                                } else {
+                                    format!("{:.3}", means[i])
+                                },
+                                format!("[{:.3}, {:.3}]", intervals[i].lower, intervals[i].upper),
+                                letters[i].clone(),
+                            ]
+                        })
+                        .collect(),
+                };
+
+                let mut tables = vec![
+                    TableData {
+                        title: format!("ANOVA: {}", param.name),
+                        headers: ["Source", "SS", "df", "MS", "F", "P-value"]
+                            .iter()
+                            .map(|h| h.to_string())
+                            .collect(),
+                        rows: anova_rows,
+                    },
+                    means_table,
+                ];
+                let mut content = if significant {
+                    format!(
+                        "**CV = {:.2}%** | Means followed by the same letter are not significantly different ({}, α = 0.05).",
+                        cv,
+                        test.label()
+                    )
+                } else {
+                    format!("**CV = {:.2}%** | Treatment effect not significant (P = {:.4}); no mean separation.", cv, p_treatment)
+                };
+                content.push_str(&format!(
+                    "\n\nEffect size: η² = {:.3}, partial η² = {:.3}, ω² = {:.3}. Confidence intervals use the pooled error mean square.",
+                    effect.eta_squared, effect.partial_eta_squared, effect.omega_squared
+                ));
+                if !estimated.is_empty() {
+                    content.push_str(&format!(
+                        "\n\nMeans marked * include estimated missing plots: {} plot value(s) estimated by the Yates/Bartlett method, error df reduced by {}.",
+                        estimated.len(),
+                        estimated.len()
+                    ));
+                    tables.push(TableData {
+                        title: "Estimated Missing Plots".to_string(),
+                        headers: ["Treatment", "Replication", "Estimated Value"]
+                            .iter()
+                            .map(|h| h.to_string())
+                            .collect(),
+                        rows: estimated
+                            .iter()
+                            .map(|e| {
+                                vec![
+                                    treatments[observed[e.treatment]].1.clone(),
+                                    reps[e.block].to_string(),
+                                    format!("{:.3} (estimated)", e.value),
+                                ]
+                            })
+                            .collect(),
+                    });
+                }
+
+                sections.push(ReportContentSection {
+                    title,
+                    content,
+                    tables,
+                    charts: vec![],
+                });
+            }
+        }
+
+        sections
+    }
+
+    /// Logistic growth curves for height and leaf-count parameters, fitted
+    /// to plot means over days after treatment. Replicate plots serve as the
+    /// units for comparing curve parameters between treatments.
+    fn growth_curve_sections(data: &ProjectReportData, test: PostHocTest) -> Vec<ReportContentSection> {
+        let treatments = Self::treatment_labels(data);
+        let mut sections = Vec::new();
+
+        for param in &data.parameters {
+            if !matches!(param.parameter_type, Some(MonitoringType::Height) | Some(MonitoringType::LeafCount)) {
+                continue;
+            }
+
+            let series: Vec<GrowthTreatmentData> = treatments
+                .iter()
+                .map(|(key, label)| GrowthTreatmentData {
+                    treatment: label.clone(),
+                    plots: data
+                        .blocks
+                        .iter()
+                        .filter(|b| Self::treatment_key(b) == *key)
+                        .map(|b| GrowthPlotData {
+                            plot: b.block_code.clone(),
+                            points: data
+                                .data_summary
+                                .iter()
+                                .filter(|d| d.parameter_id == param.id && d.block_id == b.id && d.n > 0)
+                                .filter_map(|d| d.days_after_treatment.map(|dat| (dat as f64, d.mean)))
+                                .collect(),
+                        })
+                        .filter(|p| !p.points.is_empty())
+                        .collect(),
+                })
+                .filter(|t| !t.plots.is_empty())
+                .collect();
+
+            let mut days: Vec<f64> = series
+                .iter()
+                .flat_map(|t| t.plots.iter().flat_map(|p| p.points.iter().map(|p| p.0)))
+                .collect();
+            days.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            days.dedup();
+            if days.len() < 4 {
+                continue;
+            }
+
+            let result = StatisticalAnalysis::growth_curve_analysis(GrowthModel::Logistic, &series, test);
+            let (first, last) = (days[0], days[days.len() - 1]);
+
+            let chart_series: Vec<serde_json::Value> = series
+                .iter()
+                .zip(&result.treatments)
+                .map(|(observed, fitted)| {
+                    let mut points: Vec<(f64, f64)> =
+                        observed.plots.iter().flat_map(|p| p.points.iter().copied()).collect();
+                    points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+                    let curve: Vec<[f64; 2]> = fitted
+                        .fit
+                        .as_ref()
+                        .map(|fit| {
+                            (0..=50)
+                                .map(|i| {
+                                    let x = first + (last - first) * i as f64 / 50.0;
+                                    [x, fit.predict(x)]
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    serde_json::json!({
+                        "name": observed.treatment,
+                        "observed": points.iter().map(|p| [p.0, p.1]).collect::<Vec<_>>(),
+                        "fitted": curve,
+                    })
+                })
+                .collect();
+
+            let fit_table = TableData {
+                title: format!("Logistic Growth Parameters: {}", param.name),
+                headers: ["Treatment", "Asymptote", "Max Rate/day", "Inflection (DAT)", "R²"]
+                    .iter()
+                    .map(|h| h.to_string())
+                    .collect(),
+                rows: result
+                    .treatments
+                    .iter()
+                    .map(|t| match &t.fit {
+                        Some(fit) => vec![
+                            t.treatment.clone(),
+                            format!("{:.3}", fit.asymptote),
+                            format!("{:.3}", fit.max_growth_rate),
+                            format!("{:.1}", fit.inflection_time),
+                            format!("{:.3}", fit.r_squared),
+                        ],
+                        None => vec![t.treatment.clone(), "-".into(), "-".into(), "-".into(), "-".into()],
+                    })
+                    .collect(),
+            };
+
+            let mut tables = vec![fit_table];
+            for comparison in &result.comparisons {
+                tables.push(TableData {
+                    title: format!("Treatment Comparison: {}", comparison.parameter.replace('_', " ")),
+                    headers: ["Source", "SS", "df", "MS", "F", "P-value"]
+                        .iter()
+                        .map(|h| h.to_string())
+                        .collect(),
+                    rows: vec![
+                        Self::anova_row("Treatment", &comparison.anova.source_between),
+                        Self::anova_row("Error", &comparison.anova.source_within),
+                        Self::anova_row("Total", &comparison.anova.source_total),
+                    ],
+                });
+                if let Some(separation) = &comparison.mean_separation {
+                    tables.push(TableData {
+                        title: format!("Means ({}): {}", test.label(), comparison.parameter.replace('_', " ")),
+                        headers: ["Treatment", "Mean", "Notation"].iter().map(|h| h.to_string()).collect(),
+                        rows: comparison
+                            .treatments
+                            .iter()
+                            .zip(separation.means.iter().zip(&separation.letters))
+                            .map(|(t, (mean, letter))| vec![t.clone(), format!("{:.3}", mean), letter.clone()])
+                            .collect(),
+                    });
+                }
+            }
+
+            let mut content = "Logistic model y = A / (1 + e^(-k(DAT - t_i))) fitted to plot means. \
+                               Curve parameters are compared between treatments using replicate plots."
+                .to_string();
+            if !result.dropped_treatments.is_empty() {
+                content.push_str(&format!(
+                    " Not compared (fewer than 2 converged plot fits): {}.",
+                    result.dropped_treatments.join(", ")
+                ));
+            }
+
+            sections.push(ReportContentSection {
+                title: format!("Growth Curve: {}", param.name),
+                content,
+                tables,
+                charts: vec![ChartData {
+                    title: format!("{} over Days After Treatment", param.name),
+                    chart_type: "line".to_string(),
+                    data: serde_json::json!({
+                        "x_label": "Days after treatment",
+                        "y_label": param.name,
+                        "series": chart_series,
+                    }),
+                }],
+            });
+        }
+
+        sections
+    }
+
+    /// Disease severity index, incidence, AUDPC and AUDPS for pest and
+    /// disease parameters, compared between treatments on replicate plots.
+    fn severity_sections(data: &ProjectReportData, test: PostHocTest) -> Vec<ReportContentSection> {
+        let treatments = Self::treatment_labels(data);
+        let mut reps: Vec<i32> = data.blocks.iter().filter_map(|b| b.replication).collect();
+        reps.sort();
+        reps.dedup();
+        let as_rcbd = data.project.experiment_design == Some(ExperimentDesign::Rak)
+            && reps.len() >= 2
+            && data.blocks.iter().all(|b| b.replication.is_some());
+        let mut sections = Vec::new();
+
+        for param in &data.parameters {
+            if !matches!(param.parameter_type, Some(MonitoringType::PestLevel) | Some(MonitoringType::DiseaseLevel)) {
+                continue;
+            }
+            let scale = SeverityScale::for_parameter(param);
+
+            let series: Vec<SeverityTreatmentData> = treatments
+                .iter()
+                .map(|(key, label)| SeverityTreatmentData {
+                    treatment: label.clone(),
+                    plots: data
+                        .blocks
+                        .iter()
+                        .filter(|b| Self::treatment_key(b) == *key)
+                        .map(|b| {
+                            let mut plot = SeverityPlotData {
+                                plot: b.block_code.clone(),
+                                replication: b.replication,
+                                sessions: Vec::new(),
+                            };
+                            for s in data
+                                .severity_scores
+                                .iter()
+                                .filter(|s| s.parameter_id == param.id && s.block_id == b.id)
+                            {
+                                plot.push_score(s.days_after_treatment as f64, s.score);
+                            }
+                            plot
+                        })
+                        .filter(|plot| !plot.sessions.is_empty())
+                        .collect(),
+                })
+                .collect();
+            if series.iter().all(|t| t.plots.is_empty()) {
+                continue;
+            }
+
+            let result = match StatisticalAnalysis::severity_analysis(&scale, &series, as_rcbd, test) {
+                Ok(result) => result,
+                Err(e) => {
+                    sections.push(ReportContentSection {
+                        title: format!("Severity and AUDPC: {}", param.name),
+                        content: format!("Skipped: {}.", e),
+                        tables: vec![],
+                        charts: vec![],
+                    });
+                    continue;
+                }
+            };
+            let plot_mean = |plots: &[PlotSeverity], value: fn(&PlotSeverity) -> Option<f64>| {
+                let values: Vec<f64> = plots.iter().filter_map(value).collect();
+                if values.is_empty() { 0.0 } else { values.iter().sum::<f64>() / values.len() as f64 }
+            };
+
+            let mut tables = vec![TableData {
+                title: format!("Severity Indices: {}", param.name),
+                headers: ["Treatment", "Plots", "Final DSI (%)", "Final Incidence (%)", "AUDPC", "AUDPS"]
+                    .iter()
+                    .map(|h| h.to_string())
+                    .collect(),
+                rows: result
+                    .treatments
+                    .iter()
+                    .map(|t| {
+                        vec![
+                            t.treatment.clone(),
+                            t.plots.len().to_string(),
+                            format!("{:.2}", plot_mean(&t.plots, |p| p.points.last().map(|x| x.severity_index))),
+                            format!("{:.2}", plot_mean(&t.plots, |p| p.points.last().map(|x| x.incidence))),
+                            format!("{:.1}", plot_mean(&t.plots, |p| Some(p.audpc))),
+                            format!("{:.1}", plot_mean(&t.plots, |p| Some(p.audps))),
+                        ]
+                    })
+                    .collect(),
+            }];
+
+            for comparison in &result.comparisons {
+                let label = comparison.index.replace('_', " ").to_uppercase();
+                tables.push(TableData {
+                    title: format!("ANOVA: {}", label),
+                    headers: ["Source", "SS", "df", "MS", "F", "P-value"]
+                        .iter()
+                        .map(|h| h.to_string())
+                        .collect(),
+                    rows: match &comparison.anova {
+                        SeverityAnova::Crd { result } => vec![
+                            Self::anova_row("Treatment", &result.source_between),
+                            Self::anova_row("Error", &result.source_within),
+                            Self::anova_row("Total", &result.source_total),
+                        ],
+                        SeverityAnova::Rcbd { result } => vec![
+                            Self::anova_row("Treatment", &result.treatment),
+                            Self::anova_row("Block", &result.block),
+                            Self::anova_row("Error", &result.error),
+                            Self::anova_row("Total", &result.total),
+                        ],
+                    },
+                });
+                if let Some(separation) = &comparison.mean_separation {
+                    tables.push(TableData {
+                        title: format!("Means ({}): {}", test.label(), label),
+                        headers: ["Treatment", "Mean", "Notation"].iter().map(|h| h.to_string()).collect(),
+                        rows: result
+                            .treatments
+                            .iter()
+                            .zip(separation.means.iter().zip(&separation.letters))
+                            .map(|(t, (mean, letter))| vec![t.treatment.clone(), format!("{:.3}", mean), letter.clone()])
+                            .collect(),
+                    });
+                }
+            }
+
+            let scale_note = if scale.class_midpoints.is_empty() {
+                format!("McKinney index on a {}-{} scale", scale.min_class, scale.max_class)
+            } else {
+                format!("class midpoint severity on a {}-class scale", scale.class_midpoints.len())
+            };
+            sections.push(ReportContentSection {
+                title: format!("Severity and AUDPC: {}", param.name),
+                content: format!(
+                    "Disease severity index (DSI) as {}; incidence counts units scored above {}. \
+                     AUDPC and AUDPS integrate the DSI over days after treatment for each plot.",
+                    scale_note, scale.healthy_class
+                ),
+                tables,
+                charts: vec![ChartData {
+                    title: format!("{} Progress", param.name),
+                    chart_type: "line".to_string(),
+                    data: serde_json::json!({
+                        "x_label": "Days after treatment",
+                        "y_label": "Severity index (%)",
+                        "series": result.treatments.iter().map(|t| serde_json::json!({
+                            "name": t.treatment,
+                            "observed": t.points.iter().map(|p| [p.days_after_treatment, p.severity_index]).collect::<Vec<_>>(),
+                        })).collect::<Vec<_>>(),
+                    }),
+                }],
+            });
+        }
+
+        sections
+    }
+
+    /// Pearson correlations and PCA of treatment means per session, with a
+    /// biplot of treatment scores and parameter loadings on PC1/PC2.
+    fn multivariate_sections(data: &ProjectReportData) -> Vec<ReportContentSection> {
+        let treatments = Self::treatment_labels(data);
+        let mut sections = Vec::new();
+
+        for session in &data.sessions {
+            let parameters: Vec<&MonitoringParameter> = data
+                .parameters
+                .iter()
+                .filter(|p| p.data_type == "numeric")
+                .filter(|p| {
+                    data.data_summary
+                        .iter()
+                        .any(|d| d.parameter_id == p.id && d.session_code == session.session_code && d.n > 0)
+                })
+                .collect();
+            if parameters.len() < 2 {
+                continue;
+            }
+
+            let mut labels = Vec::new();
+            let mut values = Vec::new();
+            for (key, label) in &treatments {
+                let row: Option<Vec<f64>> = parameters
+                    .iter()
+                    .map(|p| {
+                        let means: Vec<f64> = data
+                            .blocks
+                            .iter()
+                            .filter(|b| Self::treatment_key(b) == *key)
+                            .filter_map(|b| {
+                                data.data_summary.iter().find(|d| {
+                                    d.parameter_id == p.id
+                                        && d.block_id == b.id
+                                        && d.session_code == session.session_code
+                                        && d.n > 0
+                                })
+                            })
+                            .map(|d| d.mean)
+                            .collect();
+                        (!means.is_empty()).then(|| means.iter().sum::<f64>() / means.len() as f64)
+                    })
+                    .collect();
+                if let Some(row) = row {
+                    labels.push(label.clone());
+                    values.push(row);
+                }
+            }
+            if values.len() < 3 {
+                continue;
+            }
+
+            let mv = MultivariateData {
+                labels,
+                variables: parameters.iter().map(|p| p.code.clone()).collect(),
+                values,
+            };
+            let correlation = StatisticalAnalysis::correlation_matrix(&mv, CorrelationMethod::Pearson);
+            let pca = StatisticalAnalysis::pca(&mv);
+
+            let stars = |p: f64| if p < 0.01 { "**" } else if p < 0.05 { "*" } else { "" };
+            let correlation_table = TableData {
+                title: format!("Pearson Correlation: {}", session.session_code),
+                headers: std::iter::once(String::new()).chain(mv.variables.iter().cloned()).collect(),
+                rows: mv
+                    .variables
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| {
+                        std::iter::once(v.clone())
+                            .chain((0..mv.variables.len()).map(|j| {
+                                if i == j {
+                                    "1".to_string()
+                                } else {
+                                    format!(
+                                        "{:.3}{}",
+                                        correlation.coefficients[i][j],
+                                        stars(correlation.p_values[i][j])
+                                    )
+                                }
+                            }))
+                            .collect()
+                    })
+                    .collect(),
+            };
+
+            let components = pca.eigenvalues.len();
+            let pca_table = TableData {
+                title: format!("Principal Components: {}", session.session_code),
+                headers: std::iter::once(String::new())
+                    .chain((1..=components).map(|c| format!("PC{}", c)))
+                    .collect(),
+                rows: [
+                    ("Eigenvalue", &pca.eigenvalues, 1.0),
+                    ("Variance (%)", &pca.explained_variance, 100.0),
+                    ("Cumulative (%)", &pca.cumulative_variance, 100.0),
+                ]
+                .iter()
+                .map(|(name, row, scale)| {
+                    std::iter::once(name.to_string())
+                        .chain(row.iter().map(|v| format!("{:.2}", v * scale)))
+                        .collect()
+                })
+                .chain(mv.variables.iter().zip(&pca.loadings).map(|(v, loadings)| {
+                    std::iter::once(v.clone())
+                        .chain(loadings.iter().map(|l| format!("{:.3}", l)))
+                        .collect()
+                }))
+                .collect(),
+            };
+
+            let axis = |row: &Vec<f64>, c: usize| row.get(c).copied().unwrap_or(0.0);
+            sections.push(ReportContentSection {
+                title: format!("Multivariate Analysis: {}", session.session_code),
+                content: format!(
+                    "Correlations and principal components of treatment means ({} treatments, {} parameters). \
+                     * p < 0.05, ** p < 0.01. PCA uses standardized parameters.",
+                    mv.labels.len(),
+                    mv.variables.len()
+                ),
+                tables: vec![correlation_table, pca_table],
+                charts: vec![ChartData {
+                    title: format!("PCA Biplot: {}", session.session_code),
+                    chart_type: "scatter".to_string(),
+                    data: serde_json::json!({
+                        "x_label": format!("PC1 ({:.1}%)", axis(&pca.explained_variance, 0) * 100.0),
+                        "y_label": format!("PC2 ({:.1}%)", axis(&pca.explained_variance, 1) * 100.0),
+                        "scores": mv.labels.iter().zip(&pca.scores).map(|(label, s)| serde_json::json!({
+                            "label": label,
+                            "x": axis(s, 0),
+                            "y": axis(s, 1),
+                        })).collect::<Vec<_>>(),
+                        "loadings": mv.variables.iter().zip(&pca.loadings).map(|(variable, l)| serde_json::json!({
+                            "variable": variable,
+                            "x": axis(l, 0),
+                            "y": axis(l, 1),
+                        })).collect::<Vec<_>>(),
+                    }),
+                }],
+            });
+        }
+
+        sections
+    }
+
+    /// Rank-based treatment comparison for rating parameters: Friedman when
+    /// every treatment is scored in every block, otherwise Kruskal-Wallis with
+    /// Dunn's letters.
+    fn rank_test_section(
+        subject: &str,
+        labels: &[String],
+        groups: &[Vec<f64>],
+        blocks: Option<Vec<Vec<Option<f64>>>>,
+    ) -> ReportContentSection {
+        let complete: Option<Vec<Vec<f64>>> = blocks.and_then(|b| {
+            b.into_iter()
+                .map(|row| row.into_iter().collect::<Option<Vec<f64>>>())
+                .collect()
+        });
+        let headers = |extra: &[&str]| -> Vec<String> {
+            ["Treatment", "n", "Median", "Mean rank"]
+                .iter()
+                .chain(extra.iter())
+                .map(|h| h.to_string())
+                .collect()
+        };
+
+        let (test, table) = match complete {
+            Some(data) => {
+                let result = StatisticalAnalysis::friedman(&data);
+                let rows = labels
+                    .iter()
+                    .enumerate()
+                    .map(|(i, label)| {
+                        vec![
+                            label.clone(),
+                            data[i].len().to_string(),
+                            format!("{:.2}", result.medians[i]),
+                            format!("{:.2}", result.mean_ranks[i]),
+                        ]
+                    })
+                    .collect();
+                (result.test, TableData { title: "Treatment Ranks".to_string(), headers: headers(&[]), rows })
+            }
+            None => {
+                let result = StatisticalAnalysis::kruskal_wallis(groups);
+                let mut ranked: Vec<usize> = (0..labels.len()).collect();
+                ranked.sort_by(|&a, &b| {
+                    result.mean_ranks[b]
+                        .partial_cmp(&result.mean_ranks[a])
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+                let rows = ranked
+                    .iter()
+                    .map(|&i| {
+                        vec![
+                            labels[i].clone(),
+                            result.sizes[i].to_string(),
+                            format!("{:.2}", result.medians[i]),
+                            format!("{:.2}", result.mean_ranks[i]),
+                            if result.test.is_significant { result.letters[i].clone() } else { String::new() },
+                        ]
+                    })
+                    .collect();
+                (
+                    result.test,
+                    TableData { title: "Treatment Ranks (Dunn, Holm-adjusted)".to_string(), headers: headers(&["Notation"]), rows },
+                )
+            }
+        };
+
+        ReportContentSection {
+            title: format!("{} Test: {}", test.test, subject),
+            content: format!(
+                "Rating parameter analysed with rank-based tests. **Statistic = {:.4}** (df = {}), **P = {:.4}**, {} = {:.3}.",
+                test.statistic,
+                test.df.unwrap_or(0.0),
+                test.p_value,
+                test.effect_size_name,
+                test.effect_size
+            ),
+            tables: vec![table],
+            charts: vec![],
+        }
+    }
+
+    fn anova_row(source: &str, s: &AnovaSource) -> Vec<String> {
+        vec![
+            source.to_string(),
+            format!("{:.4}", s.ss),
+            s.df.to_string(),
+            if s.ms > 0.0 { format!("{:.4}", s.ms) } else { "-".to_string() },
+            s.f.map(|f| format!("{:.4}", f)).unwrap_or_else(|| "-".to_string()),
+            s.p.map(|p| format!("{:.4}", p)).unwrap_or_else(|| "-".to_string()),
+        ]
+    }
+
     async fn generate_qc_report(
         &self,
         data: &ProjectReportData,
@@ -1064,6 +2052,15 @@ pub struct ProjectReportData {
     pub sessions: Vec<MonitoringSession>,
     pub parameters: Vec<MonitoringParameter>,
     pub data_summary: Vec<DataSummary>,
+    pub severity_scores: Vec<UnitScore>, // pest and disease parameters only
+}
+
+#[derive(Debug, Clone)]
+pub struct UnitScore {
+    pub parameter_id: Uuid,
+    pub block_id: Uuid,
+    pub days_after_treatment: i32,
+    pub score: f64,
 }
 
 #[derive(Debug, Clone)]
@@ -3,7 +3,10 @@ use crate::config::Settings;
 use crate::errors::AppError;
 use crate::models::*;
 use chrono::{Duration, Utc};
-use sqlx::{PgPool, Row, FromRow};
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use sqlx::{PgConnection, PgPool, Postgres, Row, FromRow, Transaction};
 use std::sync::Arc;
 use uuid::Uuid;
 use validator::Validate;
@@ -474,6 +477,365 @@ impl ProjectService {
     }
 }
 
+// ==============================================================================
+// FIELD LAYOUT SERVICE
+// ==============================================================================
+
+pub struct LayoutService;
+
+impl LayoutService {
+    /// Generates and stores a randomized plot layout for the project's design.
+    ///
+    /// RAL plots are fully randomized over a grid with one column per
+    /// treatment; RAK and factorial trials get one row per replication with
+    /// treatments randomized independently within each row. Existing blocks
+    /// and units are replaced, which is refused once any monitoring data has
+    /// been recorded.
+    pub async fn generate(
+        pool: &PgPool,
+        project_id: Uuid,
+        req: GenerateLayoutRequest,
+        user_id: Uuid,
+    ) -> Result<GeneratedLayout, AppError> {
+        let project = ProjectService::get_by_id(pool, project_id).await?;
+        if project.is_locked {
+            return Err(AppError::ProjectLockedError("Cannot change the layout of a locked project".to_string()));
+        }
+
+        let design = match project.experiment_design {
+            Some(d @ (ExperimentDesign::Ral | ExperimentDesign::Rak | ExperimentDesign::Factorial)) => d,
+            _ => {
+                return Err(AppError::Validation(
+                    "Layout generation supports RAL, RAK and factorial designs".to_string(),
+                ))
+            }
+        };
+
+        let replications = req.replications.or(project.replications).unwrap_or(0);
+        let units_per_plot = req.units_per_plot.unwrap_or(1);
+        if replications < 2 || units_per_plot < 1 {
+            return Err(AppError::Validation(
+                "At least 2 replications and 1 unit per plot are required".to_string(),
+            ));
+        }
+
+        let treatments = Self::treatments(pool, project_id, &design, &req).await?;
+        if treatments.len() < 2 {
+            return Err(AppError::Validation("At least 2 treatments are required".to_string()));
+        }
+
+        let seed = req.seed.unwrap_or_else(rand::random::<i64>);
+        let plots = Self::randomize(&design, treatments.len(), replications as usize, seed as u64);
+
+        let mut tx = pool.begin().await.map_err(|e| AppError::Database(e.to_string()))?;
+
+        // Locking the units waits for in-flight submissions (which hold a key
+        // share on their unit) and makes later ones fail, so the check below
+        // sees every observation the delete would cascade into.
+        sqlx::query("SELECT id FROM projects WHERE id = $1 FOR UPDATE")
+            .bind(project_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        sqlx::query(
+            r#"
+            SELECT eu.id FROM experimental_units eu
+            JOIN experimental_blocks eb ON eu.block_id = eb.id
+            WHERE eb.project_id = $1
+            FOR UPDATE OF eu
+            "#
+        )
+        .bind(project_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        let has_data: (bool,) = sqlx::query_as(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM monitoring_data md
+                JOIN experimental_units eu ON md.unit_id = eu.id
+                JOIN experimental_blocks eb ON eu.block_id = eb.id
+                WHERE eb.project_id = $1
+            )
+            "#
+        )
+        .bind(project_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        if has_data.0 {
+            return Err(AppError::Conflict(
+                "Monitoring data has already been recorded; the layout can no longer be re-randomized".to_string(),
+            ));
+        }
+
+        sqlx::query("DELETE FROM experimental_blocks WHERE project_id = $1")
+            .bind(project_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::Database(e.to_string()))?;
+
+        let mut blocks = Vec::with_capacity(plots.len());
+        for (i, plot) in plots.iter().enumerate() {
+            let treatment = &treatments[plot.treatment];
+            let block_code = format!("P{:03}", i + 1);
+            let block: ExperimentalBlock = sqlx::query_as(
+                r#"
+                INSERT INTO experimental_blocks (
+                    id, project_id, block_code, block_name, formula_id, treatment_description,
+                    is_control, position_row, position_column, replication, factor_levels
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                RETURNING *
+                "#
+            )
+            .bind(Uuid::new_v4())
+            .bind(project_id)
+            .bind(&block_code)
+            .bind(format!("{} R{}", treatment.description, plot.replication))
+            .bind(treatment.formula_id)
+            .bind(&treatment.description)
+            .bind(treatment.is_control)
+            .bind(plot.row as i32)
+            .bind(plot.column as i32)
+            .bind(plot.replication as i32)
+            .bind(&treatment.factor_levels)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| AppError::Database(e.to_string()))?;
+
+            for position in 1..=units_per_plot {
+                sqlx::query(
+                    r#"
+                    INSERT INTO experimental_units (id, block_id, unit_code, position_in_block)
+                    VALUES ($1, $2, $3, $4)
+                    "#
+                )
+                .bind(Uuid::new_v4())
+                .bind(block.id)
+                .bind(format!("{}-{:02}", block_code, position))
+                .bind(position)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            }
+            blocks.push(block);
+        }
+
+        let layout: FieldLayout = sqlx::query_as(
+            r#"
+            INSERT INTO field_layouts (
+                project_id, experiment_design, seed, replications, units_per_plot, treatments, generated_by
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (project_id) DO UPDATE SET
+                experiment_design = EXCLUDED.experiment_design,
+                seed = EXCLUDED.seed,
+                replications = EXCLUDED.replications,
+                units_per_plot = EXCLUDED.units_per_plot,
+                treatments = EXCLUDED.treatments,
+                generated_by = EXCLUDED.generated_by,
+                generated_at = NOW()
+            RETURNING *
+            "#
+        )
+        .bind(project_id)
+        .bind(&design)
+        .bind(seed)
+        .bind(replications)
+        .bind(units_per_plot)
+        .bind(serde_json::to_value(&treatments).unwrap_or_default())
+        .bind(user_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        sqlx::query(
+            r#"
+            UPDATE projects SET
+                replications = $2,
+                treatments_count = $3,
+                blocks_count = $4,
+                updated_at = NOW()
+            WHERE id = $1
+            "#
+        )
+        .bind(project_id)
+        .bind(replications)
+        .bind(treatments.len() as i32)
+        .bind(blocks.len() as i32)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        tx.commit().await.map_err(|e| AppError::Database(e.to_string()))?;
+
+        AuditService::log_simple(pool, project_id, user_id, "layout_generated", Some(&format!("Seed: {}", seed))).await?;
+
+        Ok(GeneratedLayout {
+            layout,
+            units_count: blocks.len() as i64 * units_per_plot as i64,
+            blocks,
+        })
+    }
+
+    pub async fn get(pool: &PgPool, project_id: Uuid) -> Result<GeneratedLayout, AppError> {
+        let layout: FieldLayout = sqlx::query_as("SELECT * FROM field_layouts WHERE project_id = $1")
+            .bind(project_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| AppError::Database(e.to_string()))?
+            .ok_or_else(|| AppError::NotFound("No layout has been generated for this project".to_string()))?;
+
+        let blocks: Vec<ExperimentalBlock> = sqlx::query_as(
+            "SELECT * FROM experimental_blocks WHERE project_id = $1 ORDER BY position_row, position_column"
+        )
+        .bind(project_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        let units: (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM experimental_units eu JOIN experimental_blocks eb ON eu.block_id = eb.id WHERE eb.project_id = $1"
+        )
+        .bind(project_id)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        Ok(GeneratedLayout {
+            layout,
+            blocks,
+            units_count: units.0,
+        })
+    }
+
+    /// Treatment list: the requested formulas plus an untreated control for
+    /// RAL/RAK, or every combination of factor levels for factorial designs.
+    async fn treatments(
+        pool: &PgPool,
+        project_id: Uuid,
+        design: &ExperimentDesign,
+        req: &GenerateLayoutRequest,
+    ) -> Result<Vec<LayoutTreatment>, AppError> {
+        if *design == ExperimentDesign::Factorial {
+            if req.factors.len() < 2 || req.factors.iter().any(|f| f.levels.len() < 2) {
+                return Err(AppError::Validation(
+                    "Factorial layouts need at least 2 factors with 2 or more levels each".to_string(),
+                ));
+            }
+            let mut combinations: Vec<Vec<(&str, &str)>> = vec![Vec::new()];
+            for factor in &req.factors {
+                combinations = combinations
+                    .into_iter()
+                    .flat_map(|c| {
+                        factor.levels.iter().map(move |level| {
+                            let mut next = c.clone();
+                            next.push((factor.name.as_str(), level.as_str()));
+                            next
+                        })
+                    })
+                    .collect();
+            }
+            return Ok(combinations
+                .into_iter()
+                .map(|levels| LayoutTreatment {
+                    formula_id: None,
+                    description: levels.iter().map(|(_, l)| *l).collect::<Vec<_>>().join(" × "),
+                    is_control: false,
+                    factor_levels: serde_json::Value::Object(
+                        levels.iter().map(|(f, l)| (f.to_string(), serde_json::json!(l))).collect(),
+                    ),
+                })
+                .collect());
+        }
+
+        let formulas: Vec<(Uuid, String)> = sqlx::query_as(
+            "SELECT id, code FROM formulas WHERE project_id = $1 AND id = ANY($2)"
+        )
+        .bind(project_id)
+        .bind(&req.formula_ids)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        let mut treatments = Vec::new();
+        if req.include_control.unwrap_or(true) {
+            treatments.push(LayoutTreatment {
+                formula_id: None,
+                description: "Control".to_string(),
+                is_control: true,
+                factor_levels: serde_json::json!({}),
+            });
+        }
+        for id in &req.formula_ids {
+            let (formula_id, code) = formulas
+                .iter()
+                .find(|(f, _)| f == id)
+                .ok_or_else(|| AppError::Validation(format!("Formula {} does not belong to this project", id)))?;
+            if treatments.iter().any(|t| t.formula_id == Some(*formula_id)) {
+                continue;
+            }
+            treatments.push(LayoutTreatment {
+                formula_id: Some(*formula_id),
+                description: code.clone(),
+                is_control: false,
+                factor_levels: serde_json::json!({}),
+            });
+        }
+
+        Ok(treatments)
+    }
+
+    /// Seeded plot order. The same seed, design and treatment count always
+    /// give the same layout; ChaCha8 is used because its output, unlike
+    /// `StdRng`'s, is fixed across rand releases.
+    fn randomize(design: &ExperimentDesign, treatments: usize, replications: usize, seed: u64) -> Vec<LayoutPlot> {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        match design {
+            ExperimentDesign::Ral => {
+                let mut order: Vec<usize> = (0..treatments).flat_map(|t| std::iter::repeat_n(t, replications)).collect();
+                order.shuffle(&mut rng);
+                let mut seen = vec![0; treatments];
+                order
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, treatment)| {
+                        seen[treatment] += 1;
+                        LayoutPlot {
+                            treatment,
+                            replication: seen[treatment],
+                            row: i / treatments + 1,
+                            column: i % treatments + 1,
+                        }
+                    })
+                    .collect()
+            }
+            _ => (1..=replications)
+                .flat_map(|replication| {
+                    let mut order: Vec<usize> = (0..treatments).collect();
+                    order.shuffle(&mut rng);
+                    order.into_iter().enumerate().map(move |(j, treatment)| LayoutPlot {
+                        treatment,
+                        replication,
+                        row: replication,
+                        column: j + 1,
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+struct LayoutPlot {
+    treatment: usize,
+    replication: usize,
+    row: usize,
+    column: usize,
+}
+
 // ==============================================================================
 // FORMULA SERVICE
 // ==============================================================================
@@ -758,6 +1120,22 @@ impl LabTestService {
 
 pub struct MonitoringService;
 
+/// A numeric value with what the outlier checks compare it against
+#[derive(Debug, FromRow)]
+struct OutlierScanRow {
+    id: Uuid,
+    unit_id: Uuid,
+    block_id: Uuid,
+    parameter_id: Uuid,
+    value: f64,
+    is_active: bool, // inactive or excluded units are not used as peers
+    min_value: Option<f64>,
+    max_value: Option<f64>,
+    threshold_percent: Option<f64>,
+    previous_value: Option<f64>,
+    previous_session: Option<String>,
+}
+
 impl MonitoringService {
     pub async fn create_session(
         pool: &PgPool,
@@ -796,31 +1174,265 @@ impl MonitoringService {
         req: SubmitMonitoringDataRequest,
         created_by: Uuid,
     ) -> Result<MonitoringData, AppError> {
-        let data_id = Uuid::new_v4();
+        let mut data = Self::submit_batch(pool, vec![req], created_by).await?;
+        Ok(data.remove(0))
+    }
 
-        let data: MonitoringData = sqlx::query_as(
-            r#"
-            INSERT INTO monitoring_data (
-                id, session_id, unit_id, parameter_id, numeric_value,
-                text_value, observation_notes, recorded_by
+    /// Inserts values and re-checks their blocks for outliers in one
+    /// transaction, so a failed scan leaves nothing behind. New values change
+    /// their block peers' statistics and the jump check of each unit's next
+    /// session, so both are scanned once for the whole batch.
+    pub async fn submit_batch(
+        pool: &PgPool,
+        reqs: Vec<SubmitMonitoringDataRequest>,
+        created_by: Uuid,
+    ) -> Result<Vec<MonitoringData>, AppError> {
+        let mut tx = pool.begin().await.map_err(|e| AppError::Database(e.to_string()))?;
+
+        let mut inserted: Vec<MonitoringData> = Vec::with_capacity(reqs.len());
+        for req in reqs {
+            let data: MonitoringData = sqlx::query_as(
+                r#"
+                INSERT INTO monitoring_data (
+                    id, session_id, unit_id, parameter_id, numeric_value,
+                    text_value, observation_notes, recorded_by
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                RETURNING *
+                "#
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-            RETURNING *
+            .bind(Uuid::new_v4())
+            .bind(req.session_id)
+            .bind(req.unit_id)
+            .bind(req.parameter_id)
+            .bind(req.numeric_value)
+            .bind(&req.text_value)
+            .bind(&req.observation_notes)
+            .bind(created_by)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| AppError::Database(e.to_string()))?;
+            inserted.push(data);
+        }
+
+        let mut sessions: Vec<Uuid> = inserted.iter().map(|d| d.session_id).collect();
+        sessions.sort();
+        sessions.dedup();
+        let mut flagged: Vec<FlaggedValue> = Vec::new();
+        for session_id in sessions {
+            let written: Vec<&MonitoringData> = inserted.iter().filter(|d| d.session_id == session_id).collect();
+            let unit_ids: Vec<Uuid> = written.iter().map(|d| d.unit_id).collect();
+            let parameter_ids: Vec<Uuid> = written.iter().map(|d| d.parameter_id).collect();
+
+            let scan = Self::scan_session(&mut tx, session_id, Some((&unit_ids, &parameter_ids))).await?;
+            flagged.extend(scan.flagged);
+
+            // The next session of each written unit and parameter now
+            // compares against these values
+            let next_sessions: Vec<(Uuid,)> = sqlx::query_as(
+                r#"
+                SELECT DISTINCT nxt.session_id
+                FROM monitoring_sessions cur
+                CROSS JOIN UNNEST($2::uuid[], $3::uuid[]) AS w(unit_id, parameter_id)
+                CROSS JOIN LATERAL (
+                    SELECT nd.session_id
+                    FROM monitoring_data nd
+                    JOIN monitoring_sessions ns ON nd.session_id = ns.id
+                    WHERE nd.unit_id = w.unit_id
+                    AND nd.parameter_id = w.parameter_id
+                    AND ns.project_id = cur.project_id
+                    AND ns.scheduled_date > cur.scheduled_date
+                    AND nd.numeric_value IS NOT NULL
+                    ORDER BY ns.scheduled_date
+                    LIMIT 1
+                ) nxt
+                WHERE cur.id = $1
+                "#
+            )
+            .bind(session_id)
+            .bind(&unit_ids)
+            .bind(&parameter_ids)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| AppError::Database(e.to_string()))?;
+            for (next_session,) in next_sessions {
+                Self::scan_session(&mut tx, next_session, Some((&unit_ids, &parameter_ids))).await?;
+            }
+        }
+
+        tx.commit().await.map_err(|e| AppError::Database(e.to_string()))?;
+
+        for data in &mut inserted {
+            if let Some(flag) = flagged.iter().find(|f| f.data_id == data.id) {
+                data.is_outlier = true;
+                data.outlier_reason = Some(flag.reason.clone());
+                data.outlier_source = Some("scan".to_string());
+            }
+        }
+
+        Ok(inserted)
+    }
+
+    /// Flags numeric values of a session as outliers and clears flags from
+    /// earlier scans that no longer apply; flags set by a reviewer are left
+    /// as they are. A value is flagged when it lies outside the parameter's
+    /// min/max, deviates from the other active units of its block by more
+    /// than `outlier_threshold_percent` or by more than
+    /// `outlier_std_deviation` (system config) standard deviations, or
+    /// changed by more than `outlier_threshold_percent` since the unit's
+    /// previous session.
+    pub async fn scan_outliers(pool: &PgPool, session_id: Uuid) -> Result<OutlierScanResult, AppError> {
+        let mut tx = pool.begin().await.map_err(|e| AppError::Database(e.to_string()))?;
+        let result = Self::scan_session(&mut tx, session_id, None).await?;
+        tx.commit().await.map_err(|e| AppError::Database(e.to_string()))?;
+        Ok(result)
+    }
+
+    /// `scope` limits the scan to the blocks of some units and to some
+    /// parameters.
+    async fn scan_session(
+        conn: &mut PgConnection,
+        session_id: Uuid,
+        scope: Option<(&[Uuid], &[Uuid])>,
+    ) -> Result<OutlierScanResult, AppError> {
+        let rows: Vec<OutlierScanRow> = sqlx::query_as(
+            r#"
+            SELECT
+                md.id,
+                md.unit_id,
+                eu.block_id,
+                md.parameter_id,
+                md.numeric_value::float8 as value,
+                COALESCE(eu.is_active, true) AND eu.excluded_reason IS NULL as is_active,
+                mp.min_value::float8 as min_value,
+                mp.max_value::float8 as max_value,
+                mp.outlier_threshold_percent::float8 as threshold_percent,
+                prev.value as previous_value,
+                prev.session_code as previous_session
+            FROM monitoring_data md
+            JOIN experimental_units eu ON md.unit_id = eu.id
+            JOIN monitoring_parameters mp ON md.parameter_id = mp.id
+            JOIN monitoring_sessions ms ON md.session_id = ms.id
+            LEFT JOIN LATERAL (
+                SELECT pd.numeric_value::float8 as value, ps.session_code
+                FROM monitoring_data pd
+                JOIN monitoring_sessions ps ON pd.session_id = ps.id
+                WHERE pd.unit_id = md.unit_id
+                AND pd.parameter_id = md.parameter_id
+                AND ps.project_id = ms.project_id
+                AND ps.scheduled_date < ms.scheduled_date
+                AND pd.numeric_value IS NOT NULL
+                ORDER BY ps.scheduled_date DESC
+                LIMIT 1
+            ) prev ON TRUE
+            WHERE md.session_id = $1
+            AND md.numeric_value IS NOT NULL
+            AND ($2::uuid[] IS NULL OR eu.block_id IN (SELECT block_id FROM experimental_units WHERE id = ANY($2)))
+            AND ($3::uuid[] IS NULL OR md.parameter_id = ANY($3))
             "#
         )
-        .bind(data_id)
-        .bind(req.session_id)
-        .bind(req.unit_id)
-        .bind(req.parameter_id)
-        .bind(req.numeric_value)
-        .bind(&req.text_value)
-        .bind(&req.observation_notes)
-        .bind(created_by)
-        .fetch_one(pool)
+        .bind(session_id)
+        .bind(scope.map(|(unit_ids, _)| unit_ids))
+        .bind(scope.map(|(_, parameter_ids)| parameter_ids))
+        .fetch_all(&mut *conn)
         .await
         .map_err(|e| AppError::Database(e.to_string()))?;
 
-        Ok(data)
+        let std_limit: Option<(String,)> = sqlx::query_as(
+            "SELECT config_value FROM system_config WHERE config_key = 'outlier_std_deviation'"
+        )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+        let std_limit = std_limit.and_then(|(v,)| v.parse::<f64>().ok()).unwrap_or(2.0);
+
+        let reasons = Self::outlier_reasons(&rows, std_limit);
+
+        sqlx::query(
+            r#"
+            UPDATE monitoring_data md SET
+                is_outlier = u.reason IS NOT NULL,
+                outlier_reason = u.reason,
+                outlier_source = CASE WHEN u.reason IS NULL THEN NULL ELSE 'scan' END
+            FROM UNNEST($1::uuid[], $2::text[]) AS u(id, reason)
+            WHERE md.id = u.id
+            AND (NOT COALESCE(md.is_outlier, false) OR md.outlier_source = 'scan')
+            AND md.outlier_reason IS DISTINCT FROM u.reason
+            "#
+        )
+        .bind(rows.iter().map(|r| r.id).collect::<Vec<_>>())
+        .bind(&reasons)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        Ok(OutlierScanResult {
+            session_id,
+            scanned: rows.len(),
+            flagged: rows
+                .iter()
+                .zip(reasons)
+                .filter_map(|(row, reason)| {
+                    reason.map(|reason| FlaggedValue {
+                        data_id: row.id,
+                        unit_id: row.unit_id,
+                        parameter_id: row.parameter_id,
+                        value: row.value,
+                        reason,
+                    })
+                })
+                .collect(),
+        })
+    }
+
+    /// Outlier reason for each row of a session scan, or None when it passes
+    fn outlier_reasons(rows: &[OutlierScanRow], std_limit: f64) -> Vec<Option<String>> {
+        let mut reasons: Vec<Option<String>> = Vec::with_capacity(rows.len());
+        for row in rows {
+            let threshold = row.threshold_percent.unwrap_or(200.0);
+            let mut found = Vec::new();
+
+            if let Some(min) = row.min_value.filter(|min| row.value < *min) {
+                found.push(format!("Below minimum {}", min));
+            }
+            if let Some(max) = row.max_value.filter(|max| row.value > *max) {
+                found.push(format!("Above maximum {}", max));
+            }
+
+            // Leave-one-out statistics over active units so the value does
+            // not mask itself
+            let peers: Vec<f64> = rows
+                .iter()
+                .filter(|r| {
+                    r.id != row.id && r.is_active && r.block_id == row.block_id && r.parameter_id == row.parameter_id
+                })
+                .map(|r| r.value)
+                .collect();
+            if peers.len() >= 2 {
+                let mean = peers.iter().sum::<f64>() / peers.len() as f64;
+                let deviation = (row.value - mean).abs();
+                if mean != 0.0 && deviation / mean.abs() * 100.0 > threshold {
+                    found.push(format!("{:.0}% from block mean {:.2}", deviation / mean.abs() * 100.0, mean));
+                } else if peers.len() >= 3 {
+                    let sd = (peers.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (peers.len() - 1) as f64).sqrt();
+                    if sd > 0.0 && deviation > std_limit * sd {
+                        found.push(format!("{:.1} SD from block mean {:.2}", deviation / sd, mean));
+                    }
+                }
+            }
+
+            if let (Some(previous), Some(session)) = (row.previous_value, &row.previous_session) {
+                if previous != 0.0 {
+                    let change = (row.value - previous).abs() / previous.abs() * 100.0;
+                    if change > threshold {
+                        found.push(format!("{:.0}% change from {} ({})", change, previous, session));
+                    }
+                }
+            }
+
+            reasons.push((!found.is_empty()).then(|| found.join("; ")));
+        }
+        reasons
     }
 
     pub async fn verify_data(
@@ -952,3 +1564,380 @@ impl AuditService {
         Ok(logs)
     }
 }
+
+// ==============================================================================
+// ANALYSIS RESULT SERVICE
+// ==============================================================================
+
+pub struct AnalysisResultService;
+
+/// One monitoring value as it enters a run's data hash
+#[derive(Debug, FromRow)]
+struct SnapshotRow {
+    id: Uuid,
+    numeric_value: Option<String>,
+    text_value: Option<String>,
+    is_outlier: bool,
+    is_active: bool,
+    is_excluded: bool,
+}
+
+impl AnalysisResultService {
+    /// Open a read-only REPEATABLE READ transaction for an analysis to read
+    /// its data from, so the hash taken by `seal_snapshot` covers exactly the
+    /// rows the analysis saw
+    pub async fn begin_snapshot(pool: &PgPool) -> Result<Transaction<'static, Postgres>, AppError> {
+        let mut tx = pool.begin().await.map_err(|e| AppError::Database(e.to_string()))?;
+
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::Database(e.to_string()))?;
+
+        Ok(tx)
+    }
+
+    /// Hash the data a run read inside the snapshot it read it from, then
+    /// end the snapshot
+    pub async fn seal_snapshot(
+        mut snapshot: Transaction<'static, Postgres>,
+        run: &mut NewAnalysisRun,
+    ) -> Result<(), AppError> {
+        let data_hash =
+            Self::snapshot_hash(&mut *snapshot, &run.project_ids(), &run.parameter_ids, &run.session_ids).await?;
+        snapshot.commit().await.map_err(|e| AppError::Database(e.to_string()))?;
+        run.data_hash = Some(data_hash);
+        Ok(())
+    }
+
+    /// Store a run as the next version of its analysis type for the project.
+    /// Locking the project rows serialises version numbering and waits for
+    /// in-flight monitoring writes, which share-lock the project. A run whose
+    /// data changed after its snapshot is stored stale; later writes find the
+    /// stored row through the staleness triggers.
+    pub async fn record(
+        pool: &PgPool,
+        run: NewAnalysisRun,
+        user_id: Uuid,
+    ) -> Result<AnalysisResult, AppError> {
+        let mut tx = pool.begin().await.map_err(|e| AppError::Database(e.to_string()))?;
+
+        let project_ids = run.project_ids();
+        sqlx::query("SELECT id FROM projects WHERE id = ANY($1) ORDER BY id FOR NO KEY UPDATE")
+            .bind(&project_ids)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AppError::Database(e.to_string()))?;
+
+        let is_stale = match &run.data_hash {
+            Some(data_hash) => {
+                let current =
+                    Self::snapshot_hash(&mut *tx, &project_ids, &run.parameter_ids, &run.session_ids).await?;
+                current != *data_hash
+            }
+            None => false,
+        };
+
+        let result: AnalysisResult = sqlx::query_as(
+            r#"
+            INSERT INTO analysis_results (
+                id, project_id, analysis_type, analysis_name, input_parameters, results,
+                ai_model_used, ai_prompt, ai_insights, ai_recommendations, generated_by,
+                version, engine_version, data_hash, related_project_ids, parameter_ids, session_ids,
+                is_stale, stale_since
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11,
+                (SELECT COALESCE(MAX(version), 0) + 1 FROM analysis_results
+                 WHERE project_id = $2 AND analysis_type = $3),
+                $12, $13, $14, $15, $16,
+                $17, CASE WHEN $17 THEN NOW() END
+            )
+            RETURNING *
+            "#
+        )
+        .bind(Uuid::new_v4())
+        .bind(run.project_id)
+        .bind(&run.analysis_type)
+        .bind(&run.analysis_name)
+        .bind(&run.input_parameters)
+        .bind(&run.results)
+        .bind(&run.ai_model_used)
+        .bind(&run.ai_prompt)
+        .bind(&run.ai_insights)
+        .bind(&run.ai_recommendations)
+        .bind(user_id)
+        .bind(env!("CARGO_PKG_VERSION"))
+        .bind(&run.data_hash)
+        .bind(&run.related_project_ids)
+        .bind(&run.parameter_ids)
+        .bind(&run.session_ids)
+        .bind(is_stale)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        tx.commit().await.map_err(|e| AppError::Database(e.to_string()))?;
+
+        Ok(result)
+    }
+
+    /// SHA-256 over every monitoring value a run could have read, including
+    /// the outlier flags and unit exclusions that decide which rows are used
+    pub async fn snapshot_hash<'e, E: sqlx::PgExecutor<'e>>(
+        executor: E,
+        project_ids: &[Uuid],
+        parameter_ids: &[Uuid],
+        session_ids: &[Uuid],
+    ) -> Result<String, AppError> {
+        let rows: Vec<SnapshotRow> = sqlx::query_as(
+            r#"
+            SELECT md.id, md.numeric_value::TEXT AS numeric_value, md.text_value,
+                   COALESCE(md.is_outlier, false) AS is_outlier,
+                   COALESCE(eu.is_active, true) AS is_active,
+                   eu.excluded_reason IS NOT NULL AS is_excluded
+            FROM monitoring_data md
+            JOIN monitoring_sessions ms ON md.session_id = ms.id
+            JOIN experimental_units eu ON md.unit_id = eu.id
+            WHERE ms.project_id = ANY($1)
+            AND (cardinality($2::UUID[]) = 0 OR md.parameter_id = ANY($2))
+            AND (cardinality($3::UUID[]) = 0 OR md.session_id = ANY($3))
+            ORDER BY md.id
+            "#
+        )
+        .bind(project_ids)
+        .bind(parameter_ids)
+        .bind(session_ids)
+        .fetch_all(executor)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        Ok(Self::digest(&rows))
+    }
+
+    fn digest(rows: &[SnapshotRow]) -> String {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        for row in rows {
+            hasher.update(format!(
+                "{}|{}|{}|{}|{}|{}\n",
+                row.id,
+                row.numeric_value.as_deref().unwrap_or_default(),
+                row.text_value.as_deref().unwrap_or_default(),
+                row.is_outlier,
+                row.is_active,
+                row.is_excluded
+            ));
+        }
+
+        format!("{:x}", hasher.finalize())
+    }
+
+    pub async fn list(
+        pool: &PgPool,
+        project_id: Uuid,
+        query: &AnalysisResultQuery,
+    ) -> Result<Vec<AnalysisResult>, AppError> {
+        let results: Vec<AnalysisResult> = sqlx::query_as(
+            r#"
+            SELECT * FROM analysis_results
+            WHERE project_id = $1
+            AND ($2::TEXT IS NULL OR analysis_type = $2)
+            AND ($3 OR is_valid)
+            ORDER BY generated_at DESC
+            "#
+        )
+        .bind(project_id)
+        .bind(&query.analysis_type)
+        .bind(query.include_invalid.unwrap_or(false))
+        .fetch_all(pool)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        Ok(results)
+    }
+
+    /// Fetch a stored run, re-hashing its data so changes the triggers cannot
+    /// see still mark it stale
+    pub async fn get(pool: &PgPool, id: Uuid) -> Result<AnalysisResult, AppError> {
+        let result: AnalysisResult = sqlx::query_as("SELECT * FROM analysis_results WHERE id = $1")
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| AppError::Database(e.to_string()))?
+            .ok_or_else(|| AppError::NotFound("Analysis result not found".to_string()))?;
+
+        if result.is_stale || result.data_hash.is_none() {
+            return Ok(result);
+        }
+
+        let project_ids: Vec<Uuid> =
+            std::iter::once(result.project_id).chain(result.related_project_ids.iter().copied()).collect();
+        let current = Self::snapshot_hash(pool, &project_ids, &result.parameter_ids, &result.session_ids).await?;
+        if result.data_hash.as_deref() == Some(current.as_str()) {
+            return Ok(result);
+        }
+
+        let result: AnalysisResult = sqlx::query_as(
+            "UPDATE analysis_results SET is_stale = true, stale_since = NOW() WHERE id = $1 RETURNING *"
+        )
+        .bind(id)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        Ok(result)
+    }
+
+    pub async fn invalidate(
+        pool: &PgPool,
+        id: Uuid,
+        req: InvalidateAnalysisRequest,
+        user_id: Uuid,
+    ) -> Result<AnalysisResult, AppError> {
+        req.validate().map_err(|e| AppError::Validation(e.to_string()))?;
+
+        let result: AnalysisResult = sqlx::query_as(
+            r#"
+            UPDATE analysis_results SET
+                is_valid = false,
+                invalidated_reason = $2,
+                invalidated_at = NOW(),
+                invalidated_by = $3
+            WHERE id = $1
+            RETURNING *
+            "#
+        )
+        .bind(id)
+        .bind(&req.reason)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound("Analysis result not found".to_string()))?;
+
+        AuditService::log(
+            pool,
+            Some(user_id),
+            "INVALIDATE",
+            "analysis_result",
+            Some(id),
+            None,
+            Some(serde_json::json!({"reason": req.reason, "version": result.version})),
+            None,
+            None,
+        )
+        .await?;
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(design: ExperimentDesign, seed: u64) -> Vec<(usize, usize, usize, usize)> {
+        LayoutService::randomize(&design, 3, 2, seed)
+            .iter()
+            .map(|p| (p.treatment, p.replication, p.row, p.column))
+            .collect()
+    }
+
+    #[test]
+    fn layout_seed_reproduces_known_layout() {
+        // (treatment, replication, row, column); a change here means stored
+        // seeds no longer reproduce the layouts generated from them
+        assert_eq!(
+            layout(ExperimentDesign::Ral, 42),
+            [(0, 1, 1, 1), (1, 1, 1, 2), (2, 1, 1, 3), (2, 2, 2, 1), (1, 2, 2, 2), (0, 2, 2, 3)]
+        );
+        assert_eq!(
+            layout(ExperimentDesign::Rak, 42),
+            [(2, 1, 1, 1), (1, 1, 1, 2), (0, 1, 1, 3), (2, 2, 2, 1), (1, 2, 2, 2), (0, 2, 2, 3)]
+        );
+    }
+
+    #[test]
+    fn rak_layout_has_every_treatment_once_per_replication() {
+        let plots = LayoutService::randomize(&ExperimentDesign::Rak, 5, 4, 7);
+        for replication in 1..=4 {
+            let mut treatments: Vec<usize> =
+                plots.iter().filter(|p| p.replication == replication).map(|p| p.treatment).collect();
+            treatments.sort();
+            assert_eq!(treatments, [0, 1, 2, 3, 4]);
+        }
+    }
+
+    fn snapshot_row(value: &str, is_outlier: bool) -> SnapshotRow {
+        SnapshotRow {
+            id: Uuid::from_u128(1),
+            numeric_value: Some(value.to_string()),
+            text_value: None,
+            is_outlier,
+            is_active: true,
+            is_excluded: false,
+        }
+    }
+
+    #[test]
+    fn snapshot_hash_covers_values_and_flags() {
+        // SHA-256 of "00000000-0000-0000-0000-000000000001|12.5||false|true|false\n";
+        // a change here marks every stored run stale on its next read
+        assert_eq!(
+            AnalysisResultService::digest(&[snapshot_row("12.5", false)]),
+            "23c2be5c345a5b3f20cceb30b55126d88acf705b6356d5975526aeeb1eff81be"
+        );
+        assert_ne!(
+            AnalysisResultService::digest(&[snapshot_row("12.5", false)]),
+            AnalysisResultService::digest(&[snapshot_row("12.5", true)])
+        );
+        assert_ne!(
+            AnalysisResultService::digest(&[snapshot_row("12.5", false)]),
+            AnalysisResultService::digest(&[snapshot_row("12.6", false)])
+        );
+    }
+
+    fn scan_row(unit: u128, value: f64, is_active: bool, previous_value: Option<f64>) -> OutlierScanRow {
+        OutlierScanRow {
+            id: Uuid::from_u128(100 + unit),
+            unit_id: Uuid::from_u128(unit),
+            block_id: Uuid::from_u128(1),
+            parameter_id: Uuid::from_u128(2),
+            value,
+            is_active,
+            min_value: Some(0.0),
+            max_value: None,
+            threshold_percent: None,
+            previous_value,
+            previous_session: previous_value.map(|_| "S1".to_string()),
+        }
+    }
+
+    #[test]
+    fn outlier_scan_flags_known_values_against_active_peers() {
+        let rows = [
+            scan_row(1, 10.0, true, Some(4.0)),
+            scan_row(2, 11.0, true, Some(3.0)),
+            scan_row(3, 10.5, true, None),
+            scan_row(4, 30.0, true, None),
+            scan_row(5, 100.0, false, None),
+            scan_row(6, -1.0, false, None),
+        ];
+        let reasons = MonitoringService::outlier_reasons(&rows, 2.0);
+        // Unit 4 only stands out because the inactive units are not its
+        // peers; they are still checked against the active units
+        assert_eq!(
+            reasons,
+            [
+                None,
+                Some("267% change from 3 (S1)".to_string()),
+                None,
+                Some("39.0 SD from block mean 10.50".to_string()),
+                Some("550% from block mean 15.38".to_string()),
+                Some("Below minimum 0".to_string()),
+            ]
+        );
+    }
+}